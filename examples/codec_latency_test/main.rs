@@ -1,5 +1,8 @@
-use std::path::PathBuf;
-
+use log::info;
+use paper_experiments::{
+    codecs::{decoder::VideoDecoder, encoder::VideoEncoder, settings::EncoderSettings, Codec},
+    processors::quality::quality_meter,
+};
 use remotia::{
     error::DropReason,
     processors::{
@@ -13,16 +16,22 @@ use remotia_core_capturers::scrap::ScrapFrameCapturer;
 use remotia_core_codecs::yuv420p::encoder::RGBAToYUV420PConverter;
 use remotia_core_loggers::{
     csv::serializer::CSVFrameDataSerializer, errors::ConsoleDropReasonLogger,
-    frame_dump::RawFrameDumper,
+    stats::ConsoleAverageStatsLogger,
 };
-use remotia_ffmpeg_codecs::{decoders::h264::H264Decoder, encoders::x264::X264Encoder};
 use remotia_profilation_utils::time::add::TimestampAdder;
 
 #[tokio::main]
 async fn main() -> std::io::Result<()> {
     env_logger::init();
 
-    let x264opts = "keyint=16";
+    // e.g. `cargo run --example codec_latency_test vp9`
+    let args: Vec<String> = std::env::args().collect();
+    let codec: Codec = args.get(1).map_or("x264", String::as_str).parse()?;
+    let encoder_settings = EncoderSettings::default();
+
+    let tick_interval = 500;
+
+    info!("Testing {}", codec.name());
 
     let capturer = ScrapFrameCapturer::new_from_primary();
     let width = capturer.width();
@@ -35,6 +44,8 @@ async fn main() -> std::io::Result<()> {
     let cbcb_pool = BuffersPool::new("cb_channel_buffer", 128, (width * height) / 4);
     let efb_pool = BuffersPool::new("encoded_frame_buffer", 128, buffer_size);
 
+    let (quality_reference_keeper, quality_meter) = quality_meter(width, height, 128)?;
+
     let error_handling_pipeline = AscodePipeline::new()
         .tag("ErrorsHandler")
         .link(
@@ -61,11 +72,12 @@ async fn main() -> std::io::Result<()> {
         .tag("Main")
         .link(
             Component::new()
-                .append(Ticker::new(500))
+                .append(Ticker::new(tick_interval))
                 .append(TimestampAdder::new("capture_timestamp"))
                 .append(rfb_pool.borrower())
                 .append(OnErrorSwitch::new(&error_handling_pipeline))
-                .append(capturer),
+                .append(capturer)
+                .append(quality_reference_keeper),
         )
         .link(
             Component::new()
@@ -77,16 +89,13 @@ async fn main() -> std::io::Result<()> {
                 .append(RGBAToYUV420PConverter::new())
                 .append(efb_pool.borrower())
                 .append(OnErrorSwitch::new(&error_handling_pipeline))
-                .append(X264Encoder::new(
-                    buffer_size,
-                    width as i32,
-                    height as i32,
-                    &x264opts,
-                ))
-                .append(RawFrameDumper::new(
-                    "raw_frame_buffer",
-                    PathBuf::from("./encoded_frames_dump/"),
-                ))
+                .append(VideoEncoder::new(
+                    codec,
+                    width,
+                    height,
+                    (1000 / tick_interval) as i32,
+                    &encoder_settings,
+                )?)
                 .append(rfb_pool.redeemer())
                 .append(ycb_pool.redeemer())
                 .append(crcb_pool.redeemer())
@@ -98,14 +107,27 @@ async fn main() -> std::io::Result<()> {
             Component::new()
                 .append(rfb_pool.borrower())
                 .append(OnErrorSwitch::new(&error_handling_pipeline))
-                .append(H264Decoder::new())
+                .append(VideoDecoder::new(codec, width, height)?)
                 .append(efb_pool.redeemer())
-                .append(RawFrameDumper::new(
-                    "raw_frame_buffer",
-                    PathBuf::from("./decoded_frames_dump/"),
-                ))
-                .append(rfb_pool.redeemer())
-                .append(OnErrorSwitch::new(&error_handling_pipeline)),
+                .append(OnErrorSwitch::new(&error_handling_pipeline))
+                .append(quality_meter)
+                .append(rfb_pool.redeemer()),
+        )
+        .link(
+            Component::new()
+                .append(KeyChecker::new("psnr"))
+                .append(
+                    ConsoleAverageStatsLogger::new()
+                        .header("--- Quality (1/10000 dB and 1/10000)")
+                        .log("psnr")
+                        .log("ssim"),
+                )
+                .append(
+                    CSVFrameDataSerializer::new("quality.csv")
+                        .log("capture_timestamp")
+                        .log("psnr")
+                        .log("ssim"),
+                ),
         )
         .bind();
 
@@ -1,9 +1,3 @@
-echo '### Server FPS'
-python3 fps.py $1/server.csv
-echo '### Client FPS'
-python3 fps.py $1/client.csv
-
-echo '### Server analytics'
-python3 analytics.py $1/server.csv
-echo '### Client analytics'
-python3 analytics.py $1/client.csv
+# Summarizes server.csv and client.csv of a results directory, pass --json for machine-readable output
+cargo run --release --quiet --bin analyze -- "$@"
+cargo run --release --quiet --bin analyze -- --join "$@"
@@ -0,0 +1,210 @@
+use std::{collections::BTreeMap, fmt, path::Path};
+
+use serde::Serialize;
+
+use super::FrameLog;
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum FrameFate {
+    DroppedOnServer,
+    // Sent by the server but neither rendered nor dropped by the client
+    LostInTransit,
+    DroppedOnClient,
+    Rendered,
+}
+
+impl FrameFate {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::DroppedOnServer => "dropped_on_server",
+            Self::LostInTransit => "lost_in_transit",
+            Self::DroppedOnClient => "dropped_on_client",
+            Self::Rendered => "rendered",
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct FrameLifecycle {
+    pub key: u128,
+    pub fate: FrameFate,
+    // Where the frame died, from the drop_stage column of the drops logs when available
+    pub stage: Option<String>,
+    pub reason: Option<String>,
+    pub frame_delay: Option<f64>,
+}
+
+// The four logs of one run, joined on frame_id when every log has it and on capture_timestamp
+// otherwise
+pub struct RunLogs {
+    pub server: FrameLog,
+    pub server_drops: FrameLog,
+    pub client: FrameLog,
+    pub client_drops: FrameLog,
+}
+
+impl RunLogs {
+    pub fn load(directory: &Path) -> std::io::Result<Self> {
+        let load = |name: &str| FrameLog::load(&directory.join(name));
+
+        Ok(Self {
+            server: load("server.csv")?,
+            server_drops: load("server_drops.csv")?,
+            client: load("client.csv")?,
+            client_drops: load("client_drops.csv")?,
+        })
+    }
+
+    pub fn key_column(&self) -> &'static str {
+        let logs = [&self.server, &self.server_drops, &self.client, &self.client_drops];
+        if logs.iter().all(|log| log.has_column("frame_id")) {
+            "frame_id"
+        } else {
+            "capture_timestamp"
+        }
+    }
+
+    // Frames captured before the first or after the last frame seen by the client are left out,
+    // since the client was not connected yet or had already stopped
+    pub fn lifecycles(&self) -> Vec<FrameLifecycle> {
+        let key_column = self.key_column();
+        let mut frames: BTreeMap<u128, FrameLifecycle> = BTreeMap::new();
+
+        let mut record = |log: &FrameLog, fate: FrameFate| {
+            for row in 0..log.len() {
+                let key = match log.value(row, key_column).and_then(|key| key.parse().ok()) {
+                    Some(key) => key,
+                    None => continue,
+                };
+
+                let dropped =
+                    matches!(fate, FrameFate::DroppedOnServer | FrameFate::DroppedOnClient);
+                let lifecycle = FrameLifecycle {
+                    key,
+                    fate,
+                    stage: if dropped {
+                        log.value(row, "drop_stage").map(str::to_string)
+                    } else {
+                        None
+                    },
+                    reason: log.value(row, "drop_reason").map(str::to_string),
+                    frame_delay: log
+                        .value(row, "frame_delay")
+                        .and_then(|delay| delay.parse().ok()),
+                };
+
+                // Logs are recorded in lifecycle order, so later stages take precedence
+                frames.insert(key, lifecycle);
+            }
+        };
+
+        record(&self.server, FrameFate::LostInTransit);
+        record(&self.server_drops, FrameFate::DroppedOnServer);
+        record(&self.client_drops, FrameFate::DroppedOnClient);
+        record(&self.client, FrameFate::Rendered);
+
+        let client_keys: Vec<u128> = frames
+            .values()
+            .filter(|frame| frame.fate >= FrameFate::DroppedOnClient)
+            .map(|frame| frame.key)
+            .collect();
+        let (first, last) = match (client_keys.first(), client_keys.last()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => return frames.into_values().collect(),
+        };
+
+        frames
+            .into_values()
+            .filter(|frame| (first..=last).contains(&frame.key))
+            .collect()
+    }
+}
+
+#[derive(Serialize)]
+pub struct LossSummary {
+    pub key_column: String,
+    pub frames: usize,
+    pub rendered: usize,
+    pub end_to_end_loss: f64,
+    pub by_fate: BTreeMap<FrameFate, usize>,
+    // "<fate>/<stage>" for drops, so that server and client stages sharing a name stay apart
+    pub by_stage: BTreeMap<String, usize>,
+}
+
+impl LossSummary {
+    pub fn new(key_column: &str, lifecycles: &[FrameLifecycle]) -> Self {
+        let mut by_fate = BTreeMap::new();
+        let mut by_stage = BTreeMap::new();
+
+        for frame in lifecycles {
+            *by_fate.entry(frame.fate).or_default() += 1;
+
+            if frame.fate != FrameFate::Rendered {
+                let stage = match &frame.stage {
+                    Some(stage) => format!("{}/{}", frame.fate.as_str(), stage),
+                    None => frame.fate.as_str().to_string(),
+                };
+                *by_stage.entry(stage).or_default() += 1;
+            }
+        }
+
+        let rendered = by_fate.get(&FrameFate::Rendered).copied().unwrap_or(0);
+        let end_to_end_loss = if lifecycles.is_empty() {
+            0.0
+        } else {
+            1.0 - rendered as f64 / lifecycles.len() as f64
+        };
+
+        Self {
+            key_column: key_column.to_string(),
+            frames: lifecycles.len(),
+            rendered,
+            end_to_end_loss,
+            by_fate,
+            by_stage,
+        }
+    }
+}
+
+impl fmt::Display for LossSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "### End-to-end loss (joined on {})", self.key_column)?;
+        writeln!(
+            f,
+            "{} frames, {} rendered, {:.2}% lost",
+            self.frames,
+            self.rendered,
+            self.end_to_end_loss * 100.0
+        )?;
+
+        for (stage, count) in &self.by_stage {
+            writeln!(
+                f,
+                "  {:<40} {:>8} {:>8.2}%",
+                stage,
+                count,
+                *count as f64 * 100.0 / self.frames as f64
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+pub fn write_lifecycles(path: &Path, lifecycles: &[FrameLifecycle]) -> std::io::Result<()> {
+    let mut writer = csv::Writer::from_path(path)?;
+    writer.write_record(["key", "fate", "stage", "reason", "frame_delay"])?;
+
+    for frame in lifecycles {
+        writer.write_record([
+            frame.key.to_string(),
+            frame.fate.as_str().to_string(),
+            frame.stage.clone().unwrap_or_default(),
+            frame.reason.clone().unwrap_or_default(),
+            frame.frame_delay.map(|delay| delay.to_string()).unwrap_or_default(),
+        ])?;
+    }
+
+    writer.flush()
+}
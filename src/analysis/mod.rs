@@ -0,0 +1,457 @@
+pub mod lifecycle;
+
+use std::{collections::BTreeMap, fmt, path::Path};
+
+use serde::Serialize;
+
+// Absolute times say nothing on their own once aggregated
+const TIMESTAMP_SUFFIX: &str = "_timestamp";
+// Neither do the frame identifiers, e.g. frame_id, encoded_frame_id and replayed_frame_id
+const FRAME_ID_SUFFIX: &str = "frame_id";
+
+// Rows of a CSV written by CSVFrameDataSerializer, one per frame
+pub struct FrameLog {
+    headers: Vec<String>,
+    rows: Vec<csv::StringRecord>,
+}
+
+impl FrameLog {
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        let mut reader = csv::Reader::from_path(path)?;
+        let headers = reader.headers()?.iter().map(str::to_string).collect();
+        let rows = reader.records().collect::<Result<_, _>>()?;
+
+        Ok(Self { headers, rows })
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    pub fn headers(&self) -> &[String] {
+        &self.headers
+    }
+
+    // Values that do not parse as numbers, such as empty cells, are skipped
+    pub fn numeric_column(&self, name: &str) -> Option<Vec<f64>> {
+        let index = self.column_index(name)?;
+        Some(
+            self.rows
+                .iter()
+                .filter_map(|row| row.get(index)?.trim().parse().ok())
+                .collect(),
+        )
+    }
+
+    pub fn text_column(&self, name: &str) -> Option<Vec<&str>> {
+        let index = self.column_index(name)?;
+        Some(
+            self.rows
+                .iter()
+                .map(|row| row.get(index).unwrap_or_default())
+                .collect(),
+        )
+    }
+
+    pub fn has_column(&self, name: &str) -> bool {
+        self.column_index(name).is_some()
+    }
+
+    pub fn value(&self, row: usize, column: &str) -> Option<&str> {
+        let value = self.rows.get(row)?.get(self.column_index(column)?)?.trim();
+        if value.is_empty() {
+            None
+        } else {
+            Some(value)
+        }
+    }
+
+    fn column_index(&self, name: &str) -> Option<usize> {
+        self.headers.iter().position(|header| header == name)
+    }
+}
+
+#[derive(Serialize)]
+pub struct FpsSummary {
+    pub mean: f64,
+    // Over the seconds with at least one frame only, as fps.py
+    pub active_mean: f64,
+    pub min: u64,
+    pub max: u64,
+    // Frames captured in each second since the first frame
+    pub per_second: Vec<u64>,
+}
+
+#[derive(Serialize)]
+pub struct DropSummary {
+    pub delivered: usize,
+    pub dropped: usize,
+    pub drop_rate: f64,
+    // Only available when the drops log has a drop_reason column
+    pub by_reason: Option<BTreeMap<String, ReasonSummary>>,
+}
+
+#[derive(Serialize)]
+pub struct ReasonSummary {
+    pub dropped: usize,
+    pub drop_rate: f64,
+}
+
+#[derive(Serialize)]
+pub struct ColumnSummary {
+    pub column: String,
+    pub count: usize,
+    pub mean: f64,
+    // Mean of the mean of every second with values, as analytics.py, weighing the seconds equally
+    // whatever their frame rate
+    pub per_second_mean: Option<f64>,
+    pub median: f64,
+    pub p95: f64,
+    pub p99: f64,
+    pub std_dev: f64,
+}
+
+#[derive(Serialize)]
+pub struct RunSummary {
+    pub name: String,
+    pub frames: usize,
+    pub fps: Option<FpsSummary>,
+    pub drops: Option<DropSummary>,
+    pub columns: Vec<ColumnSummary>,
+}
+
+impl RunSummary {
+    pub fn new(name: &str, stats: &FrameLog, drops: Option<&FrameLog>) -> Self {
+        let columns = stats
+            .headers()
+            .iter()
+            .filter(|header| {
+                !header.ends_with(TIMESTAMP_SUFFIX) && !header.ends_with(FRAME_ID_SUFFIX)
+            })
+            .filter_map(|header| {
+                let mut summary = summarize_column(header, stats.numeric_column(header)?)?;
+                summary.per_second_mean = per_second_mean(stats, header);
+                Some(summary)
+            })
+            .collect();
+
+        Self {
+            name: name.to_string(),
+            frames: stats.len(),
+            fps: stats
+                .numeric_column("capture_timestamp")
+                .and_then(|timestamps| summarize_fps(&timestamps)),
+            drops: drops.map(|drops| summarize_drops(stats.len(), drops)),
+            columns,
+        }
+    }
+}
+
+// Seconds without any frame count as zero FPS instead of being skipped, so that stalls show up
+fn summarize_fps(timestamps: &[f64]) -> Option<FpsSummary> {
+    let seconds: Vec<u64> = timestamps
+        .iter()
+        .map(|timestamp| (*timestamp / 1000.0) as u64)
+        .collect();
+    let first = *seconds.iter().min()?;
+    let last = *seconds.iter().max()?;
+
+    let mut per_second = vec![0; (last - first + 1) as usize];
+    for second in seconds {
+        per_second[(second - first) as usize] += 1;
+    }
+
+    let frames = per_second.iter().sum::<u64>() as f64;
+    let active_seconds = per_second.iter().filter(|frames| **frames > 0).count();
+
+    Some(FpsSummary {
+        mean: frames / per_second.len() as f64,
+        active_mean: frames / active_seconds as f64,
+        min: *per_second.iter().min()?,
+        max: *per_second.iter().max()?,
+        per_second,
+    })
+}
+
+// Seconds without values are skipped, as pandas' resample().mean() leaves them NaN
+fn per_second_mean(stats: &FrameLog, column: &str) -> Option<f64> {
+    let parse = |row, column| stats.value(row, column)?.parse::<f64>().ok();
+
+    let mut seconds: BTreeMap<u64, (f64, usize)> = BTreeMap::new();
+    for row in 0..stats.len() {
+        let timestamp = parse(row, "capture_timestamp");
+        if let (Some(timestamp), Some(value)) = (timestamp, parse(row, column)) {
+            let (sum, count) = seconds.entry((timestamp / 1000.0) as u64).or_default();
+            *sum += value;
+            *count += 1;
+        }
+    }
+
+    if seconds.is_empty() {
+        return None;
+    }
+
+    let means = seconds.values().map(|(sum, count)| sum / *count as f64);
+    Some(means.sum::<f64>() / seconds.len() as f64)
+}
+
+fn summarize_drops(delivered: usize, drops: &FrameLog) -> DropSummary {
+    let total = delivered + drops.len();
+    let rate = |dropped: usize| {
+        if total == 0 {
+            0.0
+        } else {
+            dropped as f64 / total as f64
+        }
+    };
+
+    let by_reason = drops.text_column("drop_reason").map(|reasons| {
+        let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+        for reason in reasons {
+            *counts.entry(reason.to_string()).or_default() += 1;
+        }
+
+        counts
+            .into_iter()
+            .map(|(reason, dropped)| {
+                let summary = ReasonSummary {
+                    dropped,
+                    drop_rate: rate(dropped),
+                };
+                (reason, summary)
+            })
+            .collect()
+    });
+
+    DropSummary {
+        delivered,
+        dropped: drops.len(),
+        drop_rate: rate(drops.len()),
+        by_reason,
+    }
+}
+
+fn summarize_column(column: &str, mut values: Vec<f64>) -> Option<ColumnSummary> {
+    if values.is_empty() {
+        return None;
+    }
+
+    values.sort_by(|a, b| a.total_cmp(b));
+
+    let count = values.len();
+    let mean = values.iter().sum::<f64>() / count as f64;
+
+    // Sample standard deviation, as computed by pandas
+    let std_dev = if count > 1 {
+        let squares = values.iter().map(|value| (value - mean).powi(2)).sum::<f64>();
+        (squares / (count - 1) as f64).sqrt()
+    } else {
+        0.0
+    };
+
+    Some(ColumnSummary {
+        column: column.to_string(),
+        count,
+        mean,
+        per_second_mean: None,
+        median: percentile(&values, 0.5),
+        p95: percentile(&values, 0.95),
+        p99: percentile(&values, 0.99),
+        std_dev,
+    })
+}
+
+// Linear interpolation between the closest ranks of sorted values
+fn percentile(sorted: &[f64], quantile: f64) -> f64 {
+    let rank = quantile * (sorted.len() - 1) as f64;
+    let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64)
+}
+
+impl fmt::Display for RunSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "### {} ({} frames)", self.name, self.frames)?;
+
+        if let Some(fps) = &self.fps {
+            writeln!(
+                f,
+                "FPS: {:.2} mean ({:.2} over the seconds with frames), {} min, {} max over {} seconds",
+                fps.mean,
+                fps.active_mean,
+                fps.min,
+                fps.max,
+                fps.per_second.len()
+            )?;
+        }
+
+        if let Some(drops) = &self.drops {
+            writeln!(
+                f,
+                "Drop rate: {:.2}% ({} dropped, {} delivered)",
+                drops.drop_rate * 100.0,
+                drops.dropped,
+                drops.delivered
+            )?;
+
+            for (reason, summary) in drops.by_reason.iter().flatten() {
+                writeln!(
+                    f,
+                    "  {:<24} {:>8.2}% ({})",
+                    reason,
+                    summary.drop_rate * 100.0,
+                    summary.dropped
+                )?;
+            }
+        }
+
+        // mean is over the frames, second_mean over the per-second means as analytics.py
+        writeln!(
+            f,
+            "{:<28} {:>8} {:>12} {:>12} {:>12} {:>12} {:>12} {:>12}",
+            "column", "count", "mean", "second_mean", "median", "p95", "p99", "std_dev"
+        )?;
+
+        for column in &self.columns {
+            let per_second_mean = match column.per_second_mean {
+                Some(mean) => format!("{:.2}", mean),
+                None => "-".to_string(),
+            };
+
+            writeln!(
+                f,
+                "{:<28} {:>8} {:>12.2} {:>12} {:>12.2} {:>12.2} {:>12.2} {:>12.2}",
+                column.column,
+                column.count,
+                column.mean,
+                per_second_mean,
+                column.median,
+                column.p95,
+                column.p99,
+                column.std_dev
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    fn column<'a>(summary: &'a RunSummary, name: &str) -> Option<&'a ColumnSummary> {
+        summary.columns.iter().find(|column| column.column == name)
+    }
+
+    #[test]
+    fn summarizes_a_column() {
+        let summary = summarize_column("delay", (1..=10).rev().map(f64::from).collect()).unwrap();
+
+        assert_eq!(summary.count, 10);
+        assert_eq!(summary.mean, 5.5);
+        assert_eq!(summary.median, 5.5);
+        assert!((summary.p95 - 9.55).abs() < 1e-9);
+        assert!((summary.p99 - 9.91).abs() < 1e-9);
+        assert!((summary.std_dev - 3.027_650_354).abs() < 1e-9);
+
+        let single = summarize_column("delay", vec![4.0]).unwrap();
+        assert_eq!((single.median, single.p99, single.std_dev), (4.0, 4.0, 0.0));
+
+        assert!(summarize_column("delay", Vec::new()).is_none());
+    }
+
+    #[test]
+    fn aggregates_a_run() {
+        let directory = TempDir::new("analysis-run");
+        let stats = directory.write(
+            "client.csv",
+            "frame_id,capture_timestamp,frame_delay,decoding_time,codec\n\
+             0,1000,30,5,x264\n\
+             1,1500,50,,x264\n\
+             2,1900,40,7,x264\n\
+             3,3100,60,6,x264\n",
+        );
+        let drops = directory.write(
+            "client_drops.csv",
+            "capture_timestamp,drop_reason\n\
+             1200,StaleFrame\n\
+             2000,ConnectionError\n\
+             2100,StaleFrame\n",
+        );
+
+        let stats = FrameLog::load(&stats).unwrap();
+        let drops = FrameLog::load(&drops).unwrap();
+        let summary = RunSummary::new("run", &stats, Some(&drops));
+
+        assert_eq!(summary.frames, 4);
+
+        // Timestamps, identifiers and text columns are not aggregated, empty cells are skipped
+        assert!(column(&summary, "capture_timestamp").is_none());
+        assert!(column(&summary, "frame_id").is_none());
+        assert!(column(&summary, "codec").is_none());
+        assert_eq!(column(&summary, "frame_delay").unwrap().mean, 45.0);
+        assert_eq!(column(&summary, "decoding_time").unwrap().count, 3);
+
+        // The mean of the second with three frames weighs as much as the one with a single frame
+        let frame_delay = column(&summary, "frame_delay").unwrap();
+        assert_eq!(frame_delay.per_second_mean, Some(50.0));
+        let decoding_time = column(&summary, "decoding_time").unwrap();
+        assert_eq!(decoding_time.per_second_mean, Some(6.0));
+
+        // The second without frames counts as zero FPS
+        let fps = summary.fps.unwrap();
+        assert_eq!(fps.per_second, vec![3, 0, 1]);
+        assert_eq!((fps.min, fps.max), (0, 3));
+        assert!((fps.mean - 4.0 / 3.0).abs() < 1e-9);
+        assert_eq!(fps.active_mean, 2.0);
+
+        let drops = summary.drops.unwrap();
+        assert_eq!((drops.delivered, drops.dropped), (4, 3));
+        assert!((drops.drop_rate - 3.0 / 7.0).abs() < 1e-9);
+
+        let by_reason = drops.by_reason.unwrap();
+        assert_eq!(by_reason["StaleFrame"].dropped, 2);
+        assert_eq!(by_reason["ConnectionError"].dropped, 1);
+        assert!((by_reason["ConnectionError"].drop_rate - 1.0 / 7.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn aggregates_empty_logs() {
+        let directory = TempDir::new("analysis-empty");
+        let stats = directory.write("client.csv", "capture_timestamp,frame_delay\n");
+        let drops = directory.write("client_drops.csv", "capture_timestamp\n");
+
+        let stats = FrameLog::load(&stats).unwrap();
+        let drops = FrameLog::load(&drops).unwrap();
+        let summary = RunSummary::new("empty", &stats, Some(&drops));
+
+        assert_eq!(summary.frames, 0);
+        assert!(summary.fps.is_none());
+        assert!(summary.columns.is_empty());
+
+        let drops = summary.drops.unwrap();
+        assert_eq!(drops.drop_rate, 0.0);
+        // Older drops logs have no drop_reason column
+        assert!(drops.by_reason.is_none());
+    }
+
+    #[test]
+    fn reads_values_by_column() {
+        let directory = TempDir::new("analysis-values");
+        let log = directory.write("server.csv", "frame_id,note\n1, \n2,late\n");
+        let log = FrameLog::load(&log).unwrap();
+
+        assert_eq!(log.value(0, "note"), None);
+        assert_eq!(log.value(1, "note"), Some("late"));
+        assert_eq!(log.value(2, "note"), None);
+        assert_eq!(log.value(0, "missing"), None);
+        assert_eq!(log.numeric_column("frame_id"), Some(vec![1.0, 2.0]));
+        assert_eq!(log.text_column("note"), Some(vec![" ", "late"]));
+        assert!(log.numeric_column("missing").is_none());
+    }
+}
@@ -0,0 +1,106 @@
+use std::path::{Path, PathBuf};
+
+use paper_experiments::analysis::{
+    lifecycle::{write_lifecycles, LossSummary, RunLogs},
+    FrameLog, RunSummary,
+};
+
+// Summarizes the stats CSVs of one or more runs, e.g. `analyze results/srt_50ms` for the
+// server.csv and client.csv of an experiment, or `analyze --json client.csv`.
+// Drops are read from the sibling <name>_drops.csv when present.
+//
+// `analyze --join [--frames lifecycle.csv] results/srt_50ms` instead joins the server and client
+// logs of a run to account for every frame between capture and rendering.
+fn main() -> std::io::Result<()> {
+    let mut json = false;
+    let mut join = false;
+    let mut frames_path = None;
+    let mut paths = Vec::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--join" => join = true,
+            "--frames" => frames_path = args.next().map(PathBuf::from),
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+
+    if paths.is_empty() {
+        eprintln!("Usage: analyze [--json] <results directory or stats CSV>...");
+        eprintln!("       analyze --join [--json] [--frames <lifecycle.csv>] <results directory>");
+        std::process::exit(1);
+    }
+
+    if join {
+        return join_run(&paths[0], frames_path.as_deref(), json);
+    }
+
+    let mut summaries = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            for name in ["server", "client"] {
+                let stats_path = path.join(format!("{}.csv", name));
+                if stats_path.exists() {
+                    summaries.push(summarize(&stats_path)?);
+                }
+            }
+        } else {
+            summaries.push(summarize(&path)?);
+        }
+    }
+
+    if json {
+        print_json(&summaries)?;
+    } else {
+        for summary in summaries {
+            println!("{}", summary);
+        }
+    }
+
+    Ok(())
+}
+
+fn join_run(directory: &Path, frames_path: Option<&Path>, json: bool) -> std::io::Result<()> {
+    let logs = RunLogs::load(directory)?;
+    let lifecycles = logs.lifecycles();
+
+    if let Some(frames_path) = frames_path {
+        write_lifecycles(frames_path, &lifecycles)?;
+    }
+
+    let summary = LossSummary::new(logs.key_column(), &lifecycles);
+    if json {
+        print_json(&summary)?;
+    } else {
+        println!("{}", summary);
+    }
+
+    Ok(())
+}
+
+fn print_json<T: serde::Serialize>(value: &T) -> std::io::Result<()> {
+    let output = serde_json::to_string_pretty(value)
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+    println!("{}", output);
+    Ok(())
+}
+
+fn summarize(stats_path: &Path) -> std::io::Result<RunSummary> {
+    let stem = stats_path.file_stem().unwrap_or_default().to_string_lossy();
+    let drops_path = stats_path.with_file_name(format!("{}_drops.csv", stem));
+
+    let stats = FrameLog::load(stats_path)?;
+    let drops = if drops_path.exists() {
+        Some(FrameLog::load(&drops_path)?)
+    } else {
+        None
+    };
+
+    Ok(RunSummary::new(
+        &stats_path.to_string_lossy(),
+        &stats,
+        drops.as_ref(),
+    ))
+}
@@ -0,0 +1,55 @@
+use std::{fs, path::PathBuf};
+
+use log::info;
+use paper_experiments::{
+    config::{ExperimentConfig, RendererKind},
+    pipelines::client::run_negotiated,
+    renderers::{file::FileRenderer, null::NullRenderer},
+};
+use remotia_core_renderers::beryllium::BerylliumRenderer;
+
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    env_logger::init();
+
+    let config_path = match std::env::args().nth(1) {
+        Some(path) => PathBuf::from(path),
+        None => {
+            eprintln!("Usage: experiment_client <experiment.toml>");
+            std::process::exit(1);
+        }
+    };
+
+    let config = ExperimentConfig::load(&config_path)?;
+    fs::create_dir_all(&config.output.directory)?;
+
+    info!("Loaded experiment configuration from {:?}", config_path);
+
+    let params = config.client_parameters();
+    match config.client.renderer {
+        RendererKind::Beryllium => {
+            run_negotiated(params, |params| {
+                Ok(BerylliumRenderer::new(
+                    params.width as u32,
+                    params.height as u32,
+                ))
+            })
+            .await?
+        }
+        RendererKind::Null => {
+            run_negotiated(params, |params| {
+                Ok(NullRenderer::new(params.width, params.height))
+            })
+            .await?
+        }
+        RendererKind::File => {
+            run_negotiated(params, |params| {
+                let render_file = params.render_file.as_ref().unwrap();
+                FileRenderer::new(render_file, params.width, params.height)
+            })
+            .await?
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,125 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use log::{info, warn};
+use paper_experiments::{
+    config::ExperimentConfig,
+    matrix::{MatrixConfig, MatrixRun},
+};
+use tokio::process::{Child, Command};
+
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    env_logger::init();
+
+    let matrix_path = match std::env::args().nth(1) {
+        Some(path) => PathBuf::from(path),
+        None => {
+            eprintln!("Usage: experiment_matrix <matrix.toml>");
+            std::process::exit(1);
+        }
+    };
+
+    let matrix = MatrixConfig::load(&matrix_path)?;
+    let runs = matrix.runs()?;
+
+    let server_binary = resolve_binary(&matrix.server_binary, "experiment_server")?;
+    let client_binary = resolve_binary(&matrix.client_binary, "experiment_client")?;
+
+    info!("Running {} experiment combinations", runs.len());
+
+    for (index, run) in runs.iter().enumerate() {
+        info!("[{}/{}] {}", index + 1, runs.len(), run.name);
+
+        let config_path = prepare_run(run)?;
+
+        if let Some(profile) = &run.netem_profile {
+            let setup_script = matrix.netem_setup_script(profile);
+            fs::copy(&setup_script, run.directory.join("netem_setup.sh"))?;
+            run_script(&setup_script).await?;
+        }
+
+        let result = execute_run(&matrix, &server_binary, &client_binary, &config_path).await;
+
+        if run.netem_profile.is_some() {
+            run_script(&matrix.netem_reset_script()).await?;
+        }
+
+        result?;
+    }
+
+    Ok(())
+}
+
+fn resolve_binary(configured: &Option<PathBuf>, name: &str) -> std::io::Result<PathBuf> {
+    match configured {
+        Some(path) => Ok(path.clone()),
+        None => Ok(std::env::current_exe()?.with_file_name(name)),
+    }
+}
+
+fn prepare_run(run: &MatrixRun) -> std::io::Result<PathBuf> {
+    fs::create_dir_all(&run.directory)?;
+
+    let config_path = run.directory.join("experiment.toml");
+    let content = toml::to_string(&run.config)
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+    fs::write(&config_path, content)?;
+
+    // Fail before touching the network if the combination is not a valid experiment
+    ExperimentConfig::load(&config_path)?;
+
+    Ok(config_path)
+}
+
+async fn run_script(script: &Path) -> std::io::Result<()> {
+    let status = Command::new("sh").arg(script).status().await?;
+    if !status.success() {
+        warn!("{:?} exited with {}", script, status);
+    }
+
+    Ok(())
+}
+
+async fn execute_run(
+    matrix: &MatrixConfig,
+    server_binary: &Path,
+    client_binary: &Path,
+    config_path: &Path,
+) -> std::io::Result<()> {
+    let mut server = Command::new(server_binary).arg(config_path).spawn()?;
+    tokio::time::sleep(Duration::from_millis(matrix.client_start_delay)).await;
+    let mut client = match Command::new(client_binary).arg(config_path).spawn() {
+        Ok(client) => client,
+        Err(err) => {
+            // The server would otherwise keep the ports every later run needs
+            warn!("Unable to start the client, stopping the server");
+            if let Err(kill_err) = server.kill().await {
+                warn!("Unable to stop the server: {}", kill_err);
+            }
+            return Err(err);
+        }
+    };
+
+    let grace_period = Duration::from_secs(matrix.shutdown_grace_period);
+
+    let server_status = server.wait().await?;
+    info!("Server exited with {}", server_status);
+
+    wait_or_kill(&mut client, grace_period, "Client").await
+}
+
+async fn wait_or_kill(child: &mut Child, grace_period: Duration, name: &str) -> std::io::Result<()> {
+    match tokio::time::timeout(grace_period, child.wait()).await {
+        Ok(status) => info!("{} exited with {}", name, status?),
+        Err(_) => {
+            warn!("{} did not stop within {:?}, killing it", name, grace_period);
+            child.kill().await?;
+        }
+    }
+
+    Ok(())
+}
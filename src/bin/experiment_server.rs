@@ -0,0 +1,109 @@
+use std::{fs, net::SocketAddr, path::PathBuf, sync::Arc, time::Duration};
+
+use log::info;
+use paper_experiments::{
+    capturers::{replay::ReplayFrameCapturer, synthetic::SyntheticFrameCapturer},
+    config::{CapturerKind, ExperimentConfig},
+    netem::{loss::LossEventLog, relay::UdpRelay, trace::LinkTrace},
+    pipelines::server::ServerPipelines,
+};
+use remotia_core_capturers::scrap::ScrapFrameCapturer;
+use tokio::sync::Notify;
+
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    env_logger::init();
+
+    let config_path = match std::env::args().nth(1) {
+        Some(path) => PathBuf::from(path),
+        None => {
+            eprintln!("Usage: experiment_server <experiment.toml>");
+            std::process::exit(1);
+        }
+    };
+
+    let config = ExperimentConfig::load(&config_path)?;
+    fs::create_dir_all(&config.output.directory)?;
+
+    info!("Loaded experiment configuration from {:?}", config_path);
+
+    let link_state = if config.network.enabled {
+        let loss_log_path = config.output.path(&config.network.loss_log);
+        let loss_log = LossEventLog::create(loss_log_path.as_ref())?;
+
+        let relay = UdpRelay::new(
+            SocketAddr::from(([0, 0, 0, 0], config.network.relay_port)),
+            SocketAddr::from(([127, 0, 0, 1], config.transport.port)),
+            config.network.conditions()?,
+        )
+        .seed(config.network.seed)
+        .queue_limit(config.network.queue_limit)
+        .loss_log(loss_log);
+        relay.start().await?;
+
+        if let Some(trace_path) = &config.network.trace {
+            let trace = LinkTrace::load(
+                trace_path,
+                config.network.trace_format,
+                config.network.conditions()?,
+                Duration::from_millis(config.network.trace_interval),
+            )?;
+
+            trace.play(
+                relay.conditions(),
+                config.network.trace_looping,
+                Some(config.output.path(&config.network.trace_log).into()),
+            )?;
+        }
+
+        Some(relay.conditions())
+    } else {
+        None
+    };
+
+    let server_parameters = |width, height| {
+        let mut params = config.server_parameters(width, height);
+        params.link_state = link_state.clone();
+        params
+    };
+
+    let pipelines = match config.capturer.kind {
+        CapturerKind::Scrap => {
+            let capturer = ScrapFrameCapturer::new_from_primary();
+            let params = server_parameters(capturer.width(), capturer.height());
+            ServerPipelines::build(capturer, &params).await?
+        }
+        CapturerKind::Synthetic => {
+            let capturer = SyntheticFrameCapturer::new(
+                config.capturer.width,
+                config.capturer.height,
+                config.capturer.content,
+            )
+            .seed(config.capturer.seed)
+            .damaged_regions(
+                config.capturer.damaged_regions,
+                config.capturer.damaged_region_size,
+            );
+            let params = server_parameters(capturer.width(), capturer.height());
+            ServerPipelines::build(capturer, &params).await?
+        }
+        CapturerKind::Replay => {
+            let stop_signal = Arc::new(Notify::new());
+            let capturer = ReplayFrameCapturer::new(
+                config.capturer.replay_directory.as_ref().unwrap(),
+                config.capturer.width,
+                config.capturer.height,
+            )?
+            .pacing(config.capturer.pacing)
+            .looping(config.capturer.looping)
+            .stopping(stop_signal.clone());
+            let mut params = server_parameters(capturer.width(), capturer.height());
+            params.stop_signal = Some(stop_signal);
+            ServerPipelines::build(capturer, &params).await?
+        }
+    };
+
+    pipelines.run().await;
+
+    Ok(())
+}
@@ -0,0 +1,103 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use log::info;
+use paper_experiments::{dumps, quality::FrameQuality};
+
+// Compares the frames dumped by the server with the ones the client displayed, e.g.
+// `frame_quality server_dump client_dump 1280 720 quality.csv`.
+// A frame missing from the client dump was dropped, so the client kept showing the last frame it
+// rendered: that one is scored against the server frame instead.
+fn main() -> std::io::Result<()> {
+    env_logger::init();
+
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 5 {
+        eprintln!("Usage: frame_quality <server dump> <client dump> <width> <height> [output CSV]");
+        std::process::exit(1);
+    }
+
+    let server_dump = PathBuf::from(&args[1]);
+    let client_dump = PathBuf::from(&args[2]);
+    let width: usize = args[3].parse().expect("Invalid width");
+    let height: usize = args[4].parse().expect("Invalid height");
+    let output_path = args.get(5).map_or("quality.csv", String::as_str);
+
+    let server_frames = dumps::list_frames(&server_dump)?;
+    let client_frames: BTreeMap<u128, PathBuf> =
+        dumps::list_frames(&client_dump)?.into_iter().collect();
+
+    let mut writer = csv::Writer::from_path(output_path)?;
+    writer.write_record([
+        "frame_id",
+        "displayed_frame_id",
+        "frozen",
+        "psnr_y",
+        "psnr_yuv",
+        "ssim",
+        "ms_ssim",
+    ])?;
+
+    let mut displayed: Option<(u128, Vec<u8>)> = None;
+    let mut scores = Vec::new();
+    let mut frozen_count = 0;
+
+    for (frame_id, server_path) in &server_frames {
+        let frozen = match client_frames.get(frame_id) {
+            Some(client_path) => {
+                displayed = Some((*frame_id, dumps::read_frame(client_path, width, height)?));
+                false
+            }
+            None => true,
+        };
+
+        // Nothing was on screen yet
+        let (displayed_frame_id, displayed_pixels) = match &displayed {
+            Some(displayed) => displayed,
+            None => continue,
+        };
+
+        let reference = dumps::read_frame(server_path, width, height)?;
+        let quality = FrameQuality::compare(&reference, displayed_pixels, width, height)?;
+
+        writer.write_record([
+            frame_id.to_string(),
+            displayed_frame_id.to_string(),
+            (frozen as u8).to_string(),
+            quality.psnr_y.to_string(),
+            quality.psnr_yuv.to_string(),
+            quality.ssim.to_string(),
+            quality.ms_ssim.to_string(),
+        ])?;
+
+        if frozen {
+            frozen_count += 1;
+        }
+        scores.push(quality);
+    }
+
+    writer.flush()?;
+
+    info!("Scored {} frames into {}", scores.len(), output_path);
+
+    if scores.is_empty() {
+        println!("No server frame was displayed by the client");
+        return Ok(());
+    }
+
+    let mean = |metric: fn(&FrameQuality) -> f64| {
+        scores.iter().map(metric).sum::<f64>() / scores.len() as f64
+    };
+
+    println!(
+        "{} frames, {} frozen ({:.2}%)",
+        scores.len(),
+        frozen_count,
+        frozen_count as f64 * 100.0 / scores.len() as f64
+    );
+    println!("PSNR Y:   {:.2} dB", mean(|quality| quality.psnr_y));
+    println!("PSNR YUV: {:.2} dB", mean(|quality| quality.psnr_yuv));
+    println!("SSIM:     {:.4}", mean(|quality| quality.ssim));
+    println!("MS-SSIM:  {:.4}", mean(|quality| quality.ms_ssim));
+
+    Ok(())
+}
@@ -0,0 +1,34 @@
+use std::net::SocketAddr;
+
+use paper_experiments::netem::{conditions::LinkConditions, relay::UdpRelay};
+
+// Standalone emulated link for pipelines that are not driven by an experiment file, e.g.
+// `netem_relay 5003 127.0.0.1:5001 60mbit_delay_packetloss 42`
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    env_logger::init();
+
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 4 {
+        eprintln!("Usage: netem_relay <listen port> <target address> <profile> [seed]");
+        std::process::exit(1);
+    }
+
+    let listen_port: u16 = args[1].parse().expect("Invalid listen port");
+    let target_address: SocketAddr = args[2].parse().expect("Invalid target address");
+    let conditions = LinkConditions::profile(&args[3]).expect("Unknown network profile");
+    let seed = args.get(4).map_or(0, |seed| seed.parse().expect("Invalid seed"));
+
+    UdpRelay::new(
+        SocketAddr::from(([0, 0, 0, 0], listen_port)),
+        target_address,
+        conditions,
+    )
+    .seed(seed)
+    .start()
+    .await?;
+
+    std::future::pending::<()>().await;
+
+    Ok(())
+}
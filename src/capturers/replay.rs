@@ -0,0 +1,159 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use log::{error, info};
+use remotia::{error::DropReason, traits::FrameProcessor, types::FrameData};
+use serde::Deserialize;
+use tokio::{sync::Notify, time::Instant};
+
+use crate::{dumps, time::now_timestamp};
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplayPacing {
+    // Sleep until the recorded capture_timestamp offsets
+    Original,
+    // Emit one frame per pipeline tick
+    Ticker,
+}
+
+pub struct ReplayFrameCapturer {
+    width: usize,
+    height: usize,
+    // Recorded capture_timestamp of every dumped frame, which names its file, and its path
+    frames: Vec<(u128, PathBuf)>,
+
+    pacing: ReplayPacing,
+    looping: bool,
+
+    next_frame: usize,
+    loop_start: Option<(Instant, u128)>,
+    finished: bool,
+    stop_signal: Option<Arc<Notify>>,
+}
+
+impl ReplayFrameCapturer {
+    pub fn new(directory: &Path, width: usize, height: usize) -> std::io::Result<Self> {
+        let frames = dumps::list_frames(directory)?;
+
+        let capturer = Self {
+            width,
+            height,
+            frames,
+            pacing: ReplayPacing::Original,
+            looping: false,
+            next_frame: 0,
+            loop_start: None,
+            finished: false,
+            stop_signal: None,
+        };
+
+        // Catch resolution mismatches before the pipeline starts
+        capturer.read_frame(&capturer.frames[0].1)?;
+
+        info!(
+            "Loaded {} frames to replay from {:?}",
+            capturer.frames.len(),
+            directory
+        );
+
+        Ok(capturer)
+    }
+
+    pub fn pacing(mut self, pacing: ReplayPacing) -> Self {
+        self.pacing = pacing;
+        self
+    }
+
+    pub fn looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    // Notified when a replay that does not loop runs out of frames
+    pub fn stopping(mut self, stop_signal: Arc<Notify>) -> Self {
+        self.stop_signal = Some(stop_signal);
+        self
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    fn read_frame(&self, path: &Path) -> std::io::Result<Vec<u8>> {
+        dumps::read_frame(path, self.width, self.height)
+    }
+
+    fn finish(&mut self) {
+        if self.finished {
+            return;
+        }
+
+        info!("Replay finished");
+        self.finished = true;
+        if let Some(stop_signal) = &self.stop_signal {
+            stop_signal.notify_one();
+        }
+    }
+}
+
+#[async_trait]
+impl FrameProcessor for ReplayFrameCapturer {
+    async fn process(&mut self, mut frame_data: FrameData) -> Option<FrameData> {
+        if self.next_frame == self.frames.len() {
+            // Dropped rather than discarded, so that the error handling pipeline redeems the
+            // borrowed buffer
+            if !self.looping {
+                self.finish();
+                frame_data.set_drop_reason(Some(DropReason::ConnectionError));
+                return Some(frame_data);
+            }
+
+            self.next_frame = 0;
+            self.loop_start = None;
+        }
+
+        let (recorded_timestamp, path) = self.frames[self.next_frame].clone();
+        self.next_frame += 1;
+
+        if self.pacing == ReplayPacing::Original {
+            let (start, first_recorded_timestamp) = *self
+                .loop_start
+                .get_or_insert_with(|| (Instant::now(), recorded_timestamp));
+
+            let elapsed = recorded_timestamp - first_recorded_timestamp;
+            let offset = Duration::from_millis(elapsed as u64);
+            tokio::time::sleep_until(start + offset).await;
+
+            // The frame is "captured" now, not when the tick started
+            frame_data.set("capture_timestamp", now_timestamp());
+        }
+
+        let pixels = match self.read_frame(&path) {
+            Ok(pixels) => pixels,
+            Err(err) => {
+                error!("Unable to replay {:?}: {}", path, err);
+                frame_data.set_drop_reason(Some(DropReason::ConnectionError));
+                return Some(frame_data);
+            }
+        };
+
+        frame_data
+            .get_writable_buffer_ref("raw_frame_buffer")
+            .unwrap()
+            .copy_from_slice(&pixels);
+
+        // Identifies the replayed frame across runs, unlike the frame_id of this one
+        frame_data.set("replayed_frame_id", recorded_timestamp);
+
+        Some(frame_data)
+    }
+}
@@ -0,0 +1,267 @@
+use async_trait::async_trait;
+use remotia::{traits::FrameProcessor, types::FrameData};
+use serde::Deserialize;
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum SyntheticContent {
+    Gradient,
+    ScrollingText,
+    Noise,
+    StaticDamage,
+}
+
+const GLYPH_WIDTH: usize = 6;
+const GLYPH_HEIGHT: usize = 10;
+const LINE_HEIGHT: usize = 16;
+const SCROLL_SPEED: usize = 2;
+const GRADIENT_SPEED: usize = 4;
+
+pub struct SyntheticFrameCapturer {
+    width: usize,
+    height: usize,
+    content: SyntheticContent,
+    seed: u64,
+
+    damaged_regions: usize,
+    damaged_region_size: usize,
+
+    frame_index: u64,
+    background: Option<Vec<u8>>,
+}
+
+impl SyntheticFrameCapturer {
+    pub fn new(width: usize, height: usize, content: SyntheticContent) -> Self {
+        Self {
+            width,
+            height,
+            content,
+            seed: 0,
+            damaged_regions: 4,
+            damaged_region_size: 64,
+            frame_index: 0,
+            background: None,
+        }
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn damaged_regions(mut self, count: usize, size: usize) -> Self {
+        self.damaged_regions = count;
+        self.damaged_region_size = size;
+        self
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    fn draw(&mut self, buffer: &mut [u8]) {
+        match self.content {
+            SyntheticContent::Gradient => self.draw_gradient(buffer),
+            SyntheticContent::ScrollingText => {
+                let offset = self.frame_index as usize * SCROLL_SPEED;
+                draw_text_page(buffer, self.width, self.height, self.seed, offset);
+            }
+            SyntheticContent::Noise => self.draw_noise(buffer),
+            SyntheticContent::StaticDamage => self.draw_static_damage(buffer),
+        }
+    }
+
+    fn draw_gradient(&self, buffer: &mut [u8]) {
+        let offset = self.frame_index as usize * GRADIENT_SPEED;
+
+        for (y, row) in buffer.chunks_exact_mut(self.width * 4).enumerate() {
+            for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+                pixel[0] = ((x + offset) % 256) as u8;
+                pixel[1] = ((y + offset / 2) % 256) as u8;
+                pixel[2] = ((x + y + offset) / 2 % 256) as u8;
+                pixel[3] = 255;
+            }
+        }
+    }
+
+    fn draw_noise(&self, buffer: &mut [u8]) {
+        let mut rng = SplitMix64::new(self.seed ^ self.frame_index.wrapping_mul(0x9E37_79B9));
+
+        for chunk in buffer.chunks_exact_mut(8) {
+            let value = rng.next().to_le_bytes();
+            chunk.copy_from_slice(&value);
+            chunk[3] = 255;
+            chunk[7] = 255;
+        }
+    }
+
+    fn draw_static_damage(&mut self, buffer: &mut [u8]) {
+        let (width, height, seed) = (self.width, self.height, self.seed);
+        let background = self.background.get_or_insert_with(|| {
+            let mut background = vec![0; width * height * 4];
+            draw_text_page(&mut background, width, height, seed, 0);
+            background
+        });
+
+        buffer[..background.len()].copy_from_slice(background);
+
+        let size = self.damaged_region_size.min(width).min(height);
+        let mut rng = SplitMix64::new(self.seed);
+        for region in 0..self.damaged_regions {
+            let region_x = (rng.next() as usize) % (width - size + 1);
+            let region_y = (rng.next() as usize) % (height - size + 1);
+            let shade = (self.frame_index as usize * 8 + region * 32) % 256;
+
+            for y in region_y..region_y + size {
+                for x in region_x..region_x + size {
+                    let index = (y * width + x) * 4;
+                    buffer[index] = shade as u8;
+                    buffer[index + 1] = ((x - region_x) * 255 / size) as u8;
+                    buffer[index + 2] = ((y - region_y) * 255 / size) as u8;
+                    buffer[index + 3] = 255;
+                }
+            }
+        }
+    }
+}
+
+// Light background with lines of pseudo-random glyphs, vertically offset by `scroll` pixels
+fn draw_text_page(buffer: &mut [u8], width: usize, height: usize, seed: u64, scroll: usize) {
+    for y in 0..height {
+        let page_y = y + scroll;
+        let line = page_y / LINE_HEIGHT;
+        let glyph_y = page_y % LINE_HEIGHT;
+
+        // Each line gets its own indentation and length, like source code in an editor
+        let line_hash = hash(seed, line as u64, 0);
+        let indentation = (line_hash % 8) as usize * 4;
+        let length = (line_hash >> 8) as usize % (width / GLYPH_WIDTH).max(1);
+
+        for x in 0..width {
+            let column = x / GLYPH_WIDTH;
+            let glyph_x = x % GLYPH_WIDTH;
+
+            let is_text = glyph_y < GLYPH_HEIGHT
+                && glyph_x < GLYPH_WIDTH - 1
+                && column >= indentation
+                && column < indentation + length
+                && {
+                    let glyph = hash(seed, line as u64, column as u64 + 1);
+                    // Spaces between words
+                    glyph % 7 != 0 && (glyph >> (8 + glyph_y * (GLYPH_WIDTH - 1) + glyph_x)) & 1 == 1
+                };
+
+            let index = (y * width + x) * 4;
+            let value = if is_text { 30 } else { 235 };
+            buffer[index] = value;
+            buffer[index + 1] = value;
+            buffer[index + 2] = value;
+            buffer[index + 3] = 255;
+        }
+    }
+}
+
+fn hash(seed: u64, a: u64, b: u64) -> u64 {
+    let mut rng = SplitMix64::new(seed ^ a.wrapping_mul(0xA24B_AED4_963E_E407) ^ b);
+    rng.next()
+}
+
+struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+#[async_trait]
+impl FrameProcessor for SyntheticFrameCapturer {
+    async fn process(&mut self, mut frame_data: FrameData) -> Option<FrameData> {
+        let buffer = frame_data
+            .get_writable_buffer_ref("raw_frame_buffer")
+            .unwrap();
+
+        self.draw(buffer);
+        self.frame_index += 1;
+
+        Some(frame_data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Wide enough for the indented lines of the scrolling text
+    const WIDTH: usize = 320;
+    const HEIGHT: usize = 64;
+
+    fn frames(content: SyntheticContent, seed: u64, count: usize) -> Vec<Vec<u8>> {
+        let mut capturer = SyntheticFrameCapturer::new(WIDTH, HEIGHT, content)
+            .seed(seed)
+            .damaged_regions(2, 16);
+
+        (0..count)
+            .map(|_| {
+                let mut buffer = vec![0; WIDTH * HEIGHT * 4];
+                capturer.draw(&mut buffer);
+                capturer.frame_index += 1;
+                buffer
+            })
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_frames() {
+        for content in [
+            SyntheticContent::Gradient,
+            SyntheticContent::ScrollingText,
+            SyntheticContent::Noise,
+            SyntheticContent::StaticDamage,
+        ] {
+            let drawn = frames(content, 42, 4);
+            assert_eq!(drawn, frames(content, 42, 4), "{:?}", content);
+
+            // Every frame is opaque and moves on from the previous one
+            for frame in &drawn {
+                assert!(frame.chunks_exact(4).all(|pixel| pixel[3] == 255));
+            }
+            assert!(drawn.windows(2).all(|pair| pair[0] != pair[1]), "{:?}", content);
+        }
+    }
+
+    #[test]
+    fn different_seeds_give_different_frames() {
+        // The gradient does not depend on the seed
+        for content in [
+            SyntheticContent::ScrollingText,
+            SyntheticContent::Noise,
+            SyntheticContent::StaticDamage,
+        ] {
+            let first = frames(content, 1, 2);
+            let second = frames(content, 2, 2);
+            for (first, second) in first.iter().zip(&second) {
+                assert_ne!(first, second, "{:?}", content);
+            }
+        }
+
+        assert_eq!(
+            frames(SyntheticContent::Gradient, 1, 2),
+            frames(SyntheticContent::Gradient, 2, 2)
+        );
+    }
+}
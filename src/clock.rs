@@ -0,0 +1,352 @@
+use std::{collections::VecDeque, time::Duration};
+
+use log::{debug, info, warn};
+use tokio::{net::UdpSocket, sync::watch, task::JoinHandle};
+
+use crate::time::now_timestamp_micros;
+
+const PROBE_SIZE: usize = 8;
+const REPLY_SIZE: usize = 24;
+
+// Most recent probes used for the estimate
+const WINDOW_SIZE: usize = 64;
+
+// Drift is only fitted once the window spans this many microseconds
+const MIN_DRIFT_SPAN: f64 = 5_000_000.0;
+
+// Server side: answers every probe with its receive and transmit timestamps
+pub struct ClockSyncResponder {
+    task: JoinHandle<()>,
+}
+
+impl ClockSyncResponder {
+    pub async fn bind(port: u16) -> std::io::Result<Self> {
+        let socket = UdpSocket::bind(("0.0.0.0", port)).await?;
+
+        let task = tokio::spawn(async move {
+            let mut buffer = [0; PROBE_SIZE];
+            loop {
+                let (size, address) = match socket.recv_from(&mut buffer).await {
+                    Ok(received) => received,
+                    Err(err) => {
+                        warn!("Unable to receive a clock probe: {}", err);
+                        continue;
+                    }
+                };
+
+                let receive_time = now_timestamp_micros();
+                if size != PROBE_SIZE {
+                    debug!("Ignoring a malformed clock probe from {}", address);
+                    continue;
+                }
+
+                let mut reply = [0; REPLY_SIZE];
+                reply[..8].copy_from_slice(&buffer);
+                reply[8..16].copy_from_slice(&receive_time.to_le_bytes());
+                reply[16..].copy_from_slice(&now_timestamp_micros().to_le_bytes());
+
+                if let Err(err) = socket.send_to(&reply, address).await {
+                    debug!("Unable to answer the clock probe of {}: {}", address, err);
+                }
+            }
+        });
+
+        Ok(Self { task })
+    }
+}
+
+impl Drop for ClockSyncResponder {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ClockEstimate {
+    // Remote clock minus local clock at `reference`, in microseconds
+    pub offset: i64,
+    // Microseconds gained by the remote clock per second of local time
+    pub drift: f64,
+    // Half the round trip of the best sample: the true offset is within offset ± uncertainty
+    pub uncertainty: u64,
+    reference: u64,
+}
+
+impl ClockEstimate {
+    pub fn offset_at(&self, local_time: u64) -> i64 {
+        let elapsed = local_time as f64 - self.reference as f64;
+        self.offset + (self.drift * elapsed / 1_000_000.0).round() as i64
+    }
+
+    pub fn uncertainty_at(&self, local_time: u64) -> u64 {
+        let elapsed = local_time.saturating_sub(self.reference) as f64;
+        self.uncertainty + (self.drift.abs() * elapsed / 1_000_000.0).round() as u64
+    }
+
+    // Converts a remote timestamp in milliseconds to the local clock
+    pub fn local_timestamp(&self, remote_timestamp: u128, local_time: u64) -> u128 {
+        let remote = remote_timestamp as i128 * 1000;
+        let local = remote - self.offset_at(local_time) as i128;
+        (local.max(0) as u128 + 500) / 1000
+    }
+}
+
+#[derive(Clone, Copy)]
+struct ClockSample {
+    // Midpoint of the probe on the local clock
+    local_time: u64,
+    offset: i64,
+    round_trip: u64,
+}
+
+impl ClockSample {
+    fn from_reply(reply: &[u8; REPLY_SIZE], receive_time: u64) -> Self {
+        let timestamp = |index: usize| {
+            u64::from_le_bytes(reply[index * 8..(index + 1) * 8].try_into().unwrap()) as i64
+        };
+        let (t1, t2, t3, t4) = (timestamp(0), timestamp(1), timestamp(2), receive_time as i64);
+
+        Self {
+            local_time: ((t1 + t4) / 2) as u64,
+            offset: ((t2 - t1) + (t3 - t4)) / 2,
+            round_trip: ((t4 - t1) - (t3 - t2)).max(0) as u64,
+        }
+    }
+}
+
+// Client side: probes the responder periodically and keeps an estimate of the offset and drift
+// of the remote clock
+pub struct ClockSynchronizer {
+    estimates: watch::Receiver<Option<ClockEstimate>>,
+    task: JoinHandle<()>,
+}
+
+impl ClockSynchronizer {
+    // Returns once the first estimate is available
+    pub async fn connect(address: &str, interval: Duration) -> std::io::Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0").await?;
+        socket.connect(address).await?;
+
+        let (sender, mut estimates) = watch::channel(None);
+        let task = tokio::spawn(synchronize(socket, interval, sender));
+
+        while estimates.borrow_and_update().is_none() {
+            if estimates.changed().await.is_err() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::BrokenPipe,
+                    "clock synchronization stopped",
+                ));
+            }
+        }
+
+        info!(
+            "Initial clock estimate against {}: {:?}",
+            address,
+            *estimates.borrow()
+        );
+
+        Ok(Self { estimates, task })
+    }
+
+    pub fn estimates(&self) -> watch::Receiver<Option<ClockEstimate>> {
+        self.estimates.clone()
+    }
+}
+
+impl Drop for ClockSynchronizer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn synchronize(
+    socket: UdpSocket,
+    interval: Duration,
+    estimates: watch::Sender<Option<ClockEstimate>>,
+) {
+    let mut samples: VecDeque<ClockSample> = VecDeque::with_capacity(WINDOW_SIZE);
+    let mut probes = tokio::time::interval(interval);
+    let mut reply = [0; REPLY_SIZE];
+
+    loop {
+        tokio::select! {
+            _ = probes.tick() => {
+                let probe = now_timestamp_micros().to_le_bytes();
+                if let Err(err) = socket.send(&probe).await {
+                    debug!("Unable to send a clock probe: {}", err);
+                }
+            }
+            received = socket.recv(&mut reply) => {
+                let receive_time = now_timestamp_micros();
+                match received {
+                    Ok(REPLY_SIZE) => {}
+                    Ok(_) => continue,
+                    Err(err) => {
+                        // Responder not listening yet
+                        debug!("Unable to receive a clock reply: {}", err);
+                        continue;
+                    }
+                }
+
+                if samples.len() == WINDOW_SIZE {
+                    samples.pop_front();
+                }
+                samples.push_back(ClockSample::from_reply(&reply, receive_time));
+
+                if estimates.send(Some(estimate(&samples))).is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+// NTP-like clock filter: samples with the shortest round trips are the least affected by
+// queuing, so only those are used, and drift is their least squares slope
+fn estimate(samples: &VecDeque<ClockSample>) -> ClockEstimate {
+    let best = *samples.iter().min_by_key(|sample| sample.round_trip).unwrap();
+    let tolerance = best.round_trip / 2 + 100;
+    let filtered: Vec<&ClockSample> = samples
+        .iter()
+        .filter(|sample| sample.round_trip <= best.round_trip + tolerance)
+        .collect();
+
+    let first = filtered.first().unwrap().local_time;
+    let reference = filtered.last().unwrap().local_time;
+    let span = (reference - first) as f64;
+
+    if filtered.len() < 3 || span < MIN_DRIFT_SPAN {
+        return ClockEstimate {
+            offset: best.offset,
+            drift: 0.0,
+            uncertainty: best.round_trip / 2,
+            reference: best.local_time,
+        };
+    }
+
+    let count = filtered.len() as f64;
+    let mean_time = filtered
+        .iter()
+        .map(|sample| (sample.local_time - first) as f64)
+        .sum::<f64>()
+        / count;
+    let mean_offset = filtered.iter().map(|sample| sample.offset as f64).sum::<f64>() / count;
+
+    let (mut covariance, mut variance) = (0.0, 0.0);
+    for sample in &filtered {
+        let time = (sample.local_time - first) as f64 - mean_time;
+        covariance += time * (sample.offset as f64 - mean_offset);
+        variance += time * time;
+    }
+
+    let slope = covariance / variance;
+    let offset = mean_offset + slope * ((reference - first) as f64 - mean_time);
+
+    ClockEstimate {
+        offset: offset.round() as i64,
+        drift: slope * 1_000_000.0,
+        uncertainty: best.round_trip / 2,
+        reference,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reply(t1: u64, t2: u64, t3: u64) -> [u8; REPLY_SIZE] {
+        let mut reply = [0; REPLY_SIZE];
+        reply[..8].copy_from_slice(&t1.to_le_bytes());
+        reply[8..16].copy_from_slice(&t2.to_le_bytes());
+        reply[16..].copy_from_slice(&t3.to_le_bytes());
+        reply
+    }
+
+    // Remote clock `offset` microseconds ahead, gaining `drift` microseconds per second
+    fn samples(count: u64, offset: i64, drift: f64, round_trip: u64) -> VecDeque<ClockSample> {
+        (0..count)
+            .map(|index| {
+                let local_time = 1_000_000_000 + index * 200_000;
+                let elapsed = (index * 200_000) as f64 / 1_000_000.0;
+                ClockSample {
+                    local_time,
+                    offset: offset + (drift * elapsed).round() as i64,
+                    round_trip,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn measures_offset_and_round_trip_of_a_probe() {
+        // 2 ms each way, 1 ms in the responder, remote clock 5 ms ahead
+        let sample = ClockSample::from_reply(&reply(10_000, 17_000, 18_000), 15_000);
+
+        assert_eq!(sample.offset, 5_000);
+        assert_eq!(sample.round_trip, 4_000);
+        assert_eq!(sample.local_time, 12_500);
+    }
+
+    #[test]
+    fn asymmetric_paths_bias_the_offset_by_half_the_difference() {
+        // 1 ms out, 3 ms back, same clocks
+        let sample = ClockSample::from_reply(&reply(10_000, 11_000, 11_000), 14_000);
+
+        assert_eq!(sample.offset, -1_000);
+        assert_eq!(sample.round_trip, 4_000);
+    }
+
+    #[test]
+    fn keeps_the_shortest_round_trip_without_enough_span() {
+        let mut samples = samples(3, 5_000, 0.0, 4_000);
+        samples[1].offset = 4_800;
+        samples[1].round_trip = 1_000;
+
+        let estimate = estimate(&samples);
+        assert_eq!(estimate.offset, 4_800);
+        assert_eq!(estimate.drift, 0.0);
+        assert_eq!(estimate.uncertainty, 500);
+        assert_eq!(estimate.offset_at(samples[2].local_time + 60_000_000), 4_800);
+    }
+
+    #[test]
+    fn fits_the_drift_over_the_window() {
+        let estimate = estimate(&samples(WINDOW_SIZE as u64, 5_000, 50.0, 1_000));
+
+        assert!((estimate.drift - 50.0).abs() < 0.5, "{:?}", estimate);
+        // The window spans 12.6 seconds
+        assert!((estimate.offset - 5_630).abs() <= 1, "{:?}", estimate);
+        assert_eq!(estimate.uncertainty, 500);
+
+        let later = estimate.reference + 10_000_000;
+        assert!((estimate.offset_at(later) - 6_130).abs() <= 6);
+        assert_eq!(estimate.uncertainty_at(later), 1_000);
+    }
+
+    #[test]
+    fn ignores_samples_delayed_by_queuing() {
+        let mut samples = samples(WINDOW_SIZE as u64, -2_000, 0.0, 1_000);
+        for sample in samples.iter_mut().step_by(4) {
+            sample.round_trip = 40_000;
+            sample.offset += 19_500;
+        }
+
+        let estimate = estimate(&samples);
+        assert_eq!(estimate.offset, -2_000);
+        assert!(estimate.drift.abs() < 0.01);
+    }
+
+    #[test]
+    fn translates_remote_timestamps() {
+        let estimate = ClockEstimate {
+            offset: 5_000,
+            drift: 0.0,
+            uncertainty: 500,
+            reference: 0,
+        };
+
+        assert_eq!(estimate.local_timestamp(1_000, 0), 995);
+        // Before the local epoch
+        assert_eq!(estimate.local_timestamp(1, 0), 0);
+    }
+}
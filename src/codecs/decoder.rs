@@ -0,0 +1,210 @@
+use std::{
+    ffi::CString,
+    io::{Error, ErrorKind},
+};
+
+use async_trait::async_trait;
+use log::{debug, info};
+use remotia::{error::DropReason, traits::FrameProcessor, types::FrameData};
+use remotia_ffmpeg_codecs::decoders::h264::H264Decoder;
+use rsmpeg::{
+    avcodec::{AVCodec, AVCodecContext, AVPacket},
+    avutil::{AVDictionary, AVFrame},
+    error::RsmpegError,
+    ffi,
+};
+
+use super::{codec_error, Codec};
+
+// Decoder of a run: H.264 streams stay on the H264Decoder of the original examples, whichever
+// encoder produced them
+pub enum VideoDecoder {
+    H264(H264Decoder),
+    FFmpeg(FFmpegDecoder),
+}
+
+impl VideoDecoder {
+    pub fn new(codec: Codec, width: usize, height: usize) -> std::io::Result<Self> {
+        match codec {
+            Codec::X264 => {
+                info!("Opened H264Decoder for {}x{}", width, height);
+                Ok(Self::H264(H264Decoder::new()))
+            }
+            codec => FFmpegDecoder::new(codec, width, height).map(Self::FFmpeg),
+        }
+    }
+}
+
+#[async_trait]
+impl FrameProcessor for VideoDecoder {
+    async fn process(&mut self, frame_data: FrameData) -> Option<FrameData> {
+        match self {
+            Self::H264(decoder) => decoder.process(frame_data).await,
+            Self::FFmpeg(decoder) => decoder.process(frame_data).await,
+        }
+    }
+}
+
+// Decodes encoded_frame_buffer into RGBA pixels in raw_frame_buffer
+pub struct FFmpegDecoder {
+    codec: Codec,
+    context: AVCodecContext,
+    width: usize,
+    height: usize,
+}
+
+// SAFETY: the FFmpeg context is owned by the processor and only reached through &mut self, so it
+// is never used from two threads at once, which is all FFmpeg requires to move it between threads
+unsafe impl Send for FFmpegDecoder {}
+
+impl FFmpegDecoder {
+    pub fn new(codec: Codec, width: usize, height: usize) -> std::io::Result<Self> {
+        let name = CString::new(codec.decoder_name()).unwrap();
+        let decoder = AVCodec::find_decoder_by_name(&name).ok_or_else(|| {
+            Error::new(
+                ErrorKind::NotFound,
+                format!("FFmpeg was built without {}", codec.decoder_name()),
+            )
+        })?;
+
+        let mut context = AVCodecContext::new(&decoder);
+        // SAFETY: the pointer comes from the context just allocated, which is not open yet
+        unsafe {
+            let raw = context.as_mut_ptr();
+            (*raw).flags |= ffi::AV_CODEC_FLAG_LOW_DELAY as i32;
+            // Frame threading delays the output by one frame per thread
+            (*raw).thread_type = ffi::FF_THREAD_SLICE as i32;
+        }
+
+        let mut dictionary: Option<AVDictionary> = None;
+        for (key, value) in codec.low_latency_decoder_options() {
+            let key = CString::new(*key).unwrap();
+            let value = CString::new(*value).unwrap();
+            dictionary = Some(match dictionary {
+                Some(dictionary) => dictionary.set(&key, &value, 0),
+                None => AVDictionary::new(&key, &value, 0),
+            });
+        }
+
+        context
+            .open(dictionary)
+            .map_err(|err| codec_error(codec.decoder_name(), err))?;
+
+        info!("Opened {} for {}x{}", codec.decoder_name(), width, height);
+
+        Ok(Self {
+            codec,
+            context,
+            width,
+            height,
+        })
+    }
+
+    pub fn codec(&self) -> Codec {
+        self.codec
+    }
+
+    fn decode(&mut self, frame_data: &mut FrameData) -> Result<(), DropReason> {
+        let encoded_size = frame_data.get("encoded_size") as usize;
+        let encoded = frame_data
+            .get_writable_buffer_ref("encoded_frame_buffer")
+            .unwrap();
+
+        if encoded_size > encoded.len() {
+            debug!("Encoded size {} exceeds the buffer", encoded_size);
+            return Err(DropReason::CodecError);
+        }
+
+        let mut packet = AVPacket::new();
+        // SAFETY: av_new_packet allocated encoded_size bytes at packet.data, and the source buffer
+        // was checked to hold as many
+        unsafe {
+            if ffi::av_new_packet(packet.as_mut_ptr(), encoded_size as i32) < 0 {
+                return Err(DropReason::CodecError);
+            }
+            std::ptr::copy_nonoverlapping(encoded.as_ptr(), packet.data, encoded_size);
+        }
+
+        if let Err(err) = self.context.send_packet(Some(&packet)) {
+            debug!("Unable to send the packet to the decoder: {}", err);
+            return Err(DropReason::CodecError);
+        }
+
+        // Drains the decoder, keeping the latest frame should a packet release more than one
+        let mut latest = None;
+        loop {
+            match self.context.receive_frame() {
+                Ok(frame) => latest = Some(frame),
+                Err(RsmpegError::DecoderDrainError) | Err(RsmpegError::DecoderFlushedError) => {
+                    break
+                }
+                Err(err) => {
+                    debug!("Unable to receive the decoded frame: {}", err);
+                    return Err(DropReason::CodecError);
+                }
+            }
+        }
+
+        let frame = latest.ok_or(DropReason::NoDecodedFrames)?;
+
+        if frame.width as usize != self.width
+            || frame.height as usize != self.height
+            || frame.format != ffi::AVPixelFormat_AV_PIX_FMT_YUV420P as i32
+        {
+            debug!(
+                "Unexpected {}x{} frame in format {}",
+                frame.width, frame.height, frame.format
+            );
+            return Err(DropReason::CodecError);
+        }
+
+        let output = frame_data.get_writable_buffer_ref("raw_frame_buffer").unwrap();
+        write_rgba(&frame, output, self.width, self.height);
+
+        // Also set on the recovery points of an intra refresh
+        frame_data.set("decoded_keyframe", (frame.key_frame != 0) as u128);
+
+        Ok(())
+    }
+}
+
+// Full-range BT.601, the inverse of RGBAToYUV420PConverter
+fn write_rgba(frame: &AVFrame, output: &mut [u8], width: usize, height: usize) {
+    // SAFETY: decode checked the frame is YUV420P at width x height, so each plane has its rows,
+    // halved for chroma, of linesize >= length bytes, and the frame outlives the slices
+    let plane = |index: usize, row: usize, length: usize| unsafe {
+        let start = frame.data[index].add(row * frame.linesize[index] as usize);
+        std::slice::from_raw_parts(start, length)
+    };
+
+    for y in 0..height {
+        let luma = plane(0, y, width);
+        let cb = plane(1, y / 2, width / 2);
+        let cr = plane(2, y / 2, width / 2);
+
+        for (x, pixel) in output[y * width * 4..(y + 1) * width * 4]
+            .chunks_exact_mut(4)
+            .enumerate()
+        {
+            let luma = luma[x] as f32;
+            let cb = cb[x / 2] as f32 - 128.0;
+            let cr = cr[x / 2] as f32 - 128.0;
+
+            pixel[0] = (luma + 1.402 * cr).clamp(0.0, 255.0) as u8;
+            pixel[1] = (luma - 0.344_136 * cb - 0.714_136 * cr).clamp(0.0, 255.0) as u8;
+            pixel[2] = (luma + 1.772 * cb).clamp(0.0, 255.0) as u8;
+            pixel[3] = 255;
+        }
+    }
+}
+
+#[async_trait]
+impl FrameProcessor for FFmpegDecoder {
+    async fn process(&mut self, mut frame_data: FrameData) -> Option<FrameData> {
+        if let Err(reason) = self.decode(&mut frame_data) {
+            frame_data.set_drop_reason(Some(reason));
+        }
+
+        Some(frame_data)
+    }
+}
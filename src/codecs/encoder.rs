@@ -0,0 +1,410 @@
+use std::{
+    ffi::CString,
+    io::{Error, ErrorKind},
+};
+
+use async_trait::async_trait;
+use log::{debug, info, warn};
+use remotia::{error::DropReason, traits::FrameProcessor, types::FrameData};
+use remotia_ffmpeg_codecs::encoders::x264::X264Encoder;
+use rsmpeg::{
+    avcodec::{AVCodec, AVCodecContext},
+    avutil::{AVDictionary, AVFrame},
+    error::RsmpegError,
+    ffi,
+};
+use tokio::sync::watch;
+
+use super::{
+    codec_error,
+    settings::{EncoderSettings, EncoderSetup, EncoderTarget},
+    Codec,
+};
+
+// Encoder of a run: x264 stays on the X264Encoder of the original examples whenever it can run the
+// settings, every other case goes through FFmpegEncoder
+pub enum VideoEncoder {
+    X264(X264Encoder),
+    FFmpeg(FFmpegEncoder),
+}
+
+impl VideoEncoder {
+    pub fn new(
+        codec: Codec,
+        width: usize,
+        height: usize,
+        frame_rate: i32,
+        settings: &EncoderSettings,
+    ) -> std::io::Result<Self> {
+        match settings.x264_options().filter(|_| codec == Codec::X264) {
+            Some(options) => Self::x264(width, height, settings, &options),
+            None => FFmpegEncoder::new(codec, width, height, frame_rate, settings).map(Self::FFmpeg),
+        }
+    }
+
+    // `options` as returned by EncoderSettings::x264_options
+    pub fn x264(
+        width: usize,
+        height: usize,
+        settings: &EncoderSettings,
+        options: &str,
+    ) -> std::io::Result<Self> {
+        settings.validate(Codec::X264)?;
+        info!("Opened X264Encoder for {}x{} with {}", width, height, options);
+
+        // Sized like the buffers of the encoded frames pool
+        let buffer_size = width * height * 4;
+        Ok(Self::X264(X264Encoder::new(
+            buffer_size,
+            width as i32,
+            height as i32,
+            options,
+        )))
+    }
+}
+
+#[async_trait]
+impl FrameProcessor for VideoEncoder {
+    async fn process(&mut self, frame_data: FrameData) -> Option<FrameData> {
+        match self {
+            Self::X264(encoder) => encoder.process(frame_data).await,
+            Self::FFmpeg(encoder) => encoder.process(frame_data).await,
+        }
+    }
+}
+
+// Encodes the YUV420P planes written by RGBAToYUV420PConverter into encoded_frame_buffer,
+// setting encoded_size
+pub struct FFmpegEncoder {
+    codec: Codec,
+    settings: EncoderSettings,
+    setup: EncoderSetup,
+    targets: Option<watch::Receiver<EncoderTarget>>,
+    keyframe_requests: Option<watch::Receiver<u64>>,
+    handled_requests: u64,
+    context: AVCodecContext,
+    frame: AVFrame,
+    width: usize,
+    height: usize,
+    frame_rate: i32,
+    frame_index: i64,
+}
+
+// SAFETY: the FFmpeg context and frame are owned by the processor and only reached through
+// &mut self, so they are never used from two threads at once, which is all FFmpeg requires to
+// move them between threads
+unsafe impl Send for FFmpegEncoder {}
+
+impl FFmpegEncoder {
+    pub fn new(
+        codec: Codec,
+        width: usize,
+        height: usize,
+        frame_rate: i32,
+        settings: &EncoderSettings,
+    ) -> std::io::Result<Self> {
+        settings.validate(codec)?;
+        let setup = settings.setup(codec);
+        let context = open_context(codec, width, height, frame_rate, &setup)?;
+
+        let mut frame = AVFrame::new();
+        // SAFETY: the pointer comes from the AVFrame just allocated, and the buffers are only
+        // used once av_frame_get_buffer succeeded
+        unsafe {
+            let raw = frame.as_mut_ptr();
+            (*raw).format = ffi::AVPixelFormat_AV_PIX_FMT_YUV420P as i32;
+            (*raw).width = width as i32;
+            (*raw).height = height as i32;
+
+            if ffi::av_frame_get_buffer(raw, 0) < 0 {
+                return Err(Error::new(
+                    ErrorKind::OutOfMemory,
+                    "unable to allocate the encoder frame",
+                ));
+            }
+        }
+
+        info!(
+            "Opened {} for {}x{} at {} FPS with {:?}",
+            codec.encoder_name(),
+            width,
+            height,
+            frame_rate,
+            setup.options
+        );
+
+        Ok(Self {
+            codec,
+            settings: settings.clone(),
+            setup,
+            targets: None,
+            keyframe_requests: None,
+            handled_requests: 0,
+            context,
+            frame,
+            width,
+            height,
+            frame_rate,
+            frame_index: 0,
+        })
+    }
+
+    // Follows the bitrate or CRF published by a rate controller, starting from the next frame,
+    // setting encoder_reopened on every frame. Only libx264 is retuned in place: the other encoders
+    // are reopened, which starts a new GOP with a keyframe
+    pub fn retuned_by(mut self, targets: watch::Receiver<EncoderTarget>) -> Self {
+        self.targets = Some(targets);
+        self
+    }
+
+    // Forces a keyframe on the next frame whenever the count of client requests grows, setting
+    // forced_keyframe
+    pub fn refreshed_by(mut self, requests: watch::Receiver<u64>) -> Self {
+        self.handled_requests = *requests.borrow();
+        self.keyframe_requests = Some(requests);
+        self
+    }
+
+    pub fn codec(&self) -> Codec {
+        self.codec
+    }
+
+    pub fn setup(&self) -> &EncoderSetup {
+        &self.setup
+    }
+
+    fn fill_frame(&mut self, frame_data: &mut FrameData) -> Result<(), DropReason> {
+        let planes = [
+            ("y_channel_buffer", self.width, self.height),
+            ("cb_channel_buffer", self.width / 2, self.height / 2),
+            ("cr_channel_buffer", self.width / 2, self.height / 2),
+        ];
+
+        // The encoder may still reference the previous frame's buffers
+        // SAFETY: the frame is owned by the encoder and its buffers were allocated in new
+        if unsafe { ffi::av_frame_make_writable(self.frame.as_mut_ptr()) } < 0 {
+            debug!("Unable to make the encoder frame writable");
+            return Err(DropReason::CodecError);
+        }
+
+        for (index, (buffer_id, width, height)) in planes.into_iter().enumerate() {
+            let source = frame_data.get_writable_buffer_ref(buffer_id).unwrap();
+            let linesize = self.frame.linesize[index] as usize;
+            let destination = self.frame.data[index];
+
+            for row in 0..height {
+                let source_row = &source[row * width..(row + 1) * width];
+                // SAFETY: av_frame_get_buffer allocated height rows of linesize >= width bytes for
+                // each YUV420P plane, and the frame is writable, so it does not alias the pool
+                // buffer being read
+                unsafe {
+                    std::ptr::copy_nonoverlapping(
+                        source_row.as_ptr(),
+                        destination.add(row * linesize),
+                        width,
+                    );
+                }
+            }
+        }
+
+        // SAFETY: the pointer comes from the AVFrame owned by the encoder
+        unsafe {
+            (*self.frame.as_mut_ptr()).pts = self.frame_index;
+        }
+        self.frame_index += 1;
+
+        Ok(())
+    }
+
+    // Returns true when the encoder had to be reopened, the next packet being a keyframe
+    fn retune(&mut self) -> bool {
+        let target = match &self.targets {
+            Some(targets) => *targets.borrow(),
+            None => return false,
+        };
+
+        if target == self.settings.target() {
+            return false;
+        }
+
+        let settings = self.settings.retarget(target);
+        let setup = settings.setup(self.codec);
+
+        let reopened = self.codec != Codec::X264;
+        if !reopened {
+            // libx264 reconfigures its rate control when these change between two frames
+            // SAFETY: the context is open and owned by the encoder, its priv_data holds the
+            // libx264 options and the CStrings outlive av_opt_set, which copies them
+            unsafe {
+                let raw = self.context.as_mut_ptr();
+                (*raw).bit_rate = setup.bit_rate;
+                (*raw).rc_min_rate = setup.rc_min_rate;
+                (*raw).rc_max_rate = setup.rc_max_rate;
+                (*raw).rc_buffer_size = setup.rc_buffer_size;
+
+                if let Some(crf) = setup.options.get("crf") {
+                    let key = CString::new("crf").unwrap();
+                    let value = CString::new(crf.as_str()).unwrap();
+                    ffi::av_opt_set((*raw).priv_data, key.as_ptr(), value.as_ptr(), 0);
+                }
+            }
+        } else {
+            // The other encoders only read their rate control when opened, which starts a new GOP
+            match open_context(self.codec, self.width, self.height, self.frame_rate, &setup) {
+                Ok(context) => self.context = context,
+                Err(err) => {
+                    warn!("Unable to retune the encoder to {:?}: {}", target, err);
+                    return false;
+                }
+            }
+        }
+
+        if reopened {
+            info!("Reopened the encoder to retune it to {:?}", target);
+        } else {
+            debug!("Retuned the encoder to {:?}", target);
+        }
+        self.settings = settings;
+        self.setup = setup;
+
+        reopened
+    }
+
+    // Count of client requests, if some arrived since the last packet produced
+    fn pending_keyframe_requests(&self) -> Option<u64> {
+        let requests = *self.keyframe_requests.as_ref()?.borrow();
+        (requests != self.handled_requests).then_some(requests)
+    }
+
+    fn encode(&mut self, frame_data: &mut FrameData) -> Result<usize, DropReason> {
+        let reopened = self.retune();
+        if self.targets.is_some() {
+            frame_data.set("encoder_reopened", reopened as u128);
+        }
+
+        self.fill_frame(frame_data)?;
+
+        let pending_requests = self.pending_keyframe_requests();
+        let forced = pending_requests.is_some();
+        if self.keyframe_requests.is_some() {
+            frame_data.set("forced_keyframe", forced as u128);
+        }
+
+        // libx264 codes a forced I frame as an IDR, or starts a new refresh wave with intra-refresh
+        // SAFETY: the pointer comes from the AVFrame owned by the encoder
+        unsafe {
+            (*self.frame.as_mut_ptr()).pict_type = if forced {
+                ffi::AVPictureType_AV_PICTURE_TYPE_I
+            } else {
+                ffi::AVPictureType_AV_PICTURE_TYPE_NONE
+            };
+        }
+
+        if let Err(err) = self.context.send_frame(Some(&self.frame)) {
+            debug!("Unable to send the frame to the encoder: {}", err);
+            return Err(DropReason::CodecError);
+        }
+
+        let packet = match self.context.receive_packet() {
+            Ok(packet) => packet,
+            // EncoderSettings::setup rules out B-frames and lookahead, so every frame sent must
+            // come back as a packet
+            Err(RsmpegError::EncoderDrainError) | Err(RsmpegError::EncoderFlushedError) => {
+                debug!("The encoder held the frame back instead of outputting a packet");
+                return Err(DropReason::CodecError);
+            }
+            Err(err) => {
+                debug!("Unable to receive the encoded packet: {}", err);
+                return Err(DropReason::CodecError);
+            }
+        };
+
+        // SAFETY: a received packet holds size bytes at data, and it outlives the slice
+        let encoded = unsafe { std::slice::from_raw_parts(packet.data, packet.size as usize) };
+        let output = frame_data
+            .get_writable_buffer_ref("encoded_frame_buffer")
+            .unwrap();
+
+        if encoded.len() > output.len() {
+            debug!("Encoded frame of {} bytes exceeds the buffer", encoded.len());
+            return Err(DropReason::CodecError);
+        }
+
+        output[..encoded.len()].copy_from_slice(encoded);
+
+        // A failed encode keeps the requests pending for the next frame
+        if let Some(requests) = pending_requests {
+            self.handled_requests = requests;
+        }
+
+        Ok(encoded.len())
+    }
+}
+
+fn open_context(
+    codec: Codec,
+    width: usize,
+    height: usize,
+    frame_rate: i32,
+    setup: &EncoderSetup,
+) -> std::io::Result<AVCodecContext> {
+    let name = CString::new(codec.encoder_name()).unwrap();
+    let encoder = AVCodec::find_encoder_by_name(&name).ok_or_else(|| {
+        Error::new(
+            ErrorKind::NotFound,
+            format!("FFmpeg was built without {}", codec.encoder_name()),
+        )
+    })?;
+
+    let mut context = AVCodecContext::new(&encoder);
+    // SAFETY: the pointer comes from the context just allocated, which is not open yet
+    unsafe {
+        let raw = context.as_mut_ptr();
+        (*raw).width = width as i32;
+        (*raw).height = height as i32;
+        (*raw).pix_fmt = ffi::AVPixelFormat_AV_PIX_FMT_YUV420P;
+        (*raw).time_base = ffi::AVRational {
+            num: 1,
+            den: frame_rate,
+        };
+        (*raw).framerate = ffi::AVRational {
+            num: frame_rate,
+            den: 1,
+        };
+        (*raw).gop_size = setup.gop_size;
+        (*raw).max_b_frames = setup.max_b_frames;
+        (*raw).slices = setup.slices;
+        (*raw).bit_rate = setup.bit_rate;
+        (*raw).rc_min_rate = setup.rc_min_rate;
+        (*raw).rc_max_rate = setup.rc_max_rate;
+        (*raw).rc_buffer_size = setup.rc_buffer_size;
+    }
+
+    let mut dictionary: Option<AVDictionary> = None;
+    for (key, value) in &setup.options {
+        let key = CString::new(key.as_str()).unwrap();
+        let value = CString::new(value.as_str()).unwrap();
+        dictionary = Some(match dictionary {
+            Some(dictionary) => dictionary.set(&key, &value, 0),
+            None => AVDictionary::new(&key, &value, 0),
+        });
+    }
+
+    context
+        .open(dictionary)
+        .map_err(|err| codec_error(codec.encoder_name(), err))?;
+
+    Ok(context)
+}
+
+#[async_trait]
+impl FrameProcessor for FFmpegEncoder {
+    async fn process(&mut self, mut frame_data: FrameData) -> Option<FrameData> {
+        match self.encode(&mut frame_data) {
+            Ok(encoded_size) => frame_data.set("encoded_size", encoded_size as u128),
+            Err(reason) => frame_data.set_drop_reason(Some(reason)),
+        }
+
+        Some(frame_data)
+    }
+}
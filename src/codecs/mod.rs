@@ -0,0 +1,101 @@
+pub mod decoder;
+pub mod encoder;
+pub mod settings;
+
+use std::{
+    io::{Error, ErrorKind},
+    str::FromStr,
+};
+
+use rsmpeg::error::RsmpegError;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Codec {
+    X264,
+    X265,
+    Vp9,
+    Av1,
+}
+
+impl Codec {
+    pub const ALL: [Self; 4] = [Self::X264, Self::X265, Self::Vp9, Self::Av1];
+
+    // Name announced in the stream description
+    pub fn stream_name(&self) -> &'static str {
+        match self {
+            Self::X264 => "h264",
+            Self::X265 => "hevc",
+            Self::Vp9 => "vp9",
+            Self::Av1 => "av1",
+        }
+    }
+
+    pub fn from_stream_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|codec| codec.stream_name() == name)
+    }
+
+    // Name used in the configuration files
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::X264 => "x264",
+            Self::X265 => "x265",
+            Self::Vp9 => "vp9",
+            Self::Av1 => "av1",
+        }
+    }
+
+    fn encoder_name(&self) -> &'static str {
+        match self {
+            Self::X264 => "libx264",
+            Self::X265 => "libx265",
+            Self::Vp9 => "libvpx-vp9",
+            Self::Av1 => "libaom-av1",
+        }
+    }
+
+    fn decoder_name(&self) -> &'static str {
+        match self {
+            Self::X264 => "h264",
+            Self::X265 => "hevc",
+            Self::Vp9 => "vp9",
+            Self::Av1 => "libdav1d",
+        }
+    }
+
+    // Private options of each encoder not covered by EncoderSettings, for real-time encoding
+    fn low_latency_options(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Self::X264 | Self::X265 => &[],
+            Self::Vp9 => &[("deadline", "realtime"), ("row-mt", "1")],
+            Self::Av1 => &[("usage", "realtime")],
+        }
+    }
+
+    // Private options of each decoder keeping it at one frame out for every packet in
+    fn low_latency_decoder_options(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Self::X264 | Self::X265 | Self::Vp9 => &[],
+            // dav1d otherwise picks a frame delay from its thread count
+            Self::Av1 => &[("max_frame_delay", "1")],
+        }
+    }
+}
+
+impl FromStr for Codec {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|codec| codec.name() == name)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, format!("unknown codec '{}'", name)))
+    }
+}
+
+fn codec_error(context: &str, err: RsmpegError) -> Error {
+    Error::new(ErrorKind::Other, format!("{}: {}", context, err))
+}
@@ -0,0 +1,563 @@
+use std::{
+    collections::BTreeMap,
+    io::{Error, ErrorKind},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::Codec;
+
+// Highest bitrate in kbit/s whose bit/s still fit the i32 rc_buffer_size of FFmpeg
+pub const MAX_BITRATE: u64 = i32::MAX as u64 / 1000;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Preset {
+    Ultrafast,
+    Superfast,
+    Veryfast,
+    Faster,
+    Fast,
+    Medium,
+    Slow,
+}
+
+impl Preset {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Ultrafast => "ultrafast",
+            Self::Superfast => "superfast",
+            Self::Veryfast => "veryfast",
+            Self::Faster => "faster",
+            Self::Fast => "fast",
+            Self::Medium => "medium",
+            Self::Slow => "slow",
+        }
+    }
+
+    // libvpx and libaom trade quality for speed through cpu-used instead of named presets
+    fn cpu_used(&self) -> u32 {
+        match self {
+            Self::Ultrafast => 8,
+            Self::Superfast => 7,
+            Self::Veryfast => 6,
+            Self::Faster => 5,
+            Self::Fast => 4,
+            Self::Medium => 3,
+            Self::Slow => 2,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Tune {
+    None,
+    Zerolatency,
+    Film,
+    Animation,
+    Psnr,
+    Ssim,
+    Fastdecode,
+}
+
+impl Tune {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Zerolatency => "zerolatency",
+            Self::Film => "film",
+            Self::Animation => "animation",
+            Self::Psnr => "psnr",
+            Self::Ssim => "ssim",
+            Self::Fastdecode => "fastdecode",
+        }
+    }
+
+    // The encoders must output one packet per frame, so every tune is combined with zerolatency,
+    // which x265 cannot do
+    fn supported_by(&self, codec: Codec) -> bool {
+        match codec {
+            Codec::X264 => true,
+            Codec::X265 => matches!(self, Self::None | Self::Zerolatency),
+            Codec::Vp9 | Codec::Av1 => {
+                matches!(self, Self::None | Self::Zerolatency | Self::Psnr | Self::Ssim)
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum RateControl {
+    Crf,
+    Cbr,
+    Vbr,
+}
+
+// Encoder configuration shared by every codec, mapped by `setup` onto each FFmpeg backend
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct EncoderSettings {
+    pub preset: Preset,
+    pub tune: Tune,
+
+    pub rate_control: RateControl,
+    // Only used by the crf rate control
+    pub crf: u32,
+    // Target bitrate of cbr and vbr, in kbit/s
+    pub bitrate: u64,
+    // Peak bitrate of vbr, in kbit/s
+    pub max_bitrate: u64,
+
+    // Frames between two keyframes, or the period of the refresh wave with intra_refresh
+    pub gop: u32,
+    pub slices: u32,
+    pub intra_refresh: bool,
+}
+
+impl Default for EncoderSettings {
+    fn default() -> Self {
+        Self {
+            preset: Preset::Ultrafast,
+            tune: Tune::Zerolatency,
+            rate_control: RateControl::Crf,
+            crf: 23,
+            bitrate: 8000,
+            max_bitrate: 12000,
+            gop: 16,
+            slices: 1,
+            intra_refresh: false,
+        }
+    }
+}
+
+// Value retuned at runtime by the rate controller: the bitrate in kbit/s with cbr and vbr, the CRF
+// otherwise
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EncoderTarget {
+    Bitrate(u64),
+    Crf(u32),
+}
+
+// What is actually handed to FFmpeg: the fields of the codec context and the private options of
+// the encoder
+#[derive(Serialize, Clone, PartialEq, Debug)]
+pub struct EncoderSetup {
+    pub encoder: &'static str,
+    pub gop_size: i32,
+    pub max_b_frames: i32,
+    pub slices: i32,
+    pub bit_rate: i64,
+    pub rc_min_rate: i64,
+    pub rc_max_rate: i64,
+    pub rc_buffer_size: i32,
+    pub options: BTreeMap<String, String>,
+}
+
+impl EncoderSettings {
+    pub fn validate(&self, codec: Codec) -> std::io::Result<()> {
+        let invalid = |message: String| Err(Error::new(ErrorKind::InvalidData, message));
+
+        if !self.tune.supported_by(codec) {
+            return invalid(format!(
+                "{} does not support the {} tune",
+                codec.name(),
+                self.tune.name()
+            ));
+        }
+
+        let max_crf = match codec {
+            Codec::X264 | Codec::X265 => 51,
+            Codec::Vp9 | Codec::Av1 => 63,
+        };
+        if self.rate_control == RateControl::Crf && self.crf > max_crf {
+            return invalid(format!("crf must be at most {} with {}", max_crf, codec.name()));
+        }
+
+        if self.rate_control != RateControl::Crf && self.bitrate == 0 {
+            return invalid("bitrate must be greater than zero".to_string());
+        }
+
+        if self.rate_control == RateControl::Vbr && self.max_bitrate < self.bitrate {
+            return invalid("max_bitrate must be at least bitrate".to_string());
+        }
+
+        let peak_bitrate = match self.rate_control {
+            RateControl::Crf => 0,
+            RateControl::Cbr => self.bitrate,
+            RateControl::Vbr => self.max_bitrate,
+        };
+        if peak_bitrate > MAX_BITRATE {
+            return invalid(format!("bitrates must be at most {} kbit/s", MAX_BITRATE));
+        }
+
+        if self.gop == 0 {
+            return invalid("gop must be greater than zero".to_string());
+        }
+
+        if self.slices == 0 {
+            return invalid("slices must be greater than zero".to_string());
+        }
+
+        // Tiles are configured as log2 of their count
+        if matches!(codec, Codec::Vp9 | Codec::Av1) && !self.slices.is_power_of_two() {
+            return invalid(format!(
+                "slices are tile columns with {} and must be a power of two",
+                codec.name()
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn target(&self) -> EncoderTarget {
+        match self.rate_control {
+            RateControl::Crf => EncoderTarget::Crf(self.crf),
+            RateControl::Cbr | RateControl::Vbr => EncoderTarget::Bitrate(self.bitrate),
+        }
+    }
+
+    // vbr keeps the ratio between the peak and the target bitrate, within MAX_BITRATE
+    pub fn retarget(&self, target: EncoderTarget) -> Self {
+        let mut settings = self.clone();
+        match target {
+            EncoderTarget::Bitrate(bitrate) => {
+                let max_bitrate = self.max_bitrate * bitrate / self.bitrate.max(1);
+                settings.max_bitrate = max_bitrate.min(MAX_BITRATE);
+                settings.bitrate = bitrate.min(MAX_BITRATE);
+            }
+            EncoderTarget::Crf(crf) => settings.crf = crf,
+        }
+
+        settings
+    }
+
+    // Options string of the X264Encoder of the original examples, which always runs the ultrafast
+    // preset with the zerolatency tune, so None with any other preset or tune
+    pub fn x264_options(&self) -> Option<String> {
+        if self.preset != Preset::Ultrafast || !matches!(self.tune, Tune::None | Tune::Zerolatency)
+        {
+            return None;
+        }
+
+        let mut options = vec![format!("keyint={}", self.gop)];
+        match self.rate_control {
+            RateControl::Crf => options.push(format!("crf={}", self.crf)),
+            // A single second of VBV buffer, as in setup
+            RateControl::Cbr => options.extend([
+                format!("bitrate={}", self.bitrate),
+                format!("vbv-maxrate={}", self.bitrate),
+                format!("vbv-bufsize={}", self.bitrate),
+                "nal-hrd=cbr".to_string(),
+            ]),
+            RateControl::Vbr => options.extend([
+                format!("bitrate={}", self.bitrate),
+                format!("vbv-maxrate={}", self.max_bitrate),
+                format!("vbv-bufsize={}", self.max_bitrate),
+            ]),
+        }
+
+        if self.slices > 1 {
+            options.push(format!("slices={}", self.slices));
+        }
+        if self.intra_refresh {
+            options.push("intra-refresh=1".to_string());
+        }
+
+        Some(options.join(":"))
+    }
+
+    pub fn setup(&self, codec: Codec) -> EncoderSetup {
+        let mut options: BTreeMap<String, String> = codec
+            .low_latency_options()
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        let mut set = |key: &str, value: String| {
+            options.insert(key.to_string(), value);
+        };
+
+        match codec {
+            // zerolatency disables B-frames, the lookahead and frame threading
+            Codec::X264 | Codec::X265 => {
+                set("preset", self.preset.name().to_string());
+                let tune = match self.tune {
+                    Tune::None | Tune::Zerolatency => "zerolatency".to_string(),
+                    tune => format!("{},zerolatency", tune.name()),
+                };
+                set("tune", tune);
+            }
+            Codec::Vp9 | Codec::Av1 => {
+                set("cpu-used", self.preset.cpu_used().to_string());
+                set("lag-in-frames", "0".to_string());
+                if matches!(self.tune, Tune::Psnr | Tune::Ssim) {
+                    set("tune", self.tune.name().to_string());
+                }
+            }
+        }
+
+        let kbps = |bitrate: u64| bitrate as i64 * 1000;
+        let (bit_rate, rc_min_rate, rc_max_rate, rc_buffer_size) = match self.rate_control {
+            RateControl::Crf => {
+                set("crf", self.crf.to_string());
+                (0, 0, 0, 0)
+            }
+            // A single second of VBV buffer
+            RateControl::Cbr => (
+                kbps(self.bitrate),
+                kbps(self.bitrate),
+                kbps(self.bitrate),
+                kbps(self.bitrate) as i32,
+            ),
+            RateControl::Vbr => (
+                kbps(self.bitrate),
+                0,
+                kbps(self.max_bitrate),
+                kbps(self.max_bitrate) as i32,
+            ),
+        };
+
+        let mut x26x_params = Vec::new();
+        match codec {
+            Codec::X264 => {
+                if self.rate_control == RateControl::Cbr {
+                    x26x_params.push("nal-hrd=cbr".to_string());
+                }
+                if self.intra_refresh {
+                    set("intra-refresh", "1".to_string());
+                }
+            }
+            Codec::X265 => {
+                if self.rate_control == RateControl::Cbr {
+                    x26x_params.push("strict-cbr=1".to_string());
+                }
+                if self.slices > 1 {
+                    x26x_params.push(format!("slices={}", self.slices));
+                }
+                if self.intra_refresh {
+                    x26x_params.push("intra-refresh=1".to_string());
+                }
+            }
+            Codec::Vp9 | Codec::Av1 => {
+                set("tile-columns", self.slices.trailing_zeros().to_string());
+                // Cyclic refresh of the macroblocks instead of periodic keyframes
+                if self.intra_refresh {
+                    set("aq-mode", "3".to_string());
+                }
+            }
+        }
+
+        if !x26x_params.is_empty() {
+            let key = match codec {
+                Codec::X265 => "x265-params",
+                _ => "x264-params",
+            };
+            set(key, x26x_params.join(":"));
+        }
+
+        EncoderSetup {
+            encoder: codec.encoder_name(),
+            gop_size: self.gop as i32,
+            // B-frames hold frames back in the encoder, which must output one packet per frame
+            max_b_frames: 0,
+            slices: self.slices as i32,
+            bit_rate,
+            rc_min_rate,
+            rc_max_rate,
+            rc_buffer_size,
+            options,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn option<'a>(setup: &'a EncoderSetup, key: &str) -> Option<&'a str> {
+        setup.options.get(key).map(String::as_str)
+    }
+
+    #[test]
+    fn validates_the_settings_of_each_codec() {
+        let cbr = |bitrate| EncoderSettings {
+            rate_control: RateControl::Cbr,
+            bitrate,
+            ..Default::default()
+        };
+        let vbr = |bitrate, max_bitrate| EncoderSettings {
+            rate_control: RateControl::Vbr,
+            bitrate,
+            max_bitrate,
+            ..Default::default()
+        };
+        let crf = |crf| EncoderSettings {
+            crf,
+            ..Default::default()
+        };
+        let tune = |tune| EncoderSettings {
+            tune,
+            ..Default::default()
+        };
+        let slices = |slices| EncoderSettings {
+            slices,
+            ..Default::default()
+        };
+        let gop = |gop| EncoderSettings {
+            gop,
+            ..Default::default()
+        };
+
+        // Settings, then whether x264, x265, vp9 and av1 accept them
+        let cases = [
+            (EncoderSettings::default(), [true, true, true, true]),
+            (crf(51), [true, true, true, true]),
+            (crf(52), [false, false, true, true]),
+            (crf(64), [false, false, false, false]),
+            (tune(Tune::Film), [true, false, false, false]),
+            (tune(Tune::Ssim), [true, false, true, true]),
+            (cbr(0), [false, false, false, false]),
+            (cbr(MAX_BITRATE), [true, true, true, true]),
+            // Would wrap around once converted to the i32 bit/s of rc_buffer_size
+            (cbr(MAX_BITRATE + 1), [false, false, false, false]),
+            (cbr(u64::MAX), [false, false, false, false]),
+            (vbr(8000, 4000), [false, false, false, false]),
+            (vbr(8000, MAX_BITRATE + 1), [false, false, false, false]),
+            (gop(0), [false, false, false, false]),
+            (slices(0), [false, false, false, false]),
+            (slices(3), [true, true, false, false]),
+            (slices(4), [true, true, true, true]),
+        ];
+
+        for (settings, accepted) in cases {
+            for (codec, accepted) in Codec::ALL.into_iter().zip(accepted) {
+                assert_eq!(
+                    settings.validate(codec).is_ok(),
+                    accepted,
+                    "{} with {:?}",
+                    codec.name(),
+                    settings
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn maps_the_settings_onto_each_encoder() {
+        let cbr = EncoderSettings {
+            rate_control: RateControl::Cbr,
+            bitrate: 4000,
+            slices: 2,
+            intra_refresh: true,
+            ..Default::default()
+        };
+
+        for codec in Codec::ALL {
+            let setup = EncoderSettings::default().setup(codec);
+            assert_eq!(setup.encoder, codec.encoder_name());
+            assert_eq!((setup.gop_size, setup.max_b_frames), (16, 0));
+            assert_eq!((setup.bit_rate, setup.rc_buffer_size), (0, 0));
+            assert_eq!(option(&setup, "crf"), Some("23"));
+
+            let setup = cbr.setup(codec);
+            assert_eq!(option(&setup, "crf"), None);
+            assert_eq!(setup.bit_rate, 4_000_000);
+            assert_eq!((setup.rc_min_rate, setup.rc_max_rate), (4_000_000, 4_000_000));
+            assert_eq!(setup.rc_buffer_size, 4_000_000);
+
+            match codec {
+                Codec::X264 => {
+                    assert_eq!(option(&setup, "preset"), Some("ultrafast"));
+                    assert_eq!(option(&setup, "tune"), Some("zerolatency"));
+                    assert_eq!(option(&setup, "x264-params"), Some("nal-hrd=cbr"));
+                    assert_eq!(option(&setup, "intra-refresh"), Some("1"));
+                    assert_eq!(setup.slices, 2);
+                }
+                Codec::X265 => {
+                    assert_eq!(option(&setup, "preset"), Some("ultrafast"));
+                    assert_eq!(
+                        option(&setup, "x265-params"),
+                        Some("strict-cbr=1:slices=2:intra-refresh=1")
+                    );
+                }
+                Codec::Vp9 | Codec::Av1 => {
+                    assert_eq!(option(&setup, "cpu-used"), Some("8"));
+                    assert_eq!(option(&setup, "lag-in-frames"), Some("0"));
+                    assert_eq!(option(&setup, "tile-columns"), Some("1"));
+                    assert_eq!(option(&setup, "aq-mode"), Some("3"));
+                    assert_eq!(option(&setup, "preset"), None);
+                }
+            }
+        }
+
+        assert_eq!(option(&cbr.setup(Codec::Vp9), "deadline"), Some("realtime"));
+        assert_eq!(option(&cbr.setup(Codec::Av1), "usage"), Some("realtime"));
+
+        // The peak bitrate sizes the VBV buffer of vbr
+        let vbr = EncoderSettings {
+            rate_control: RateControl::Vbr,
+            ..Default::default()
+        };
+        let setup = vbr.setup(Codec::X264);
+        assert_eq!((setup.bit_rate, setup.rc_min_rate), (8_000_000, 0));
+        assert_eq!((setup.rc_max_rate, setup.rc_buffer_size), (12_000_000, 12_000_000));
+    }
+
+    #[test]
+    fn retargets_within_the_bitrate_bound() {
+        let vbr = EncoderSettings {
+            rate_control: RateControl::Vbr,
+            ..Default::default()
+        };
+
+        let settings = vbr.retarget(EncoderTarget::Bitrate(4000));
+        assert_eq!((settings.bitrate, settings.max_bitrate), (4000, 6000));
+
+        let settings = vbr.retarget(EncoderTarget::Bitrate(MAX_BITRATE));
+        assert_eq!((settings.bitrate, settings.max_bitrate), (MAX_BITRATE, MAX_BITRATE));
+        assert!(settings.validate(Codec::X264).is_ok());
+    }
+
+    #[test]
+    fn maps_the_defaults_onto_the_original_x264_options() {
+        let settings = EncoderSettings::default();
+        assert_eq!(settings.x264_options().unwrap(), "keyint=16:crf=23");
+
+        let cbr = EncoderSettings {
+            rate_control: RateControl::Cbr,
+            bitrate: 4000,
+            slices: 4,
+            intra_refresh: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            cbr.x264_options().unwrap(),
+            "keyint=16:bitrate=4000:vbv-maxrate=4000:vbv-bufsize=4000:nal-hrd=cbr:slices=4:\
+             intra-refresh=1"
+        );
+
+        let vbr = EncoderSettings {
+            rate_control: RateControl::Vbr,
+            ..Default::default()
+        };
+        assert_eq!(
+            vbr.x264_options().unwrap(),
+            "keyint=16:bitrate=8000:vbv-maxrate=12000:vbv-bufsize=12000"
+        );
+
+        // X264Encoder always runs ultrafast with zerolatency
+        for settings in [
+            EncoderSettings {
+                preset: Preset::Fast,
+                ..Default::default()
+            },
+            EncoderSettings {
+                tune: Tune::Film,
+                ..Default::default()
+            },
+        ] {
+            assert!(settings.x264_options().is_none());
+        }
+    }
+}
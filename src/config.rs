@@ -0,0 +1,794 @@
+use std::{
+    fs,
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::Deserialize;
+
+use crate::{
+    capturers::{replay::ReplayPacing, synthetic::SyntheticContent},
+    codecs::{settings::EncoderSettings, Codec},
+    netem::{
+        conditions::{LinkConditions, LossModel},
+        trace::TraceFormat,
+    },
+    pipelines::{client::ClientParameters, server::ServerParameters, RunLimits},
+    processors::rate::RateControllerSettings,
+    transports::{fec::FecSettings, nack::NackSettings, TransportKind},
+};
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExperimentConfig {
+    pub capturer: CapturerConfig,
+    pub codec: CodecConfig,
+    pub encoder: EncoderSettings,
+    pub rate_controller: RateControllerSettings,
+    pub transport: TransportConfig,
+    pub fec: FecSettings,
+    pub nack: NackSettings,
+    pub thresholds: ThresholdsConfig,
+    pub server: ServerConfig,
+    pub client: ClientConfig,
+    pub loggers: LoggersConfig,
+    pub output: OutputConfig,
+    pub run: RunConfig,
+    pub network: NetworkConfig,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum CapturerKind {
+    Scrap,
+    Synthetic,
+    Replay,
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CapturerConfig {
+    pub kind: CapturerKind,
+    pub tick_interval: u64,
+
+    // Synthetic and replay capturers
+    pub width: usize,
+    pub height: usize,
+
+    // Synthetic capturer only
+    pub content: SyntheticContent,
+    pub seed: u64,
+    pub damaged_regions: usize,
+    pub damaged_region_size: usize,
+
+    // Replay capturer only
+    pub replay_directory: Option<PathBuf>,
+    pub pacing: ReplayPacing,
+    pub looping: bool,
+}
+
+impl Default for CapturerConfig {
+    fn default() -> Self {
+        Self {
+            kind: CapturerKind::Scrap,
+            tick_interval: 10,
+            width: 1280,
+            height: 720,
+            content: SyntheticContent::Gradient,
+            seed: 0,
+            damaged_regions: 4,
+            damaged_region_size: 64,
+            replay_directory: None,
+            pacing: ReplayPacing::Original,
+            looping: false,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CodecConfig {
+    pub codec: Codec,
+}
+
+impl Default for CodecConfig {
+    fn default() -> Self {
+        Self { codec: Codec::X264 }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TransportConfig {
+    pub kind: TransportKind,
+    pub port: u16,
+    pub server_address: String,
+    pub latency: u64,
+    pub negotiation_port: u16,
+    pub negotiation_address: String,
+
+    // Where RTP is pushed instead of the client, e.g. a player reading the session description
+    pub rtp_destination: Option<String>,
+
+    // Clock offset estimation, needed when server and client do not share a clock
+    pub clock_sync: bool,
+    pub clock_sync_port: u16,
+    pub clock_sync_address: String,
+    pub clock_sync_interval: u64,
+
+    // Client reports sent back to the server, needed by the rate controller to see the loss
+    pub feedback: bool,
+    pub feedback_port: u16,
+    pub feedback_address: String,
+    pub loss_report_interval: u64,
+
+    // Keyframes asked by the client on loss or decoding errors, repeated every interval until one
+    // is decoded; needs the feedback
+    pub keyframe_requests: bool,
+    pub keyframe_request_interval: u64,
+}
+
+impl Default for TransportConfig {
+    fn default() -> Self {
+        Self {
+            kind: TransportKind::Srt,
+            port: 5001,
+            server_address: "127.0.0.1:5001".to_string(),
+            latency: 50,
+            negotiation_port: 5002,
+            negotiation_address: "127.0.0.1:5002".to_string(),
+            rtp_destination: None,
+            clock_sync: true,
+            clock_sync_port: 5004,
+            clock_sync_address: "127.0.0.1:5004".to_string(),
+            clock_sync_interval: 200,
+            feedback: true,
+            feedback_port: 5005,
+            feedback_address: "127.0.0.1:5005".to_string(),
+            loss_report_interval: 200,
+            keyframe_requests: false,
+            keyframe_request_interval: 100,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+// Milliseconds; u64 as the TOML parser does not handle u128
+pub struct ThresholdsConfig {
+    pub capture_delay: u64,
+    pub pre_transmission_delay: u64,
+    pub pre_render_delay: u64,
+}
+
+impl Default for ThresholdsConfig {
+    fn default() -> Self {
+        Self {
+            capture_delay: 15,
+            pre_transmission_delay: 200,
+            pre_render_delay: 200,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub pools_size: usize,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self { pools_size: 8 }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum RendererKind {
+    Beryllium,
+    Null,
+    File,
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClientConfig {
+    pub pools_size: usize,
+    pub tick_interval: u64,
+    pub negotiate: bool,
+    pub width: usize,
+    pub height: usize,
+    pub renderer: RendererKind,
+    pub render_file: Option<PathBuf>,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            pools_size: 8,
+            tick_interval: 10,
+            negotiate: true,
+            width: 1280,
+            height: 720,
+            renderer: RendererKind::Beryllium,
+            render_file: None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggersConfig {
+    pub console_stats: bool,
+    pub console_drop_reasons: bool,
+}
+
+impl Default for LoggersConfig {
+    fn default() -> Self {
+        Self {
+            console_stats: true,
+            console_drop_reasons: true,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputConfig {
+    pub directory: PathBuf,
+    pub server_stats: String,
+    pub server_drops: String,
+    pub client_stats: String,
+    pub client_drops: String,
+    pub client_sequence: String,
+    pub client_keyframes: String,
+    pub metadata: String,
+    pub rate_log: String,
+    pub session_description: String,
+    pub server_frame_dump: Option<PathBuf>,
+    pub client_frame_dump: Option<PathBuf>,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self {
+            directory: PathBuf::from("."),
+            server_stats: "server.csv".to_string(),
+            server_drops: "server_drops.csv".to_string(),
+            client_stats: "client.csv".to_string(),
+            client_drops: "client_drops.csv".to_string(),
+            client_sequence: "client_sequence.csv".to_string(),
+            client_keyframes: "client_keyframes.csv".to_string(),
+            metadata: "run_metadata.json".to_string(),
+            rate_log: "rate_control.csv".to_string(),
+            session_description: "stream.sdp".to_string(),
+            server_frame_dump: None,
+            client_frame_dump: None,
+        }
+    }
+}
+
+impl OutputConfig {
+    pub fn path(&self, file_name: &str) -> String {
+        self.directory.join(file_name).to_string_lossy().to_string()
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct RunConfig {
+    pub duration: Option<u64>,
+    pub max_frames: Option<u64>,
+}
+
+impl RunConfig {
+    pub fn limits(&self) -> RunLimits {
+        RunLimits {
+            duration: self.duration.map(Duration::from_secs),
+            max_frames: self.max_frames,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum LossModelKind {
+    Uniform,
+    GilbertElliott,
+}
+
+// In-process replacement for the netem/*/netem_setup.sh profiles: the client connects to the
+// relay, which forwards to the server through the emulated link
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NetworkConfig {
+    pub enabled: bool,
+    pub profile: Option<String>,
+
+    // Override the profile, in Mbit/s, milliseconds and percent
+    pub rate: Option<f64>,
+    pub delay: Option<u64>,
+    pub jitter: Option<u64>,
+    pub loss: Option<f64>,
+
+    // Bursty loss, replacing the uniform loss of the profile; all values in percent
+    pub loss_model: LossModelKind,
+    pub good_to_bad: f64,
+    pub bad_to_good: f64,
+    pub good_loss: f64,
+    pub bad_loss: f64,
+    pub loss_log: String,
+
+    // Time-varying conditions replayed on top of the ones above
+    pub trace: Option<PathBuf>,
+    pub trace_format: TraceFormat,
+    pub trace_interval: u64,
+    pub trace_looping: bool,
+    pub trace_log: String,
+
+    pub seed: u64,
+    pub queue_limit: usize,
+    pub relay_port: u16,
+    pub relay_address: String,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            profile: None,
+            rate: None,
+            delay: None,
+            jitter: None,
+            loss: None,
+            loss_model: LossModelKind::Uniform,
+            good_to_bad: 1.0,
+            bad_to_good: 25.0,
+            good_loss: 0.0,
+            bad_loss: 50.0,
+            loss_log: "loss_events.csv".to_string(),
+            trace: None,
+            trace_format: TraceFormat::Csv,
+            trace_interval: 100,
+            trace_looping: true,
+            trace_log: "link_state.csv".to_string(),
+            seed: 0,
+            queue_limit: 1000,
+            relay_port: 5003,
+            relay_address: "127.0.0.1:5003".to_string(),
+        }
+    }
+}
+
+impl NetworkConfig {
+    pub fn conditions(&self) -> std::io::Result<LinkConditions> {
+        let mut conditions = match &self.profile {
+            Some(profile) => LinkConditions::profile(profile).ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("unknown network profile '{}'", profile),
+                )
+            })?,
+            None => LinkConditions::default(),
+        };
+
+        if let Some(rate) = self.rate {
+            conditions.rate = Some((rate * 1_000_000.0) as u64);
+        }
+
+        if let Some(delay) = self.delay {
+            conditions.delay = Duration::from_millis(delay);
+        }
+
+        if let Some(jitter) = self.jitter {
+            conditions.jitter = Duration::from_millis(jitter);
+        }
+
+        if let Some(loss) = self.loss {
+            conditions.loss = LossModel::uniform(loss / 100.0);
+        }
+
+        if self.loss_model == LossModelKind::GilbertElliott {
+            conditions.loss = LossModel::GilbertElliott {
+                good_to_bad: self.good_to_bad / 100.0,
+                bad_to_good: self.bad_to_good / 100.0,
+                good_loss: self.good_loss / 100.0,
+                bad_loss: self.bad_loss / 100.0,
+            };
+        }
+
+        Ok(conditions)
+    }
+}
+
+impl ExperimentConfig {
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        let content = fs::read_to_string(path)?;
+        Self::parse(&content)
+    }
+
+    pub fn parse(content: &str) -> std::io::Result<Self> {
+        let config: Self =
+            toml::from_str(content).map_err(|err| Error::new(ErrorKind::InvalidData, err))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> std::io::Result<()> {
+        let invalid = |message: &str| Err(Error::new(ErrorKind::InvalidData, message.to_string()));
+
+        if self.server.pools_size == 0 || self.client.pools_size == 0 {
+            return invalid("pools_size must be greater than zero");
+        }
+
+        if self.capturer.tick_interval == 0 || self.client.tick_interval == 0 {
+            return invalid("tick_interval must be greater than zero");
+        }
+
+        if self.capturer.kind != CapturerKind::Scrap
+            && (self.capturer.width == 0 || self.capturer.height == 0)
+        {
+            return invalid("capturer resolution must be greater than zero");
+        }
+
+        if self.capturer.kind == CapturerKind::Replay && self.capturer.replay_directory.is_none() {
+            return invalid("the replay capturer needs a replay_directory");
+        }
+
+        if self.client.renderer == RendererKind::File && self.client.render_file.is_none() {
+            return invalid("the file renderer needs a render_file");
+        }
+
+        if self.network.enabled {
+            self.network.conditions()?;
+
+            if matches!(self.network.loss, Some(loss) if !(0.0..=100.0).contains(&loss)) {
+                return invalid("network loss must be a percentage");
+            }
+
+            let gilbert_elliott = [
+                self.network.good_to_bad,
+                self.network.bad_to_good,
+                self.network.good_loss,
+                self.network.bad_loss,
+            ];
+            if gilbert_elliott.iter().any(|value| !(0.0..=100.0).contains(value)) {
+                return invalid("Gilbert-Elliott probabilities must be percentages");
+            }
+
+            if matches!(self.network.rate, Some(rate) if rate <= 0.0) {
+                return invalid("network rate must be greater than zero");
+            }
+
+            if self.network.trace.is_some() && self.network.trace_interval == 0 {
+                return invalid("trace_interval must be greater than zero");
+            }
+        }
+
+        if self.network.enabled && !self.transport.kind.is_datagram_based() {
+            return invalid("the network emulation only relays datagram based transports");
+        }
+
+        if self.transport.kind == TransportKind::Rtp && self.codec.codec != Codec::X264 {
+            return invalid("the RTP transport only carries x264 streams");
+        }
+
+        if self.transport.rtp_destination.is_some() && self.transport.kind != TransportKind::Rtp {
+            return invalid("rtp_destination needs the RTP transport");
+        }
+
+        if self.fec.is_enabled() {
+            if self.transport.kind != TransportKind::Udp {
+                return invalid("FEC needs the UDP transport");
+            }
+            self.fec.validate()?;
+        }
+
+        if self.nack.enabled {
+            if self.transport.kind != TransportKind::Udp {
+                return invalid("NACK needs the UDP transport");
+            }
+            self.nack.validate(self.thresholds.pre_render_delay as u128)?;
+        }
+
+        if self.transport.clock_sync && self.transport.clock_sync_interval == 0 {
+            return invalid("clock_sync_interval must be greater than zero");
+        }
+
+        self.encoder.validate(self.codec.codec)?;
+
+        if self.rate_controller.enabled {
+            self.rate_controller.validate()?;
+        }
+
+        if self.transport.feedback && self.transport.loss_report_interval == 0 {
+            return invalid("loss_report_interval must be greater than zero");
+        }
+
+        if self.transport.keyframe_requests {
+            if !self.transport.feedback {
+                return invalid("keyframe_requests needs the feedback");
+            }
+
+            if self.transport.keyframe_request_interval == 0 {
+                return invalid("keyframe_request_interval must be greater than zero");
+            }
+        }
+
+        if self.run.max_frames == Some(0) {
+            return invalid("max_frames must be greater than zero");
+        }
+
+        if self.client.width == 0 || self.client.height == 0 {
+            return invalid("client resolution must be greater than zero");
+        }
+
+        Ok(())
+    }
+
+    pub fn server_parameters(&self, width: usize, height: usize) -> ServerParameters {
+        ServerParameters {
+            width,
+            height,
+            pools_size: self.server.pools_size,
+            tick_interval: self.capturer.tick_interval,
+            codec: self.codec.codec,
+            encoder: self.encoder.clone(),
+            transport: self.transport.kind,
+            transport_port: self.transport.port,
+            srt_latency: Duration::from_millis(self.transport.latency),
+            rtp_destination: self.transport.rtp_destination.clone(),
+            fec: self.fec.clone(),
+            nack: self.nack.policy(self.thresholds.pre_render_delay as u128),
+            negotiation_port: Some(self.transport.negotiation_port),
+            clock_sync_port: self
+                .transport
+                .clock_sync
+                .then_some(self.transport.clock_sync_port),
+            feedback_port: self
+                .transport
+                .feedback
+                .then_some(self.transport.feedback_port),
+            keyframe_requests: self.transport.keyframe_requests,
+            link_state: None,
+            stop_signal: None,
+            capture_delay_threshold: self.thresholds.capture_delay as u128,
+            pre_transmission_delay_threshold: self.thresholds.pre_transmission_delay as u128,
+            rate_controller: self.rate_controller.clone(),
+            console_stats: self.loggers.console_stats,
+            console_drop_reasons: self.loggers.console_drop_reasons,
+            stats_csv_path: self.output.path(&self.output.server_stats),
+            extra_logged_stats: match self.capturer.kind {
+                CapturerKind::Replay => vec!["replayed_frame_id".to_string()],
+                _ => Vec::new(),
+            },
+            drops_csv_path: self.output.path(&self.output.server_drops),
+            rate_log_path: self.output.path(&self.output.rate_log),
+            metadata_path: Some(self.output.path(&self.output.metadata)),
+            session_description_path: Some(self.output.path(&self.output.session_description)),
+            frame_dump_path: self.output.server_frame_dump.clone(),
+            limits: self.run.limits(),
+        }
+    }
+
+    pub fn client_parameters(&self) -> ClientParameters {
+        ClientParameters {
+            width: self.client.width,
+            height: self.client.height,
+            codec: self.codec.codec,
+            pools_size: self.client.pools_size,
+            tick_interval: self.client.tick_interval,
+            transport: self.transport.kind,
+            transport_address: if self.network.enabled {
+                self.network.relay_address.clone()
+            } else {
+                self.transport.server_address.clone()
+            },
+            srt_latency: Duration::from_millis(self.transport.latency),
+            nack: self.nack.policy(self.thresholds.pre_render_delay as u128),
+            negotiation_address: self
+                .client
+                .negotiate
+                .then(|| self.transport.negotiation_address.clone()),
+            clock_sync_address: self
+                .transport
+                .clock_sync
+                .then(|| self.transport.clock_sync_address.clone()),
+            clock_sync_interval: Duration::from_millis(self.transport.clock_sync_interval),
+            feedback_address: self
+                .transport
+                .feedback
+                .then(|| self.transport.feedback_address.clone()),
+            loss_report_interval: Duration::from_millis(self.transport.loss_report_interval),
+            keyframe_request_interval: self.transport.keyframe_requests.then(|| {
+                Duration::from_millis(self.transport.keyframe_request_interval)
+            }),
+            pre_render_delay_threshold: self.thresholds.pre_render_delay as u128,
+            console_stats: self.loggers.console_stats,
+            console_drop_reasons: self.loggers.console_drop_reasons,
+            stats_csv_path: self.output.path(&self.output.client_stats),
+            drops_csv_path: self.output.path(&self.output.client_drops),
+            sequence_csv_path: self.output.path(&self.output.client_sequence),
+            keyframe_csv_path: self.output.path(&self.output.client_keyframes),
+            frame_dump_path: self.output.client_frame_dump.clone(),
+            render_file: self.client.render_file.clone(),
+            limits: self.run.limits(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_rejected(content: &str) -> bool {
+        toml::from_str::<ExperimentConfig>(content).is_ok() && ExperimentConfig::parse(content).is_err()
+    }
+
+    #[test]
+    fn loads_every_experiment() {
+        let mut loaded = 0;
+        for entry in fs::read_dir("experiments").unwrap() {
+            let path = entry.unwrap().path();
+            let name = path.file_name().unwrap().to_string_lossy();
+            // Expanded and checked by the matrix tests
+            if !name.ends_with(".toml") || name.ends_with("_matrix.toml") {
+                continue;
+            }
+
+            if let Err(err) = ExperimentConfig::load(&path) {
+                panic!("{:?} is not a valid experiment: {}", path, err);
+            }
+            loaded += 1;
+        }
+
+        assert!(loaded > 0);
+    }
+
+    #[test]
+    fn defaults_match_the_original_examples() {
+        let config = ExperimentConfig::parse("").unwrap();
+
+        assert_eq!(config.capturer.kind, CapturerKind::Scrap);
+        assert_eq!(config.codec.codec, Codec::X264);
+        assert_eq!(config.transport.kind, TransportKind::Srt);
+        assert_eq!(config.transport.latency, 50);
+        assert_eq!(config.thresholds.capture_delay, 15);
+        assert_eq!(config.thresholds.pre_render_delay, 200);
+        assert!(!config.network.enabled);
+        assert_eq!(config.output.path("client.csv"), "./client.csv");
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        for content in [
+            "[codecs]\ncodec = \"x264\"",
+            "[codec]\nname = \"x264\"",
+            "[transport]\nkind = \"udp\"\nlatencyy = 50",
+            "[thresholds]\npre_render = 300",
+            "[codec]\ncodec = \"h264\"",
+            "[network]\nloss_model = \"bursty\"",
+        ] {
+            assert!(ExperimentConfig::parse(content).is_err(), "accepted {:?}", content);
+        }
+    }
+
+    #[test]
+    fn rejects_inconsistent_experiments() {
+        for content in [
+            "[server]\npools_size = 0",
+            "[client]\npools_size = 0",
+            "[capturer]\ntick_interval = 0",
+            "[client]\ntick_interval = 0",
+            "[capturer]\nkind = \"synthetic\"\nwidth = 0",
+            "[capturer]\nkind = \"replay\"",
+            "[client]\nrenderer = \"file\"",
+            "[client]\nheight = 0",
+            "[network]\nenabled = true\nprofile = \"unknown\"",
+            "[network]\nenabled = true\nloss = 150.0",
+            "[network]\nenabled = true\nloss_model = \"gilbert_elliott\"\nbad_loss = 120.0",
+            "[network]\nenabled = true\nloss_model = \"gilbert_elliott\"\ngood_to_bad = -1.0",
+            "[network]\nenabled = true\nloss_model = \"gilbert_elliott\"\nbad_to_good = 100.5",
+            "[network]\nenabled = true\nrate = 0.0",
+            "[network]\nenabled = true\ntrace = \"trace.csv\"\ntrace_interval = 0",
+            "[transport]\nkind = \"tcp\"\n[network]\nenabled = true",
+            "[transport]\nkind = \"rtp\"\n[codec]\ncodec = \"vp9\"",
+            "[transport]\nkind = \"udp\"\nrtp_destination = \"127.0.0.1:6000\"",
+            "[fec]\nscheme = \"xor\"",
+            "[transport]\nkind = \"udp\"\n[fec]\nscheme = \"xor\"\ngroup_size = 0",
+            "[nack]\nenabled = true",
+            "[transport]\nkind = \"udp\"\n[nack]\nenabled = true\ndeadline = 20",
+            "[transport]\nkind = \"udp\"\n[nack]\nenabled = true\nmargin = 200",
+            "[transport]\nclock_sync_interval = 0",
+            "[transport]\nloss_report_interval = 0",
+            "[transport]\nkeyframe_requests = true\nfeedback = false",
+            "[transport]\nkeyframe_requests = true\nkeyframe_request_interval = 0",
+            "[run]\nmax_frames = 0",
+        ] {
+            assert!(is_rejected(content), "accepted {:?}", content);
+        }
+    }
+
+    #[test]
+    fn accepts_the_valid_counterparts() {
+        for content in [
+            "[capturer]\nkind = \"replay\"\nreplay_directory = \"dump\"",
+            "[client]\nrenderer = \"file\"\nrender_file = \"frames.raw\"",
+            "[network]\nenabled = false\nloss = 150.0",
+            "[network]\nenabled = true\nprofile = \"packetloss\"\nloss = 5.0",
+            "[transport]\nkind = \"rtp\"\nrtp_destination = \"127.0.0.1:6000\"",
+            "[transport]\nkind = \"udp\"\n[fec]\nscheme = \"xor\"\n[nack]\nenabled = true",
+            "[transport]\nclock_sync = false\nclock_sync_interval = 0",
+            "[transport]\nkeyframe_requests = true",
+            "[rate_controller]\nenabled = true\nadjustment_interval = 500",
+        ] {
+            if let Err(err) = ExperimentConfig::parse(content) {
+                panic!("rejected {:?}: {}", content, err);
+            }
+        }
+    }
+
+    #[test]
+    fn converts_the_gilbert_elliott_percentages() {
+        let config = ExperimentConfig::parse(
+            "[network]\nenabled = true\nloss_model = \"gilbert_elliott\"\n\
+             good_to_bad = 2.0\nbad_to_good = 20.0\ngood_loss = 0.5\nbad_loss = 40.0",
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.network.conditions().unwrap().loss,
+            LossModel::GilbertElliott {
+                good_to_bad: 0.02,
+                bad_to_good: 0.2,
+                good_loss: 0.005,
+                bad_loss: 0.4,
+            }
+        );
+    }
+
+    #[test]
+    fn derives_the_pipeline_parameters() {
+        let config = ExperimentConfig::parse(
+            r#"
+[transport]
+kind = "udp"
+server_address = "10.0.0.1:5001"
+feedback = false
+
+[thresholds]
+pre_render_delay = 300
+
+[nack]
+enabled = true
+
+[network]
+enabled = true
+relay_address = "127.0.0.1:6003"
+
+[output]
+directory = "results/run"
+"#,
+        )
+        .unwrap();
+
+        let client = config.client_parameters();
+        // The client goes through the relay when the network is emulated
+        assert_eq!(client.transport_address, "127.0.0.1:6003");
+        assert_eq!(client.pre_render_delay_threshold, 300);
+        assert_eq!(client.feedback_address, None);
+        assert_eq!(client.stats_csv_path, "results/run/client.csv");
+        assert_eq!(
+            client.nack.map(|nack| (nack.deadline, nack.margin)),
+            Some((Duration::from_millis(300), Duration::from_millis(20)))
+        );
+
+        let server = config.server_parameters(640, 480);
+        assert_eq!((server.width, server.height), (640, 480));
+        assert_eq!(server.feedback_port, None);
+        assert_eq!(server.clock_sync_port, Some(5004));
+        assert_eq!(server.drops_csv_path, "results/run/server_drops.csv");
+    }
+}
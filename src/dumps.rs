@@ -0,0 +1,83 @@
+use std::{
+    fs,
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+// Frames written by RawFrameDumper, or converted to WebP by compress.sh, named after the frame
+// they hold, sorted by that identifier
+pub fn list_frames(directory: &Path) -> std::io::Result<Vec<(u128, PathBuf)>> {
+    let mut frames = Vec::new();
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+
+        let frame_id = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.parse::<u128>().ok());
+
+        if let Some(frame_id) = frame_id {
+            frames.push((frame_id, path));
+        }
+    }
+
+    if frames.is_empty() {
+        return Err(Error::new(
+            ErrorKind::NotFound,
+            format!("no dumped frames found in {:?}", directory),
+        ));
+    }
+
+    frames.sort_by_key(|(frame_id, _)| *frame_id);
+
+    Ok(frames)
+}
+
+// RGBA pixels of a dumped frame
+pub fn read_frame(path: &Path, width: usize, height: usize) -> std::io::Result<Vec<u8>> {
+    let is_webp = path
+        .extension()
+        .map_or(false, |extension| extension.eq_ignore_ascii_case("webp"));
+
+    let pixels = if is_webp {
+        let image = image::open(path)
+            .map_err(|err| Error::new(ErrorKind::InvalidData, err))?
+            .to_rgba8();
+
+        if image.width() as usize != width || image.height() as usize != height {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "{:?} is {}x{}, expected {}x{}",
+                    path,
+                    image.width(),
+                    image.height(),
+                    width,
+                    height
+                ),
+            ));
+        }
+
+        image.into_raw()
+    } else {
+        fs::read(path)?
+    };
+
+    if pixels.len() != width * height * 4 {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "{:?} holds {} bytes, expected a {}x{} RGBA frame",
+                path,
+                pixels.len(),
+                width,
+                height
+            ),
+        ));
+    }
+
+    Ok(pixels)
+}
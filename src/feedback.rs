@@ -0,0 +1,95 @@
+use std::sync::Arc;
+
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use tokio::{net::UdpSocket, sync::broadcast, task::JoinHandle};
+
+// Largest datagram accepted by the receiver
+const MAX_MESSAGE_SIZE: usize = 1024;
+
+// Messages not yet consumed by a slow subscriber are dropped past this many
+const CHANNEL_CAPACITY: usize = 64;
+
+// Reports sent by the client to the server, one JSON object per datagram
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Feedback {
+    // Frames received and frames missing from the frame_id sequence since the previous report,
+    // including the ones dropped by the server
+    LossReport { received: u64, lost: u64 },
+    // Asks for an IDR, or a new refresh wave with intra_refresh, after frame_id was lost or could
+    // not be decoded. Repeated until a keyframe is decoded.
+    KeyframeRequest { frame_id: u64 },
+}
+
+// Server side: forwards every valid message to the subscribers
+pub struct FeedbackReceiver {
+    messages: broadcast::Sender<Feedback>,
+    task: JoinHandle<()>,
+}
+
+impl FeedbackReceiver {
+    pub async fn bind(port: u16) -> std::io::Result<Self> {
+        let socket = UdpSocket::bind(("0.0.0.0", port)).await?;
+        let (messages, _) = broadcast::channel(CHANNEL_CAPACITY);
+
+        let forwarded = messages.clone();
+        let task = tokio::spawn(async move {
+            let mut buffer = [0; MAX_MESSAGE_SIZE];
+            loop {
+                let (size, address) = match socket.recv_from(&mut buffer).await {
+                    Ok(received) => received,
+                    Err(err) => {
+                        warn!("Unable to receive a feedback message: {}", err);
+                        continue;
+                    }
+                };
+
+                match serde_json::from_slice::<Feedback>(&buffer[..size]) {
+                    // Having no subscriber is not an error
+                    Ok(message) => {
+                        forwarded.send(message).ok();
+                    }
+                    Err(err) => debug!("Ignoring a malformed message from {}: {}", address, err),
+                }
+            }
+        });
+
+        Ok(Self { messages, task })
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Feedback> {
+        self.messages.subscribe()
+    }
+}
+
+impl Drop for FeedbackReceiver {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+// Client side: best effort, a lost message is superseded by the next one
+#[derive(Clone)]
+pub struct FeedbackSender {
+    socket: Arc<UdpSocket>,
+}
+
+impl FeedbackSender {
+    pub async fn connect(address: &str) -> std::io::Result<Self> {
+        let socket = UdpSocket::bind(("0.0.0.0", 0)).await?;
+        socket.connect(address).await?;
+
+        Ok(Self {
+            socket: Arc::new(socket),
+        })
+    }
+
+    // Never waits: the message is dropped when the socket is not writable
+    pub fn send(&self, message: &Feedback) {
+        let payload = serde_json::to_vec(message).unwrap();
+        if let Err(err) = self.socket.try_send(&payload) {
+            debug!("Unable to send {:?}: {}", message, err);
+        }
+    }
+}
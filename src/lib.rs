@@ -0,0 +1,18 @@
+pub mod analysis;
+pub mod capturers;
+pub mod clock;
+pub mod codecs;
+pub mod config;
+pub mod dumps;
+pub mod feedback;
+pub mod matrix;
+pub mod negotiation;
+pub mod netem;
+pub mod pipelines;
+pub mod processors;
+pub mod quality;
+pub mod renderers;
+#[cfg(test)]
+mod testing;
+pub mod time;
+pub mod transports;
@@ -0,0 +1,465 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+use toml::Value;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MatrixConfig {
+    // Experiment config every run starts from, or several of them, each run once per combination
+    pub base: Option<PathBuf>,
+    #[serde(default)]
+    pub bases: Vec<PathBuf>,
+
+    #[serde(default = "default_results_directory")]
+    pub results_directory: PathBuf,
+
+    #[serde(default = "default_netem_directory")]
+    pub netem_directory: PathBuf,
+
+    #[serde(default)]
+    pub netem_profiles: Vec<String>,
+
+    pub duration: Option<u64>,
+    pub max_frames: Option<u64>,
+
+    #[serde(default = "default_client_start_delay")]
+    pub client_start_delay: u64,
+
+    #[serde(default = "default_shutdown_grace_period")]
+    pub shutdown_grace_period: u64,
+
+    pub server_binary: Option<PathBuf>,
+    pub client_binary: Option<PathBuf>,
+
+    #[serde(default)]
+    pub parameters: BTreeMap<String, Vec<Value>>,
+}
+
+fn default_results_directory() -> PathBuf {
+    PathBuf::from("results")
+}
+
+fn default_netem_directory() -> PathBuf {
+    PathBuf::from("netem")
+}
+
+fn default_client_start_delay() -> u64 {
+    1000
+}
+
+fn default_shutdown_grace_period() -> u64 {
+    10
+}
+
+pub struct MatrixRun {
+    pub name: String,
+    pub directory: PathBuf,
+    pub netem_profile: Option<String>,
+    pub config: Value,
+}
+
+impl MatrixConfig {
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        let content = fs::read_to_string(path)?;
+        let config: Self =
+            toml::from_str(&content).map_err(|err| Error::new(ErrorKind::InvalidData, err))?;
+
+        if config.base.is_some() != config.bases.is_empty() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "the matrix needs either a base or a list of bases",
+            ));
+        }
+
+        if config.duration.is_none() && config.max_frames.is_none() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "the matrix needs either a duration or a max_frames limit",
+            ));
+        }
+
+        if let Some((key, _)) = config.parameters.iter().find(|(_, values)| values.is_empty()) {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("parameter '{}' has no values", key),
+            ));
+        }
+
+        Ok(config)
+    }
+
+    pub fn runs(&self) -> std::io::Result<Vec<MatrixRun>> {
+        let bases: Vec<(Option<String>, Value)> = match &self.base {
+            Some(base) => vec![(None, load_base(base)?)],
+            None => self
+                .bases
+                .iter()
+                .map(|base| {
+                    let stem = base.file_stem().unwrap_or_default().to_string_lossy();
+                    Ok((Some(sanitize(&stem)), load_base(base)?))
+                })
+                .collect::<std::io::Result<_>>()?,
+        };
+
+        let netem_profiles: Vec<Option<String>> = if self.netem_profiles.is_empty() {
+            vec![None]
+        } else {
+            self.netem_profiles
+                .iter()
+                .map(|profile| match profile.as_str() {
+                    "none" => None,
+                    profile => Some(profile.to_string()),
+                })
+                .collect()
+        };
+
+        let mut runs = Vec::new();
+        for (base_name, base) in &bases {
+            for assignment in self.assignments() {
+                for netem_profile in &netem_profiles {
+                    runs.push(self.run(base_name.as_deref(), base, &assignment, netem_profile)?);
+                }
+            }
+        }
+
+        Ok(runs)
+    }
+
+    fn run(
+        &self,
+        base_name: Option<&str>,
+        base: &Value,
+        assignment: &[(String, Value)],
+        netem_profile: &Option<String>,
+    ) -> std::io::Result<MatrixRun> {
+        let mut name_parts = Vec::new();
+        if let Some(base_name) = base_name {
+            name_parts.push(format!("base-{}", base_name));
+        }
+        // The full key, as two tables may share a parameter name
+        name_parts.extend(
+            assignment
+                .iter()
+                .map(|(key, value)| format!("{}-{}", key, sanitize(&value_to_string(value)))),
+        );
+        name_parts.push(format!(
+            "netem-{}",
+            netem_profile.as_deref().unwrap_or("none")
+        ));
+
+        let name = name_parts.join("_");
+        let directory = self.results_directory.join(&name);
+
+        let mut config = base.clone();
+        for (key, value) in assignment {
+            set_dotted(&mut config, key, value.clone())?;
+        }
+
+        set_dotted(
+            &mut config,
+            "output.directory",
+            Value::String(directory.to_string_lossy().to_string()),
+        )?;
+
+        if let Some(duration) = self.duration {
+            set_dotted(&mut config, "run.duration", Value::Integer(duration as i64))?;
+        }
+
+        if let Some(max_frames) = self.max_frames {
+            set_dotted(&mut config, "run.max_frames", Value::Integer(max_frames as i64))?;
+        }
+
+        Ok(MatrixRun {
+            name,
+            directory,
+            netem_profile: netem_profile.clone(),
+            config,
+        })
+    }
+
+    pub fn netem_setup_script(&self, profile: &str) -> PathBuf {
+        self.netem_directory.join(profile).join("netem_setup.sh")
+    }
+
+    pub fn netem_reset_script(&self) -> PathBuf {
+        self.netem_directory.join("reset.sh")
+    }
+
+    fn assignments(&self) -> Vec<Vec<(String, Value)>> {
+        let mut assignments: Vec<Vec<(String, Value)>> = vec![Vec::new()];
+
+        for (key, values) in &self.parameters {
+            assignments = assignments
+                .into_iter()
+                .flat_map(|assignment| {
+                    values.iter().map(move |value| {
+                        let mut assignment = assignment.clone();
+                        assignment.push((key.clone(), value.clone()));
+                        assignment
+                    })
+                })
+                .collect();
+        }
+
+        assignments
+    }
+}
+
+fn load_base(path: &Path) -> std::io::Result<Value> {
+    let content = fs::read_to_string(path)?;
+    toml::from_str(&content).map_err(|err| Error::new(ErrorKind::InvalidData, err))
+}
+
+fn set_dotted(config: &mut Value, key: &str, value: Value) -> std::io::Result<()> {
+    let mut segments: Vec<&str> = key.split('.').collect();
+    let last = segments.pop().unwrap();
+
+    let mut table = config
+        .as_table_mut()
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "the base config is not a table"))?;
+
+    for segment in segments {
+        table = table
+            .entry(segment.to_string())
+            .or_insert_with(|| Value::Table(Default::default()))
+            .as_table_mut()
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("'{}' in '{}' is not a table", segment, key),
+                )
+            })?;
+    }
+
+    table.insert(last.to_string(), value);
+    Ok(())
+}
+
+fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::String(string) => string.clone(),
+        value => value.to_string(),
+    }
+}
+
+fn sanitize(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '.' { c } else { '-' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::ExperimentConfig, testing::TempDir};
+
+    const BASE: &str = r#"
+[run]
+duration = 60
+
+[codec]
+codec = "x264"
+
+[encoder]
+rate_control = "crf"
+crf = 23
+"#;
+
+    fn matrix(directory: &TempDir, extra: &str) -> MatrixConfig {
+        let base = directory.write("base.toml", BASE);
+        let content = format!("base = {:?}\n{}", base, extra);
+        MatrixConfig::load(&directory.write("matrix.toml", &content)).unwrap()
+    }
+
+    fn get<'a>(config: &'a Value, key: &str) -> Option<&'a Value> {
+        key.split('.')
+            .try_fold(config, |value, segment| value.get(segment))
+    }
+
+    // Every run must be an experiment the binaries accept
+    fn assert_valid(runs: &[MatrixRun]) {
+        for run in runs {
+            let content = toml::to_string(&run.config).unwrap();
+            if let Err(err) = ExperimentConfig::parse(&content) {
+                panic!("{} is not a valid experiment: {}", run.name, err);
+            }
+        }
+    }
+
+    #[test]
+    fn expands_every_combination() {
+        let directory = TempDir::new("matrix-combinations");
+        let matrix = matrix(
+            &directory,
+            r#"
+max_frames = 500
+netem_profiles = ["none", "loss_1"]
+
+[parameters]
+"encoder.crf" = [18, 28]
+"transport.kind" = ["udp", "srt"]
+"#,
+        );
+        let runs = matrix.runs().unwrap();
+        assert_eq!(runs.len(), 8);
+        assert_valid(&runs);
+
+        let names: Vec<&str> = runs.iter().map(|run| run.name.as_str()).collect();
+        assert_eq!(names[0], "encoder.crf-18_transport.kind-udp_netem-none");
+        assert_eq!(names[1], "encoder.crf-18_transport.kind-udp_netem-loss_1");
+        assert_eq!(names[7], "encoder.crf-28_transport.kind-srt_netem-loss_1");
+        assert_eq!(runs[1].netem_profile.as_deref(), Some("loss_1"));
+        assert_eq!(runs[0].netem_profile, None);
+
+        let run = &runs[6];
+        assert_eq!(run.directory, PathBuf::from("results").join(&run.name));
+        assert_eq!(get(&run.config, "encoder.crf"), Some(&Value::Integer(28)));
+        assert_eq!(
+            get(&run.config, "codec.codec"),
+            Some(&Value::String("x264".to_string()))
+        );
+        // New tables are created as needed
+        assert_eq!(
+            get(&run.config, "transport.kind"),
+            Some(&Value::String("srt".to_string()))
+        );
+        assert_eq!(
+            get(&run.config, "output.directory"),
+            Some(&Value::String(run.directory.to_string_lossy().to_string()))
+        );
+        // Only the limits of the matrix are overridden
+        assert_eq!(get(&run.config, "run.duration"), Some(&Value::Integer(60)));
+        assert_eq!(get(&run.config, "run.max_frames"), Some(&Value::Integer(500)));
+    }
+
+    #[test]
+    fn names_keep_the_table_of_each_parameter() {
+        let directory = TempDir::new("matrix-collisions");
+        let runs = matrix(
+            &directory,
+            r#"
+duration = 30
+
+[parameters]
+"server.pools_size" = [4]
+"client.pools_size" = [4, 16]
+"#,
+        )
+        .runs()
+        .unwrap();
+        assert_valid(&runs);
+
+        let names: Vec<&str> = runs.iter().map(|run| run.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "client.pools_size-4_server.pools_size-4_netem-none",
+                "client.pools_size-16_server.pools_size-4_netem-none",
+            ]
+        );
+    }
+
+    #[test]
+    fn runs_every_base() {
+        let directory = TempDir::new("matrix-bases");
+        let fast = directory.write("fast.toml", BASE);
+        let slow = directory.write(
+            "slow.toml",
+            &format!("{}\n[thresholds]\npre_render_delay = 300\n", BASE),
+        );
+        let content = format!(
+            "bases = [{:?}, {:?}]\nduration = 30\n[parameters]\n\"encoder.gop\" = [16]",
+            fast, slow
+        );
+        let runs = MatrixConfig::load(&directory.write("matrix.toml", &content))
+            .unwrap()
+            .runs()
+            .unwrap();
+        assert_valid(&runs);
+
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0].name, "base-fast_encoder.gop-16_netem-none");
+        assert_eq!(runs[1].name, "base-slow_encoder.gop-16_netem-none");
+        assert_eq!(get(&runs[0].config, "thresholds.pre_render_delay"), None);
+        assert_eq!(
+            get(&runs[1].config, "thresholds.pre_render_delay"),
+            Some(&Value::Integer(300))
+        );
+    }
+
+    #[test]
+    fn expands_every_matrix_into_valid_experiments() {
+        let mut expanded = 0;
+        for entry in fs::read_dir("experiments").unwrap() {
+            let path = entry.unwrap().path();
+            if !path.to_string_lossy().ends_with("_matrix.toml") {
+                continue;
+            }
+
+            let runs = MatrixConfig::load(&path).unwrap().runs().unwrap();
+            let mut names: Vec<&str> = runs.iter().map(|run| run.name.as_str()).collect();
+            names.sort_unstable();
+            names.dedup();
+            assert_eq!(names.len(), runs.len(), "{:?} reuses run names", path);
+
+            assert_valid(&runs);
+            expanded += 1;
+        }
+
+        assert!(expanded > 0);
+    }
+
+    #[test]
+    fn single_run_without_parameters() {
+        let directory = TempDir::new("matrix-single");
+        let runs = matrix(&directory, "duration = 30").runs().unwrap();
+        assert_valid(&runs);
+
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].name, "netem-none");
+        assert_eq!(get(&runs[0].config, "run.duration"), Some(&Value::Integer(30)));
+    }
+
+    #[test]
+    fn rejects_invalid_matrices() {
+        let directory = TempDir::new("matrix-invalid");
+        let base = directory.write("base.toml", BASE);
+
+        let without_limits = format!("base = {:?}", base);
+        assert!(MatrixConfig::load(&directory.write("limits.toml", &without_limits)).is_err());
+
+        let empty_parameter = format!("base = {:?}\nduration = 30\n[parameters]\ncrf = []", base);
+        assert!(MatrixConfig::load(&directory.write("empty.toml", &empty_parameter)).is_err());
+
+        let unknown_field = format!("base = {:?}\nduration = 30\nruns = 3", base);
+        assert!(MatrixConfig::load(&directory.write("unknown.toml", &unknown_field)).is_err());
+
+        let without_base = "duration = 30";
+        assert!(MatrixConfig::load(&directory.write("no_base.toml", without_base)).is_err());
+
+        let both_bases = format!("base = {:?}\nbases = [{:?}]\nduration = 30", base, base);
+        assert!(MatrixConfig::load(&directory.write("both.toml", &both_bases)).is_err());
+    }
+
+    #[test]
+    fn does_not_replace_values_by_tables() {
+        let mut config: Value = toml::from_str(BASE).unwrap();
+
+        assert!(set_dotted(&mut config, "encoder.crf.value", Value::Integer(1)).is_err());
+        assert_eq!(get(&config, "encoder.crf"), Some(&Value::Integer(23)));
+    }
+
+    #[test]
+    fn sanitizes_run_names() {
+        assert_eq!(sanitize("1.5 ms/loss"), "1.5-ms-loss");
+        assert_eq!(value_to_string(&Value::String("udp".to_string())), "udp");
+        assert_eq!(value_to_string(&Value::Boolean(true)), "true");
+    }
+}
@@ -0,0 +1,226 @@
+use std::time::Duration;
+
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    sync::watch,
+    task::JoinHandle,
+};
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct StreamDescription {
+    pub width: usize,
+    pub height: usize,
+    pub pixel_format: String,
+    pub codec: String,
+}
+
+// Server side: every connected client receives the current description of the stream, and again
+// whenever it is updated, e.g. after a resolution change. The connection is kept open so that the
+// client also notices a restart, possibly with a different description.
+pub struct StreamAnnouncer {
+    descriptions: watch::Sender<StreamDescription>,
+    port: u16,
+    task: JoinHandle<()>,
+}
+
+impl StreamAnnouncer {
+    pub async fn bind(port: u16, description: StreamDescription) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(("0.0.0.0", port)).await?;
+        let port = listener.local_addr()?.port();
+        let (descriptions, announced) = watch::channel(description);
+
+        let task = tokio::spawn(async move {
+            loop {
+                let (stream, address) = match listener.accept().await {
+                    Ok(connection) => connection,
+                    Err(err) => {
+                        warn!("Unable to accept a negotiation connection: {}", err);
+                        continue;
+                    }
+                };
+
+                info!("Announcing stream description to {}", address);
+                tokio::spawn(announce(stream, announced.clone()));
+            }
+        });
+
+        Ok(Self {
+            descriptions,
+            port,
+            task,
+        })
+    }
+
+    // Announces `description` to the connected clients, which rebuild their pipelines for it
+    pub fn update(&self, description: StreamDescription) {
+        if *self.descriptions.borrow() == description {
+            return;
+        }
+
+        info!("Stream description changed to {:?}", description);
+        // The accept task keeps a receiver as long as the announcer lives
+        self.descriptions.send(description).ok();
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+}
+
+impl Drop for StreamAnnouncer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn announce(mut stream: TcpStream, mut descriptions: watch::Receiver<StreamDescription>) {
+    let mut buffer = [0; 64];
+
+    loop {
+        let mut line = serde_json::to_string(&*descriptions.borrow_and_update()).unwrap();
+        line.push('\n');
+
+        if let Err(err) = stream.write_all(line.as_bytes()).await {
+            debug!("Negotiation connection closed: {}", err);
+            return;
+        }
+
+        // The client never writes, reading only tells when it goes away
+        loop {
+            tokio::select! {
+                changed = descriptions.changed() => {
+                    if changed.is_err() {
+                        return;
+                    }
+                    break;
+                }
+                read = stream.read(&mut buffer) => {
+                    if matches!(read, Ok(0) | Err(_)) {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Client side: keeps a connection to the announcer, reconnecting when the server restarts
+pub struct StreamDescriptionReceiver {
+    descriptions: watch::Receiver<StreamDescription>,
+}
+
+impl StreamDescriptionReceiver {
+    pub async fn connect(address: &str) -> Self {
+        let mut reader = connect_with_retry(address).await;
+        let description = loop {
+            match read_description(&mut reader).await {
+                Some(description) => break description,
+                None => reader = connect_with_retry(address).await,
+            }
+        };
+
+        info!("Negotiated stream description {:?}", description);
+
+        let (sender, descriptions) = watch::channel(description);
+        let address = address.to_string();
+        tokio::spawn(async move {
+            loop {
+                // Reconnecting to an unchanged server must not rebuild the client pipelines
+                while let Some(description) = read_description(&mut reader).await {
+                    if *sender.borrow() == description {
+                        continue;
+                    }
+
+                    info!("Received stream description {:?}", description);
+                    if sender.send(description).is_err() {
+                        return;
+                    }
+                }
+
+                warn!("Negotiation connection lost, reconnecting");
+                reader = connect_with_retry(&address).await;
+            }
+        });
+
+        Self { descriptions }
+    }
+
+    pub fn current(&mut self) -> StreamDescription {
+        self.descriptions.borrow_and_update().clone()
+    }
+
+    pub async fn changed(&mut self) {
+        if self.descriptions.changed().await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+async fn connect_with_retry(address: &str) -> BufReader<TcpStream> {
+    loop {
+        match TcpStream::connect(address).await {
+            Ok(stream) => return BufReader::new(stream),
+            Err(err) => {
+                debug!("Negotiation server at {} not available: {}", address, err);
+                tokio::time::sleep(Duration::from_millis(500)).await;
+            }
+        }
+    }
+}
+
+async fn read_description(reader: &mut BufReader<TcpStream>) -> Option<StreamDescription> {
+    let mut line = String::new();
+    match reader.read_line(&mut line).await {
+        Ok(0) | Err(_) => None,
+        Ok(_) => match serde_json::from_str(&line) {
+            Ok(description) => Some(description),
+            Err(err) => {
+                warn!("Malformed stream description {:?}: {}", line, err);
+                None
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn description(width: usize, height: usize) -> StreamDescription {
+        StreamDescription {
+            width,
+            height,
+            pixel_format: "rgba".to_string(),
+            codec: "h264".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn announces_updates_to_connected_clients() {
+        let announcer = StreamAnnouncer::bind(0, description(1280, 720)).await.unwrap();
+        let address = format!("127.0.0.1:{}", announcer.port());
+
+        let mut receiver = StreamDescriptionReceiver::connect(&address).await;
+        assert_eq!(receiver.current(), description(1280, 720));
+
+        // Mid-session, on the same connection
+        announcer.update(description(1920, 1080));
+        tokio::time::timeout(Duration::from_secs(5), receiver.changed())
+            .await
+            .unwrap();
+        assert_eq!(receiver.current(), description(1920, 1080));
+
+        // Clients connecting later start from the update
+        let mut late_receiver = StreamDescriptionReceiver::connect(&address).await;
+        assert_eq!(late_receiver.current(), description(1920, 1080));
+
+        // An unchanged description does not reconfigure the clients
+        announcer.update(description(1920, 1080));
+        let unchanged =
+            tokio::time::timeout(Duration::from_millis(200), receiver.changed()).await;
+        assert!(unchanged.is_err());
+    }
+}
@@ -0,0 +1,104 @@
+use std::time::Duration;
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct LinkConditions {
+    // Bits per second, unlimited when None
+    pub rate: Option<u64>,
+    pub delay: Duration,
+    pub jitter: Duration,
+    pub loss: LossModel,
+}
+
+// All probabilities are fractions between 0 and 1
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LossModel {
+    Uniform {
+        probability: f64,
+    },
+    // Two-state Markov chain, evaluated once per packet before deciding its loss
+    GilbertElliott {
+        good_to_bad: f64,
+        bad_to_good: f64,
+        good_loss: f64,
+        bad_loss: f64,
+    },
+}
+
+impl Default for LossModel {
+    fn default() -> Self {
+        Self::Uniform { probability: 0.0 }
+    }
+}
+
+impl LossModel {
+    pub fn uniform(probability: f64) -> Self {
+        Self::Uniform { probability }
+    }
+
+    // Long-run fraction of lost packets
+    pub fn average_loss(&self) -> f64 {
+        match *self {
+            Self::Uniform { probability } => probability,
+            Self::GilbertElliott {
+                good_to_bad,
+                bad_to_good,
+                good_loss,
+                bad_loss,
+            } => {
+                if good_to_bad + bad_to_good == 0.0 {
+                    return good_loss;
+                }
+
+                let bad_share = good_to_bad / (good_to_bad + bad_to_good);
+                (1.0 - bad_share) * good_loss + bad_share * bad_loss
+            }
+        }
+    }
+}
+
+impl LinkConditions {
+    // Same conditions as the netem/<profile>/netem_setup.sh scripts
+    pub fn profile(name: &str) -> Option<Self> {
+        let rate = Some(60_000_000);
+        let delay = Duration::from_millis(40);
+        let jitter = Duration::from_millis(10);
+        let loss = LossModel::uniform(0.015);
+
+        let conditions = match name {
+            "none" => Self::default(),
+            "60mbit" => Self {
+                rate,
+                ..Default::default()
+            },
+            "60mbit_delay_packetloss" => Self {
+                rate,
+                delay,
+                jitter,
+                loss,
+            },
+            "60mbit_packetloss" => Self {
+                rate,
+                loss,
+                ..Default::default()
+            },
+            "delay" => Self {
+                delay,
+                jitter,
+                ..Default::default()
+            },
+            "delay_packetloss" => Self {
+                delay,
+                jitter,
+                loss,
+                ..Default::default()
+            },
+            "packetloss" => Self {
+                loss,
+                ..Default::default()
+            },
+            _ => return None,
+        };
+
+        Some(conditions)
+    }
+}
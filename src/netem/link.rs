@@ -0,0 +1,253 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use tokio::time::Instant;
+
+use super::{
+    conditions::{LinkConditions, LossModel},
+    loss::{LossCause, LossEventLog},
+};
+
+// One direction of an emulated link: rate limiting with a bounded queue, delay with uniform
+// jitter and random or bursty loss, in the same order netem applies them
+pub struct EmulatedLink {
+    conditions: Arc<Mutex<LinkConditions>>,
+    queue_limit: usize,
+    rng: StdRng,
+
+    direction: &'static str,
+    loss_log: Option<Arc<LossEventLog>>,
+    bad_state: bool,
+
+    link_free_at: Instant,
+    queued_departures: VecDeque<Instant>,
+}
+
+impl EmulatedLink {
+    pub fn new(conditions: Arc<Mutex<LinkConditions>>, seed: u64) -> Self {
+        Self {
+            conditions,
+            queue_limit: 1000,
+            rng: StdRng::seed_from_u64(seed),
+            direction: "",
+            loss_log: None,
+            bad_state: false,
+            link_free_at: Instant::now(),
+            queued_departures: VecDeque::new(),
+        }
+    }
+
+    pub fn queue_limit(mut self, queue_limit: usize) -> Self {
+        self.queue_limit = queue_limit;
+        self
+    }
+
+    pub fn loss_log(mut self, direction: &'static str, loss_log: Arc<LossEventLog>) -> Self {
+        self.direction = direction;
+        self.loss_log = Some(loss_log);
+        self
+    }
+
+    // Returns the delivery time of the packet, or None when it is lost
+    pub fn schedule(&mut self, size: usize, now: Instant) -> Option<Instant> {
+        let conditions = *self.conditions.lock().unwrap();
+
+        if let Some(cause) = self.draw_loss(&conditions.loss) {
+            self.record_loss(cause, size);
+            return None;
+        }
+
+        let departure = match conditions.rate {
+            Some(rate) => {
+                while matches!(self.queued_departures.front(), Some(departure) if *departure <= now)
+                {
+                    self.queued_departures.pop_front();
+                }
+
+                if self.queued_departures.len() >= self.queue_limit {
+                    self.record_loss(LossCause::QueueOverflow, size);
+                    return None;
+                }
+
+                let transmission_time = Duration::from_secs_f64(size as f64 * 8.0 / rate as f64);
+                let departure = self.link_free_at.max(now) + transmission_time;
+                self.link_free_at = departure;
+                self.queued_departures.push_back(departure);
+                departure
+            }
+            None => now,
+        };
+
+        let delay = conditions.delay.as_micros() as i64;
+        let jitter = conditions.jitter.as_micros() as i64;
+        let delay = if jitter > 0 {
+            (delay + self.rng.gen_range(-jitter..=jitter)).max(0)
+        } else {
+            delay
+        };
+
+        Some(departure + Duration::from_micros(delay as u64))
+    }
+
+    fn draw_loss(&mut self, model: &LossModel) -> Option<LossCause> {
+        match *model {
+            LossModel::Uniform { probability } => {
+                if probability > 0.0 && self.rng.gen::<f64>() < probability {
+                    Some(LossCause::Random)
+                } else {
+                    None
+                }
+            }
+            LossModel::GilbertElliott {
+                good_to_bad,
+                bad_to_good,
+                good_loss,
+                bad_loss,
+            } => {
+                let transition = if self.bad_state { bad_to_good } else { good_to_bad };
+                if self.rng.gen::<f64>() < transition {
+                    self.bad_state = !self.bad_state;
+                }
+
+                let (loss, cause) = if self.bad_state {
+                    (bad_loss, LossCause::Burst)
+                } else {
+                    (good_loss, LossCause::Random)
+                };
+
+                if self.rng.gen::<f64>() < loss {
+                    Some(cause)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    fn record_loss(&self, cause: LossCause, size: usize) {
+        if let Some(loss_log) = &self.loss_log {
+            loss_log.record(self.direction, cause, size);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DRAWS: usize = 1_000_000;
+
+    fn link(seed: u64) -> EmulatedLink {
+        EmulatedLink::new(Arc::new(Mutex::new(LinkConditions::default())), seed)
+    }
+
+    fn gilbert_elliott(bad_loss: f64) -> LossModel {
+        LossModel::GilbertElliott {
+            good_to_bad: 0.01,
+            bad_to_good: 0.25,
+            good_loss: 0.001,
+            bad_loss,
+        }
+    }
+
+    fn assert_close(measured: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (measured - expected).abs() <= expected * tolerance,
+            "measured {}, expected {}",
+            measured,
+            expected
+        );
+    }
+
+    #[test]
+    fn gilbert_elliott_loses_the_stationary_share() {
+        let model = gilbert_elliott(0.5);
+        // pi_bad = p / (p + r), loss = pi_good * k + pi_bad * h
+        let bad_share = 0.01 / (0.01 + 0.25);
+        let expected = (1.0 - bad_share) * 0.001 + bad_share * 0.5;
+        assert_close(model.average_loss(), expected, 1e-12);
+
+        let mut link = link(42);
+        let mut losses = 0;
+        let mut bursts = 0;
+        for _ in 0..DRAWS {
+            match link.draw_loss(&model) {
+                Some(LossCause::Random) => losses += 1,
+                Some(LossCause::Burst) => {
+                    losses += 1;
+                    bursts += 1;
+                }
+                Some(LossCause::QueueOverflow) => unreachable!(),
+                None => {}
+            }
+        }
+
+        assert_close(losses as f64 / DRAWS as f64, expected, 0.05);
+        assert_close(bursts as f64 / DRAWS as f64, bad_share * 0.5, 0.05);
+    }
+
+    #[test]
+    fn gilbert_elliott_bursts_last_the_bad_state_sojourn() {
+        // Every packet of the bad state and none of the good one is lost, so a run of losses is a
+        // stay in the bad state, geometric with mean 1 / r
+        let model = LossModel::GilbertElliott {
+            good_to_bad: 0.01,
+            bad_to_good: 0.25,
+            good_loss: 0.0,
+            bad_loss: 1.0,
+        };
+
+        let mut link = link(7);
+        let mut bursts = Vec::new();
+        let mut current = 0;
+        for _ in 0..DRAWS {
+            if link.draw_loss(&model).is_some() {
+                current += 1;
+            } else if current > 0 {
+                bursts.push(current);
+                current = 0;
+            }
+        }
+
+        let mean_burst = bursts.iter().sum::<usize>() as f64 / bursts.len() as f64;
+        assert_close(mean_burst, 1.0 / 0.25, 0.05);
+
+        let lost = bursts.iter().sum::<usize>() + current;
+        assert_close(lost as f64 / DRAWS as f64, model.average_loss(), 0.05);
+    }
+
+    #[test]
+    fn draws_are_reproducible_from_the_seed() {
+        let model = gilbert_elliott(0.5);
+        let draws = |seed| {
+            let mut link = link(seed);
+            (0..10_000)
+                .map(|_| link.draw_loss(&model))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(draws(42), draws(42));
+        assert_ne!(draws(42), draws(43));
+    }
+
+    #[test]
+    fn degenerate_models() {
+        // Never leaving the good state
+        let stuck = LossModel::GilbertElliott {
+            good_to_bad: 0.0,
+            bad_to_good: 0.0,
+            good_loss: 0.02,
+            bad_loss: 1.0,
+        };
+        assert_eq!(stuck.average_loss(), 0.02);
+
+        let mut link = link(42);
+        assert!((0..DRAWS).all(|_| link.draw_loss(&stuck) != Some(LossCause::Burst)));
+        assert!((0..DRAWS).all(|_| link.draw_loss(&LossModel::uniform(0.0)).is_none()));
+        assert!((0..DRAWS).all(|_| link.draw_loss(&LossModel::uniform(1.0)).is_some()));
+    }
+}
@@ -0,0 +1,64 @@
+use std::{
+    fs::File,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use log::warn;
+
+use crate::time::now_timestamp;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LossCause {
+    // Uniform model, or the good state of the Gilbert-Elliott model
+    Random,
+    // Bad state of the Gilbert-Elliott model
+    Burst,
+    QueueOverflow,
+}
+
+impl LossCause {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Random => "random",
+            Self::Burst => "burst",
+            Self::QueueOverflow => "queue_overflow",
+        }
+    }
+}
+
+// One row per lost packet, timestamped with the same clock as capture_timestamp so that
+// client drops can be matched to the losses that preceded them
+pub struct LossEventLog {
+    writer: Mutex<csv::Writer<File>>,
+}
+
+impl LossEventLog {
+    pub fn create(path: &Path) -> std::io::Result<Arc<Self>> {
+        let mut writer = csv::Writer::from_path(path)?;
+        writer.write_record(["timestamp", "direction", "cause", "packet_size"])?;
+        writer.flush()?;
+
+        Ok(Arc::new(Self {
+            writer: Mutex::new(writer),
+        }))
+    }
+
+    pub fn record(&self, direction: &str, cause: LossCause, packet_size: usize) {
+        let record = [
+            now_timestamp().to_string(),
+            direction.to_string(),
+            cause.as_str().to_string(),
+            packet_size.to_string(),
+        ];
+
+        // Flushed every row, the relay being killed at the end of the experiments
+        let mut writer = self.writer.lock().unwrap();
+        let written = writer
+            .write_record(&record)
+            .and_then(|_| writer.flush().map_err(csv::Error::from));
+        if let Err(err) = written {
+            warn!("Unable to log the packet loss: {}", err);
+        }
+    }
+}
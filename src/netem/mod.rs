@@ -0,0 +1,6 @@
+pub mod conditions;
+pub mod link;
+pub mod loss;
+pub mod relay;
+pub mod stamper;
+pub mod trace;
@@ -0,0 +1,232 @@
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use log::{debug, info, warn};
+use tokio::{
+    net::UdpSocket,
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    time::Instant,
+};
+
+use super::{conditions::LinkConditions, link::EmulatedLink, loss::LossEventLog};
+
+const MAX_DATAGRAM_SIZE: usize = 65536;
+
+// UDP relay between a transport sender and its receiver: the receiver connects to the relay,
+// which forwards every datagram to the target through an emulated link in each direction
+pub struct UdpRelay {
+    listen_address: SocketAddr,
+    target_address: SocketAddr,
+    conditions: Arc<Mutex<LinkConditions>>,
+
+    seed: u64,
+    queue_limit: usize,
+    loss_log: Option<Arc<LossEventLog>>,
+}
+
+struct ScheduledDatagram {
+    delivery: Instant,
+    sequence: u64,
+    payload: Vec<u8>,
+}
+
+impl PartialEq for ScheduledDatagram {
+    fn eq(&self, other: &Self) -> bool {
+        (self.delivery, self.sequence) == (other.delivery, other.sequence)
+    }
+}
+
+impl Eq for ScheduledDatagram {}
+
+impl PartialOrd for ScheduledDatagram {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ScheduledDatagram {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.delivery, self.sequence).cmp(&(other.delivery, other.sequence))
+    }
+}
+
+impl UdpRelay {
+    pub fn new(
+        listen_address: SocketAddr,
+        target_address: SocketAddr,
+        conditions: LinkConditions,
+    ) -> Self {
+        Self {
+            listen_address,
+            target_address,
+            conditions: Arc::new(Mutex::new(conditions)),
+            seed: 0,
+            queue_limit: 1000,
+            loss_log: None,
+        }
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn queue_limit(mut self, queue_limit: usize) -> Self {
+        self.queue_limit = queue_limit;
+        self
+    }
+
+    pub fn loss_log(mut self, loss_log: Arc<LossEventLog>) -> Self {
+        self.loss_log = Some(loss_log);
+        self
+    }
+
+    pub fn conditions(&self) -> Arc<Mutex<LinkConditions>> {
+        self.conditions.clone()
+    }
+
+    pub async fn start(&self) -> std::io::Result<()> {
+        let (listen_address, target_address) = (self.listen_address, self.target_address);
+        let conditions = self.conditions.clone();
+
+        let client_side = Arc::new(UdpSocket::bind(listen_address).await?);
+        let server_side = Arc::new(UdpSocket::bind("0.0.0.0:0").await?);
+        server_side.connect(target_address).await?;
+
+        info!(
+            "Emulating {:?} between {} and {}",
+            *conditions.lock().unwrap(),
+            listen_address,
+            target_address
+        );
+
+        let client_address: Arc<Mutex<Option<SocketAddr>>> = Arc::new(Mutex::new(None));
+
+        // Receiver -> target
+        let upstream_link = self.link(self.seed, "upstream");
+        let (upstream_sender, upstream_receiver) = mpsc::unbounded_channel();
+        tokio::spawn(deliver(upstream_receiver, {
+            let server_side = server_side.clone();
+            move |payload| {
+                let server_side = server_side.clone();
+                async move { server_side.send(&payload).await.map(|_| ()) }
+            }
+        }));
+
+        {
+            let client_side = client_side.clone();
+            let client_address = client_address.clone();
+            tokio::spawn(async move {
+                let mut buffer = vec![0; MAX_DATAGRAM_SIZE];
+                let mut link = upstream_link;
+                loop {
+                    let (size, address) = match client_side.recv_from(&mut buffer).await {
+                        Ok(received) => received,
+                        Err(err) => {
+                            warn!("Relay receive error: {}", err);
+                            continue;
+                        }
+                    };
+
+                    client_address.lock().unwrap().replace(address);
+                    enqueue(&mut link, &upstream_sender, &buffer[..size]);
+                }
+            });
+        }
+
+        // Target -> receiver
+        let downstream_link = self.link(self.seed.wrapping_add(1), "downstream");
+        let (downstream_sender, downstream_receiver) = mpsc::unbounded_channel();
+        tokio::spawn(deliver(downstream_receiver, {
+            let client_side = client_side.clone();
+            let client_address = client_address.clone();
+            move |payload| {
+                let client_side = client_side.clone();
+                let address = *client_address.lock().unwrap();
+                async move {
+                    match address {
+                        Some(address) => client_side.send_to(&payload, address).await.map(|_| ()),
+                        None => Ok(()),
+                    }
+                }
+            }
+        }));
+
+        tokio::spawn(async move {
+            let mut buffer = vec![0; MAX_DATAGRAM_SIZE];
+            let mut link = downstream_link;
+            loop {
+                let size = match server_side.recv(&mut buffer).await {
+                    Ok(size) => size,
+                    Err(err) => {
+                        // Target not listening yet
+                        debug!("Relay receive error: {}", err);
+                        continue;
+                    }
+                };
+
+                enqueue(&mut link, &downstream_sender, &buffer[..size]);
+            }
+        });
+
+        Ok(())
+    }
+
+    fn link(&self, seed: u64, direction: &'static str) -> EmulatedLink {
+        let link = EmulatedLink::new(self.conditions.clone(), seed).queue_limit(self.queue_limit);
+        match &self.loss_log {
+            Some(loss_log) => link.loss_log(direction, loss_log.clone()),
+            None => link,
+        }
+    }
+}
+
+fn enqueue(
+    link: &mut EmulatedLink,
+    sender: &UnboundedSender<(Instant, Vec<u8>)>,
+    payload: &[u8],
+) {
+    if let Some(delivery) = link.schedule(payload.len(), Instant::now()) {
+        sender.send((delivery, payload.to_vec())).ok();
+    }
+}
+
+async fn deliver<F, R>(mut receiver: UnboundedReceiver<(Instant, Vec<u8>)>, send: F)
+where
+    F: Fn(Vec<u8>) -> R,
+    R: std::future::Future<Output = std::io::Result<()>>,
+{
+    let mut scheduled: BinaryHeap<Reverse<ScheduledDatagram>> = BinaryHeap::new();
+    let mut sequence = 0;
+
+    loop {
+        let next_delivery = scheduled.peek().map(|Reverse(datagram)| datagram.delivery);
+
+        tokio::select! {
+            received = receiver.recv() => match received {
+                Some((delivery, payload)) => {
+                    scheduled.push(Reverse(ScheduledDatagram { delivery, sequence, payload }));
+                    sequence += 1;
+                }
+                None => return,
+            },
+            _ = sleep_until(next_delivery) => {
+                let Reverse(datagram) = scheduled.pop().unwrap();
+                if let Err(err) = send(datagram.payload).await {
+                    debug!("Relay send error: {}", err);
+                }
+            }
+        }
+    }
+}
+
+async fn sleep_until(instant: Option<Instant>) {
+    match instant {
+        Some(instant) => tokio::time::sleep_until(instant).await,
+        None => std::future::pending().await,
+    }
+}
@@ -0,0 +1,44 @@
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use remotia::{traits::FrameProcessor, types::FrameData};
+
+use super::conditions::LinkConditions;
+
+pub const LINK_STATE_STATS: [&str; 4] = [
+    "link_rate_kbps",
+    "link_delay",
+    "link_jitter",
+    "link_loss_ppm",
+];
+
+// Records the emulated link conditions active when the frame goes through the processor
+pub struct LinkStateStamper {
+    conditions: Arc<Mutex<LinkConditions>>,
+}
+
+impl LinkStateStamper {
+    pub fn new(conditions: Arc<Mutex<LinkConditions>>) -> Self {
+        Self { conditions }
+    }
+}
+
+#[async_trait]
+impl FrameProcessor for LinkStateStamper {
+    async fn process(&mut self, mut frame_data: FrameData) -> Option<FrameData> {
+        let conditions = *self.conditions.lock().unwrap();
+
+        frame_data.set(
+            "link_rate_kbps",
+            conditions.rate.map_or(0, |rate| rate / 1000) as u128,
+        );
+        frame_data.set("link_delay", conditions.delay.as_millis());
+        frame_data.set("link_jitter", conditions.jitter.as_millis());
+        frame_data.set(
+            "link_loss_ppm",
+            (conditions.loss.average_loss() * 1_000_000.0).round() as u128,
+        );
+
+        Some(frame_data)
+    }
+}
@@ -0,0 +1,321 @@
+use std::{
+    fs,
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use log::{info, warn};
+use serde::Deserialize;
+use tokio::time::Instant;
+
+use crate::time::now_timestamp;
+
+use super::conditions::{LinkConditions, LossModel};
+
+// Mahimahi traces describe one MTU-sized delivery opportunity per line
+const MAHIMAHI_PACKET_SIZE: u64 = 1500;
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum TraceFormat {
+    // time_ms,rate_kbps,delay_ms,jitter_ms,loss_percent
+    Csv,
+    Mahimahi,
+}
+
+pub struct LinkTrace {
+    // Each entry holds from its offset until the next one
+    entries: Vec<(Duration, LinkConditions)>,
+    length: Duration,
+}
+
+#[derive(Deserialize)]
+struct CsvTraceRow {
+    time_ms: u64,
+    rate_kbps: Option<u64>,
+    delay_ms: Option<u64>,
+    jitter_ms: Option<u64>,
+    loss_percent: Option<f64>,
+}
+
+impl LinkTrace {
+    // Fields missing from the trace keep the values of `base`
+    pub fn load(
+        path: &Path,
+        format: TraceFormat,
+        base: LinkConditions,
+        interval: Duration,
+    ) -> std::io::Result<Self> {
+        let trace = match format {
+            TraceFormat::Csv => Self::load_csv(path, base)?,
+            TraceFormat::Mahimahi => Self::load_mahimahi(path, base, interval)?,
+        };
+
+        if trace.entries.is_empty() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("{:?} holds no trace entries", path),
+            ));
+        }
+
+        // A looping play would otherwise restart it without ever waiting
+        if trace.length.is_zero() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("{:?} spans no time, all its entries start at 0", path),
+            ));
+        }
+
+        info!(
+            "Loaded a {:?} link trace with {} entries from {:?}",
+            trace.length,
+            trace.entries.len(),
+            path
+        );
+
+        Ok(trace)
+    }
+
+    fn load_csv(path: &Path, base: LinkConditions) -> std::io::Result<Self> {
+        let mut reader = csv::Reader::from_path(path)?;
+
+        let mut entries = Vec::new();
+        for row in reader.deserialize() {
+            let row: CsvTraceRow = row?;
+            let conditions = LinkConditions {
+                rate: row.rate_kbps.map(|rate| rate * 1000).or(base.rate),
+                delay: row.delay_ms.map_or(base.delay, Duration::from_millis),
+                jitter: row.jitter_ms.map_or(base.jitter, Duration::from_millis),
+                loss: row
+                    .loss_percent
+                    .map_or(base.loss, |loss| LossModel::uniform(loss / 100.0)),
+            };
+
+            entries.push((Duration::from_millis(row.time_ms), conditions));
+        }
+
+        entries.sort_by_key(|(offset, _)| *offset);
+
+        // The last entry lasts as long as the average step
+        let length = match entries.len() {
+            0 => Duration::ZERO,
+            1 => Duration::from_secs(1),
+            count => {
+                let last = entries[count - 1].0;
+                last + last / (count as u32 - 1)
+            }
+        };
+
+        Ok(Self { entries, length })
+    }
+
+    fn load_mahimahi(
+        path: &Path,
+        base: LinkConditions,
+        interval: Duration,
+    ) -> std::io::Result<Self> {
+        let content = fs::read_to_string(path)?;
+
+        let mut opportunities = Vec::new();
+        for line in content.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let timestamp: u64 = line.parse().map_err(|_| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("invalid Mahimahi timestamp '{}'", line),
+                )
+            })?;
+            opportunities.push(timestamp);
+        }
+
+        let last = match opportunities.iter().max() {
+            Some(last) => *last,
+            None => {
+                return Ok(Self {
+                    entries: Vec::new(),
+                    length: Duration::ZERO,
+                });
+            }
+        };
+
+        let interval_ms = (interval.as_millis() as u64).max(1);
+        let intervals_count = (last / interval_ms + 1) as usize;
+
+        let mut packets = vec![0u64; intervals_count];
+        for timestamp in opportunities {
+            packets[(timestamp / interval_ms) as usize] += 1;
+        }
+
+        let entries = packets
+            .into_iter()
+            .enumerate()
+            .map(|(index, count)| {
+                let rate = count * MAHIMAHI_PACKET_SIZE * 8 * 1000 / interval_ms;
+                let conditions = LinkConditions {
+                    // A zero rate would stall the link forever, emulate an outage with 1 kbit/s
+                    rate: Some(rate.max(1000)),
+                    ..base
+                };
+
+                (Duration::from_millis(index as u64 * interval_ms), conditions)
+            })
+            .collect();
+
+        Ok(Self {
+            entries,
+            length: Duration::from_millis(intervals_count as u64 * interval_ms),
+        })
+    }
+
+    // Replays the trace on the shared link conditions, optionally logging every transition
+    pub fn play(
+        self,
+        conditions: Arc<Mutex<LinkConditions>>,
+        looping: bool,
+        log_path: Option<PathBuf>,
+    ) -> std::io::Result<()> {
+        let mut log = match log_path {
+            Some(path) => {
+                let mut writer = csv::Writer::from_path(path)?;
+                writer.write_record([
+                    "timestamp",
+                    "link_rate_kbps",
+                    "link_delay",
+                    "link_jitter",
+                    "link_loss_ppm",
+                ])?;
+                Some(writer)
+            }
+            None => None,
+        };
+
+        tokio::spawn(async move {
+            loop {
+                let start = Instant::now();
+                for (offset, state) in &self.entries {
+                    tokio::time::sleep_until(start + *offset).await;
+                    *conditions.lock().unwrap() = *state;
+
+                    if let Some(log) = &mut log {
+                        let record = [
+                            now_timestamp(),
+                            state.rate.map_or(0, |rate| rate / 1000) as u128,
+                            state.delay.as_millis(),
+                            state.jitter.as_millis(),
+                            (state.loss.average_loss() * 1_000_000.0).round() as u128,
+                        ];
+
+                        let result = log
+                            .write_record(record.iter().map(|value| value.to_string()))
+                            .and_then(|_| log.flush().map_err(csv::Error::from));
+                        if let Err(err) = result {
+                            warn!("Unable to log the link state: {}", err);
+                        }
+                    }
+                }
+
+                tokio::time::sleep_until(start + self.length).await;
+
+                if !looping {
+                    info!("Link trace finished, keeping the last state");
+                    return;
+                }
+            }
+        });
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    fn base() -> LinkConditions {
+        LinkConditions {
+            rate: Some(60_000_000),
+            delay: Duration::from_millis(10),
+            ..Default::default()
+        }
+    }
+
+    fn load(content: &str, format: TraceFormat) -> std::io::Result<LinkTrace> {
+        let directory = TempDir::new("trace");
+        let path = directory.write("trace", content);
+        LinkTrace::load(&path, format, base(), Duration::from_millis(100))
+    }
+
+    #[test]
+    fn loads_a_csv_trace() {
+        let trace = load(
+            "time_ms,rate_kbps,delay_ms,jitter_ms,loss_percent\n\
+             1000,2000,,5,\n\
+             0,8000,40,,1.5\n",
+            TraceFormat::Csv,
+        )
+        .unwrap();
+
+        // Sorted by offset, the last entry lasting as long as the average step
+        let offsets: Vec<Duration> = trace.entries.iter().map(|(offset, _)| *offset).collect();
+        assert_eq!(offsets, [Duration::ZERO, Duration::from_secs(1)]);
+        assert_eq!(trace.length, Duration::from_secs(2));
+
+        let (_, first) = trace.entries[0];
+        assert_eq!(first.rate, Some(8_000_000));
+        assert_eq!(first.delay, Duration::from_millis(40));
+        assert_eq!(first.jitter, Duration::ZERO);
+        assert_eq!(first.loss, LossModel::uniform(0.015));
+
+        // Missing fields keep the base conditions
+        let (_, second) = trace.entries[1];
+        assert_eq!(second.rate, Some(2_000_000));
+        assert_eq!(second.delay, Duration::from_millis(10));
+        assert_eq!(second.jitter, Duration::from_millis(5));
+        assert_eq!(second.loss, LossModel::default());
+    }
+
+    #[test]
+    fn loads_the_example_trace() {
+        let trace = LinkTrace::load(
+            Path::new("traces/cellular_example.csv"),
+            TraceFormat::Csv,
+            base(),
+            Duration::from_millis(100),
+        )
+        .unwrap();
+
+        assert_eq!(trace.entries.len(), 5);
+        assert_eq!(trace.length, Duration::from_secs(25));
+    }
+
+    #[test]
+    fn loads_a_mahimahi_trace() {
+        // Two delivery opportunities in the first 100 ms, none in the second, one in the third
+        let trace = load("0\n50\n\n250\n", TraceFormat::Mahimahi).unwrap();
+
+        let rates: Vec<Option<u64>> = trace.entries.iter().map(|(_, state)| state.rate).collect();
+        assert_eq!(rates, [Some(240_000), Some(1000), Some(120_000)]);
+        assert_eq!(trace.entries[2].0, Duration::from_millis(200));
+        assert_eq!(trace.entries[2].1.delay, Duration::from_millis(10));
+        assert_eq!(trace.length, Duration::from_millis(300));
+    }
+
+    #[test]
+    fn rejects_invalid_traces() {
+        let header = "time_ms,rate_kbps,delay_ms,jitter_ms,loss_percent\n";
+        let cases = [
+            (header.to_string(), TraceFormat::Csv),
+            // Would spin a looping play
+            (format!("{}0,2000,,,\n0,4000,,,\n", header), TraceFormat::Csv),
+            (format!("{}-5,2000,,,\n", header), TraceFormat::Csv),
+            ("".to_string(), TraceFormat::Mahimahi),
+            ("0\nsoon\n".to_string(), TraceFormat::Mahimahi),
+        ];
+
+        for (content, format) in cases {
+            assert!(load(&content, format).is_err(), "accepted {:?}", content);
+        }
+    }
+}
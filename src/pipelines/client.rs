@@ -0,0 +1,499 @@
+use std::{
+    future::Future,
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use log::info;
+
+use remotia::{
+    error::DropReason,
+    pipeline::ascode::{component::Component, AscodePipeline},
+    processors::{
+        clone_switch::CloneSwitch, error_switch::OnErrorSwitch,
+        frame_drop::threshold::ThresholdBasedFrameDropper, key_check::KeyChecker, ticker::Ticker,
+    },
+    traits::FrameProcessor,
+};
+use remotia_buffer_utils::pool::BuffersPool;
+use remotia_core_loggers::{
+    csv::serializer::CSVFrameDataSerializer, errors::ConsoleDropReasonLogger,
+    stats::ConsoleAverageStatsLogger,
+};
+use remotia_profilation_utils::time::{add::TimestampAdder, diff::TimestampDiffCalculator};
+use tokio::sync::Notify;
+
+use crate::{
+    clock::ClockSynchronizer,
+    codecs::{decoder::VideoDecoder, Codec},
+    feedback::FeedbackSender,
+    negotiation::StreamDescriptionReceiver,
+    processors::{
+        clock::{ClockOffsetCorrector, CLOCK_OFFSET_STATS},
+        drops::{DropAnnotator, DropStage, DropsCSVSerializer},
+        feedback::LossReporter,
+        keyframe::KeyframeRequester,
+        limit::FrameCountLimiter,
+        sequence::SequenceTracker,
+    },
+    transports::{
+        nack::NackPolicy, rtp::RTP_RECEIVER_STATS, udp::UDP_RECEIVER_STATS, FrameReceiver,
+        TransportKind,
+    },
+};
+
+use super::{build_frame_dump_pipeline, run_all, RunLimits};
+
+pub struct ClientParameters {
+    pub width: usize,
+    pub height: usize,
+    pub codec: Codec,
+
+    pub pools_size: usize,
+    pub tick_interval: u64,
+
+    pub transport: TransportKind,
+    pub transport_address: String,
+    pub srt_latency: Duration,
+    pub nack: Option<NackPolicy>,
+    pub negotiation_address: Option<String>,
+    pub clock_sync_address: Option<String>,
+    pub clock_sync_interval: Duration,
+    pub feedback_address: Option<String>,
+    pub loss_report_interval: Duration,
+    // Period of the keyframe requests until a keyframe is decoded, None not to send them
+    pub keyframe_request_interval: Option<Duration>,
+
+    pub pre_render_delay_threshold: u128,
+
+    pub console_stats: bool,
+    pub console_drop_reasons: bool,
+
+    pub stats_csv_path: String,
+    pub drops_csv_path: String,
+    pub sequence_csv_path: String,
+    pub keyframe_csv_path: String,
+    pub frame_dump_path: Option<PathBuf>,
+    // Written by the file renderer, if used
+    pub render_file: Option<PathBuf>,
+
+    pub limits: RunLimits,
+}
+
+// Behaves like the original SRT examples: the side channels and run files are only enabled by
+// the experiment config
+impl Default for ClientParameters {
+    fn default() -> Self {
+        Self {
+            width: 1280,
+            height: 720,
+            codec: Codec::X264,
+            pools_size: 8,
+            tick_interval: 10,
+            transport: TransportKind::Srt,
+            transport_address: "127.0.0.1:5001".to_string(),
+            srt_latency: Duration::from_millis(50),
+            nack: None,
+            negotiation_address: None,
+            clock_sync_address: None,
+            clock_sync_interval: Duration::from_millis(200),
+            feedback_address: None,
+            loss_report_interval: Duration::from_millis(200),
+            keyframe_request_interval: None,
+            pre_render_delay_threshold: 200,
+            console_stats: true,
+            console_drop_reasons: true,
+            stats_csv_path: "client.csv".to_string(),
+            drops_csv_path: "client_drops.csv".to_string(),
+            sequence_csv_path: "client_sequence.csv".to_string(),
+            keyframe_csv_path: "client_keyframes.csv".to_string(),
+            frame_dump_path: None,
+            render_file: None,
+            limits: RunLimits::default(),
+        }
+    }
+}
+
+pub struct ClientPools {
+    pub encoded_frame: BuffersPool,
+    pub raw_frame: BuffersPool,
+    // Loss episodes seen by both the main and the error handling pipeline
+    pub keyframe_requester: Option<KeyframeRequester>,
+}
+
+impl ClientPools {
+    pub fn new(params: &ClientParameters, keyframe_requester: Option<KeyframeRequester>) -> Self {
+        let buffer_size = params.width * params.height * 4;
+
+        Self {
+            encoded_frame: BuffersPool::new("encoded_frame_buffer", params.pools_size, buffer_size),
+            raw_frame: BuffersPool::new("raw_frame_buffer", params.pools_size, buffer_size),
+            keyframe_requester,
+        }
+    }
+}
+
+pub struct ClientPipelines {
+    pub main: AscodePipeline,
+    pub error_handling: AscodePipeline,
+    pub frame_dump: Option<AscodePipeline>,
+    pub clock: Option<ClockSynchronizer>,
+
+    limits: RunLimits,
+    stop_signal: Arc<Notify>,
+
+    // Kept alive until the pipelines are done with the buffers
+    _pools: ClientPools,
+}
+
+impl ClientPipelines {
+    pub async fn build<R: FrameProcessor + Send + 'static>(
+        renderer: R,
+        params: &ClientParameters,
+    ) -> std::io::Result<Self> {
+        let clock = match &params.clock_sync_address {
+            Some(address) => {
+                Some(ClockSynchronizer::connect(address, params.clock_sync_interval).await?)
+            }
+            None => None,
+        };
+
+        let keyframe_requester =
+            match (&params.feedback_address, params.keyframe_request_interval) {
+                (Some(address), Some(interval)) => Some(KeyframeRequester::new(
+                    FeedbackSender::connect(address).await?,
+                    interval,
+                    Path::new(&params.keyframe_csv_path),
+                )?),
+                _ => None,
+            };
+
+        let pools = ClientPools::new(params, keyframe_requester);
+        let error_handling = build_error_handling_pipeline(&pools, params)?;
+        let frame_dump = params.frame_dump_path.clone().map(build_frame_dump_pipeline);
+        let stop_signal = Arc::new(Notify::new());
+        let main = build_main_pipeline(
+            renderer,
+            &pools,
+            &error_handling,
+            frame_dump.as_ref(),
+            clock.as_ref(),
+            stop_signal.clone(),
+            params,
+        )
+        .await?;
+
+        Ok(Self {
+            main,
+            error_handling,
+            frame_dump,
+            clock,
+            limits: params.limits,
+            stop_signal,
+            _pools: pools,
+        })
+    }
+
+    pub async fn run(self) {
+        self.run_until(std::future::pending()).await;
+    }
+
+    // Returns true when the pipelines were stopped by `interrupt`
+    pub async fn run_until<F: Future<Output = ()>>(self, interrupt: F) -> bool {
+        let mut pipelines = vec![self.main, self.error_handling];
+        pipelines.extend(self.frame_dump);
+        run_all(pipelines, self.limits, self.stop_signal, interrupt).await
+    }
+}
+
+// Sizes the pipelines from the stream description announced by the server and rebuilds them
+// whenever it changes. Without a negotiation address the configured resolution is used as is.
+pub async fn run_negotiated<R, F>(
+    mut params: ClientParameters,
+    mut build_renderer: F,
+) -> std::io::Result<()>
+where
+    R: FrameProcessor + Send + 'static,
+    F: FnMut(&ClientParameters) -> std::io::Result<R>,
+{
+    let address = match params.negotiation_address.clone() {
+        Some(address) => address,
+        None => {
+            let renderer = build_renderer(&params)?;
+            ClientPipelines::build(renderer, &params).await?.run().await;
+            return Ok(());
+        }
+    };
+
+    let stats_csv_path = params.stats_csv_path.clone();
+    let drops_csv_path = params.drops_csv_path.clone();
+    let sequence_csv_path = params.sequence_csv_path.clone();
+    let keyframe_csv_path = params.keyframe_csv_path.clone();
+    let frame_dump_path = params.frame_dump_path.clone();
+    let render_file = params.render_file.clone();
+
+    let mut descriptions = StreamDescriptionReceiver::connect(&address).await;
+    for session in 0.. {
+        let description = descriptions.current();
+        let codec = Codec::from_stream_name(&description.codec).ok_or_else(|| {
+            Error::new(
+                ErrorKind::Unsupported,
+                format!("unsupported stream codec '{}'", description.codec),
+            )
+        })?;
+
+        info!(
+            "Configuring the client for a {}x{} {} {} stream",
+            description.width, description.height, description.pixel_format, description.codec
+        );
+
+        params.width = description.width;
+        params.height = description.height;
+        params.codec = codec;
+
+        // Keep the logs and frames of previous sessions instead of truncating them, as the renderer
+        // and the frame dump are created again for every session
+        if session > 0 {
+            params.stats_csv_path = session_path(&stats_csv_path, session);
+            params.drops_csv_path = session_path(&drops_csv_path, session);
+            params.sequence_csv_path = session_path(&sequence_csv_path, session);
+            params.keyframe_csv_path = session_path(&keyframe_csv_path, session);
+            params.frame_dump_path = frame_dump_path
+                .as_ref()
+                .map(|path| PathBuf::from(session_path(&path.to_string_lossy(), session)));
+            params.render_file = render_file
+                .as_ref()
+                .map(|path| PathBuf::from(session_path(&path.to_string_lossy(), session)));
+        }
+
+        let renderer = build_renderer(&params)?;
+        let pipelines = ClientPipelines::build(renderer, &params).await?;
+        if !pipelines.run_until(descriptions.changed()).await {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+fn session_path(path: &str, session: usize) -> String {
+    let path = Path::new(path);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let file_name = match path.extension() {
+        Some(extension) => format!("{}_{}.{}", stem, session, extension.to_string_lossy()),
+        None => format!("{}_{}", stem, session),
+    };
+
+    path.with_file_name(file_name).to_string_lossy().to_string()
+}
+
+pub fn build_error_handling_pipeline(
+    pools: &ClientPools,
+    params: &ClientParameters,
+) -> std::io::Result<AscodePipeline> {
+    let mut component = Component::new()
+        .append(pools.raw_frame.redeemer().soft())
+        .append(pools.encoded_frame.redeemer().soft());
+
+    if params.console_drop_reasons {
+        component = component.append(
+            ConsoleDropReasonLogger::new()
+                .log(DropReason::StaleFrame)
+                .log(DropReason::ConnectionError)
+                .log(DropReason::CodecError)
+                .log(DropReason::NoDecodedFrames)
+                .log(DropReason::NoAvailableBuffers),
+        );
+    }
+
+    let mut component = component.append(KeyChecker::new("capture_timestamp"));
+
+    if let Some(keyframe_requester) = &pools.keyframe_requester {
+        component = component.append(keyframe_requester.error_detector());
+    }
+
+    let component = component
+        .append(KeyChecker::new("frame_id"))
+        .append(DropsCSVSerializer::new(Path::new(&params.drops_csv_path))?);
+
+    Ok(AscodePipeline::new()
+        .tag("ErrorsHandler")
+        .link(component)
+        .bind()
+        .feedable())
+}
+
+pub async fn build_main_pipeline<R: FrameProcessor + Send + 'static>(
+    renderer: R,
+    pools: &ClientPools,
+    error_handling_pipeline: &AscodePipeline,
+    frame_dump_pipeline: Option<&AscodePipeline>,
+    clock: Option<&ClockSynchronizer>,
+    stop_signal: Arc<Notify>,
+    params: &ClientParameters,
+) -> std::io::Result<AscodePipeline> {
+    let mut rendering_component = Component::new()
+        .append(TimestampDiffCalculator::new(
+            "local_capture_timestamp",
+            "pre_render_frame_delay",
+        ))
+        .append(ThresholdBasedFrameDropper::new(
+            "pre_render_frame_delay",
+            params.pre_render_delay_threshold,
+        ))
+        .append(DropAnnotator::new(DropStage::PreRenderDelay))
+        .append(OnErrorSwitch::new(error_handling_pipeline))
+        .append(TimestampAdder::new("rendering_start_timestamp"))
+        .append(renderer)
+        .append(TimestampDiffCalculator::new(
+            "rendering_start_timestamp",
+            "rendering_time",
+        ));
+
+    if let Some(frame_dump_pipeline) = frame_dump_pipeline {
+        rendering_component = rendering_component.append(CloneSwitch::new(frame_dump_pipeline));
+    }
+
+    let rendering_component = rendering_component
+        .append(pools.raw_frame.redeemer())
+        .append(DropAnnotator::new(DropStage::Rendering))
+        .append(OnErrorSwitch::new(error_handling_pipeline))
+        .append(TimestampDiffCalculator::new(
+            "reception_start_timestamp",
+            "total_time",
+        ))
+        .append(TimestampDiffCalculator::new(
+            "local_capture_timestamp",
+            "frame_delay",
+        ));
+
+    let mut logging_component = Component::new();
+
+    if let Some(max_frames) = params.limits.max_frames {
+        logging_component =
+            logging_component.append(FrameCountLimiter::new(max_frames, stop_signal));
+    }
+
+    if params.console_stats {
+        logging_component = logging_component
+            .append(
+                ConsoleAverageStatsLogger::new()
+                    .header("--- Computational times")
+                    .log("reception_time")
+                    .log("decoding_time")
+                    .log("rendering_time")
+                    .log("total_time"),
+            )
+            .append(
+                ConsoleAverageStatsLogger::new()
+                    .header("--- Delay times")
+                    .log("reception_delay")
+                    .log("frame_delay"),
+            );
+    }
+
+    let mut stats_serializer = CSVFrameDataSerializer::new(&params.stats_csv_path)
+        .log("frame_id")
+        .log("capture_timestamp")
+        .log("local_capture_timestamp")
+        .log("reception_time")
+        .log("decoding_time")
+        .log("rendering_time")
+        .log("total_time")
+        .log("reception_delay")
+        .log("frame_delay");
+
+    for stat in CLOCK_OFFSET_STATS {
+        stats_serializer = stats_serializer.log(stat);
+    }
+
+    if params.transport == TransportKind::Rtp {
+        for stat in RTP_RECEIVER_STATS {
+            stats_serializer = stats_serializer.log(stat);
+        }
+    }
+
+    if params.transport == TransportKind::Udp {
+        for stat in UDP_RECEIVER_STATS {
+            stats_serializer = stats_serializer.log(stat);
+        }
+    }
+
+    if pools.keyframe_requester.is_some() {
+        stats_serializer = stats_serializer.log("corrupted_frame");
+    }
+
+    let logging_component = logging_component.append(stats_serializer);
+
+    let mut reception_component = Component::new()
+        .append(Ticker::new(params.tick_interval))
+        .append(pools.encoded_frame.borrower())
+        .append(DropAnnotator::new(DropStage::ReceptionBuffers))
+        .append(OnErrorSwitch::new(error_handling_pipeline))
+        .append(TimestampAdder::new("reception_start_timestamp"))
+        .append(
+            FrameReceiver::connect(
+                params.transport,
+                &params.transport_address,
+                params.srt_latency,
+                params.nack,
+                clock.map(ClockSynchronizer::estimates),
+            )
+            .await?,
+        )
+        .append(TimestampDiffCalculator::new(
+            "reception_start_timestamp",
+            "reception_time",
+        ))
+        .append(DropAnnotator::new(DropStage::Reception))
+        .append(OnErrorSwitch::new(error_handling_pipeline))
+        .append(SequenceTracker::new(Path::new(&params.sequence_csv_path))?);
+
+    if let Some(address) = &params.feedback_address {
+        let sender = FeedbackSender::connect(address).await?;
+        reception_component =
+            reception_component.append(LossReporter::new(sender, params.loss_report_interval));
+    }
+
+    if let Some(keyframe_requester) = &pools.keyframe_requester {
+        reception_component = reception_component.append(keyframe_requester.gap_detector());
+    }
+
+    let reception_component = reception_component.append(ClockOffsetCorrector::new(
+        clock.map(ClockSynchronizer::estimates),
+    ));
+
+    let mut decoding_component = Component::new()
+        .append(pools.raw_frame.borrower())
+        .append(DropAnnotator::new(DropStage::DecodingBuffers))
+        .append(OnErrorSwitch::new(error_handling_pipeline))
+        .append(TimestampAdder::new("decoding_start_timestamp"))
+        .append(VideoDecoder::new(
+            params.codec,
+            params.width,
+            params.height,
+        )?)
+        .append(TimestampDiffCalculator::new(
+            "decoding_start_timestamp",
+            "decoding_time",
+        ))
+        .append(pools.encoded_frame.redeemer())
+        .append(DropAnnotator::new(DropStage::Decoding))
+        .append(OnErrorSwitch::new(error_handling_pipeline));
+
+    if let Some(keyframe_requester) = &pools.keyframe_requester {
+        decoding_component = decoding_component.append(keyframe_requester.recovery_detector());
+    }
+
+    let pipeline = AscodePipeline::new()
+        .tag("ClientMain")
+        .link(reception_component)
+        .link(decoding_component)
+        .link(rendering_component)
+        .link(logging_component)
+        .bind();
+
+    Ok(pipeline)
+}
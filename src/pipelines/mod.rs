@@ -0,0 +1,79 @@
+use std::{future::Future, path::PathBuf, sync::Arc, time::Duration};
+
+use log::info;
+use remotia::pipeline::ascode::{component::Component, AscodePipeline};
+use remotia_core_loggers::{frame_dump::RawFrameDumper, stats::ConsoleAverageStatsLogger};
+use remotia_profilation_utils::time::{add::TimestampAdder, diff::TimestampDiffCalculator};
+use tokio::sync::Notify;
+
+pub mod client;
+pub mod server;
+
+#[derive(Clone, Copy, Default)]
+pub struct RunLimits {
+    pub duration: Option<Duration>,
+    pub max_frames: Option<u64>,
+}
+
+// Returns true when the run was cut short by `interrupt`
+async fn run_all<F: Future<Output = ()>>(
+    pipelines: Vec<AscodePipeline>,
+    limits: RunLimits,
+    stop_signal: Arc<Notify>,
+    interrupt: F,
+) -> bool {
+    let mut handles = Vec::new();
+    for pipeline in pipelines {
+        handles.extend(pipeline.run());
+    }
+
+    let completion = async {
+        for handle in handles.iter_mut() {
+            handle.await.unwrap()
+        }
+    };
+
+    let timeout = async {
+        match limits.duration {
+            Some(duration) => tokio::time::sleep(duration).await,
+            None => std::future::pending().await,
+        }
+    };
+
+    let interrupted = tokio::select! {
+        _ = completion => false,
+        _ = timeout => {
+            info!("Run duration elapsed, stopping");
+            false
+        },
+        _ = stop_signal.notified() => false,
+        _ = interrupt => true,
+    };
+
+    for handle in &handles {
+        handle.abort();
+    }
+
+    interrupted
+}
+
+fn build_frame_dump_pipeline(path: PathBuf) -> AscodePipeline {
+    AscodePipeline::new()
+        .tag("FrameDump")
+        .link(
+            Component::new()
+                .append(TimestampAdder::new("dump_start_timestamp"))
+                .append(RawFrameDumper::new("raw_frame_buffer", path))
+                .append(TimestampDiffCalculator::new(
+                    "dump_start_timestamp",
+                    "dump_time",
+                ))
+                .append(
+                    ConsoleAverageStatsLogger::new()
+                        .header("--- Frame dump times")
+                        .log("dump_time"),
+                ),
+        )
+        .bind()
+        .feedable()
+}
@@ -0,0 +1,621 @@
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use remotia::{
+    error::DropReason,
+    pipeline::ascode::{component::Component, AscodePipeline},
+    processors::{
+        clone_switch::CloneSwitch, error_switch::OnErrorSwitch,
+        frame_drop::threshold::ThresholdBasedFrameDropper, key_check::KeyChecker, ticker::Ticker,
+    },
+    traits::FrameProcessor,
+};
+use remotia_buffer_utils::pool::BuffersPool;
+use remotia_core_codecs::yuv420p::encoder::RGBAToYUV420PConverter;
+use remotia_core_loggers::{
+    csv::serializer::CSVFrameDataSerializer, errors::ConsoleDropReasonLogger,
+    stats::ConsoleAverageStatsLogger,
+};
+use remotia_profilation_utils::time::{add::TimestampAdder, diff::TimestampDiffCalculator};
+use serde::Serialize;
+use tokio::sync::{watch, Notify};
+
+use crate::{
+    clock::ClockSyncResponder,
+    codecs::{
+        encoder::{FFmpegEncoder, VideoEncoder},
+        settings::{EncoderSettings, EncoderSetup},
+        Codec,
+    },
+    feedback::FeedbackReceiver,
+    negotiation::{StreamAnnouncer, StreamDescription},
+    netem::{
+        conditions::LinkConditions,
+        stamper::{LinkStateStamper, LINK_STATE_STATS},
+    },
+    processors::{
+        drops::{DropAnnotator, DropStage, DropsCSVSerializer},
+        keyframe::KeyframeRequestListener,
+        limit::FrameCountLimiter,
+        rate::{RateController, RateControllerSettings, SendQueue},
+        sequence::FrameIdStamper,
+    },
+    transports::{
+        fec::FecSettings,
+        nack::NackPolicy,
+        rtp::{self, RTP_SENDER_STATS},
+        udp::UDP_SENDER_STATS,
+        FrameSender, TransportKind,
+    },
+};
+
+use super::{build_frame_dump_pipeline, run_all, RunLimits};
+
+pub struct ServerParameters {
+    pub width: usize,
+    pub height: usize,
+
+    pub pools_size: usize,
+    pub tick_interval: u64,
+
+    pub codec: Codec,
+    pub encoder: EncoderSettings,
+
+    pub transport: TransportKind,
+    pub transport_port: u16,
+    pub srt_latency: Duration,
+    pub rtp_destination: Option<String>,
+    pub fec: FecSettings,
+    pub nack: Option<NackPolicy>,
+    pub negotiation_port: Option<u16>,
+    pub clock_sync_port: Option<u16>,
+    pub feedback_port: Option<u16>,
+    // Forces a keyframe when the client asks for one through the feedback
+    pub keyframe_requests: bool,
+    pub link_state: Option<Arc<Mutex<LinkConditions>>>,
+    // Stops the run when notified from outside the pipelines, e.g. by a replay running out of frames
+    pub stop_signal: Option<Arc<Notify>>,
+
+    pub capture_delay_threshold: u128,
+    pub pre_transmission_delay_threshold: u128,
+
+    pub rate_controller: RateControllerSettings,
+
+    pub console_stats: bool,
+    pub console_drop_reasons: bool,
+
+    pub stats_csv_path: String,
+    pub extra_logged_stats: Vec<String>,
+    pub drops_csv_path: String,
+    pub rate_log_path: String,
+    pub metadata_path: Option<String>,
+    // Written when RTP is pushed to rtp_destination
+    pub session_description_path: Option<String>,
+    pub frame_dump_path: Option<PathBuf>,
+
+    pub limits: RunLimits,
+}
+
+impl ServerParameters {
+    pub fn frame_rate(&self) -> i32 {
+        (1000 / self.tick_interval) as i32
+    }
+
+    // Options of the X264Encoder of the original examples when it encodes the run. It cannot be
+    // retuned nor forced to a keyframe, so the rate controller and keyframe requests need the
+    // libx264 of FFmpegEncoder instead
+    pub fn x264_options(&self) -> Option<String> {
+        let keyframe_requests = self.keyframe_requests && self.feedback_port.is_some();
+        if self.codec != Codec::X264 || self.rate_controller.enabled || keyframe_requests {
+            return None;
+        }
+
+        self.encoder.x264_options()
+    }
+}
+
+// Behaves like the original SRT examples: the side channels and run files are only enabled by
+// the experiment config
+impl Default for ServerParameters {
+    fn default() -> Self {
+        Self {
+            width: 1280,
+            height: 720,
+            pools_size: 8,
+            tick_interval: 10,
+            codec: Codec::X264,
+            encoder: EncoderSettings::default(),
+            transport: TransportKind::Srt,
+            transport_port: 5001,
+            srt_latency: Duration::from_millis(50),
+            rtp_destination: None,
+            fec: FecSettings::default(),
+            nack: None,
+            negotiation_port: None,
+            clock_sync_port: None,
+            feedback_port: None,
+            keyframe_requests: false,
+            link_state: None,
+            stop_signal: None,
+            capture_delay_threshold: 15,
+            pre_transmission_delay_threshold: 200,
+            rate_controller: RateControllerSettings::default(),
+            console_stats: true,
+            console_drop_reasons: true,
+            stats_csv_path: "server.csv".to_string(),
+            extra_logged_stats: Vec::new(),
+            drops_csv_path: "server_drops.csv".to_string(),
+            rate_log_path: "rate_control.csv".to_string(),
+            metadata_path: None,
+            session_description_path: None,
+            frame_dump_path: None,
+            limits: RunLimits::default(),
+        }
+    }
+}
+
+pub struct ServerPools {
+    pub raw_frame: BuffersPool,
+    pub y_channel: BuffersPool,
+    pub cr_channel: BuffersPool,
+    pub cb_channel: BuffersPool,
+    pub encoded_frame: BuffersPool,
+    // Encoded frames waiting for the sender
+    pub send_queue: SendQueue,
+}
+
+impl ServerPools {
+    pub fn new(params: &ServerParameters) -> Self {
+        let pixels_count = params.width * params.height;
+        let buffer_size = pixels_count * 4;
+
+        Self {
+            raw_frame: BuffersPool::new("raw_frame_buffer", params.pools_size, buffer_size),
+            y_channel: BuffersPool::new("y_channel_buffer", params.pools_size, pixels_count),
+            cr_channel: BuffersPool::new("cr_channel_buffer", params.pools_size, pixels_count / 4),
+            cb_channel: BuffersPool::new("cb_channel_buffer", params.pools_size, pixels_count / 4),
+            encoded_frame: BuffersPool::new("encoded_frame_buffer", params.pools_size, buffer_size),
+            send_queue: SendQueue::new(),
+        }
+    }
+}
+
+pub struct ServerPipelines {
+    pub main: AscodePipeline,
+    pub error_handling: AscodePipeline,
+    pub frame_dump: Option<AscodePipeline>,
+    pub announcer: Option<StreamAnnouncer>,
+    pub clock_responder: Option<ClockSyncResponder>,
+    pub feedback: Option<FeedbackReceiver>,
+
+    limits: RunLimits,
+    stop_signal: Arc<Notify>,
+
+    // Kept alive until the pipelines are done with the buffers
+    _pools: ServerPools,
+}
+
+impl ServerPipelines {
+    pub async fn build<C: FrameProcessor + Send + 'static>(
+        capturer: C,
+        params: &ServerParameters,
+    ) -> std::io::Result<Self> {
+        if let Some(path) = &params.metadata_path {
+            write_metadata(Path::new(path), params)?;
+        }
+
+        if params.transport == TransportKind::Rtp {
+            if let (Some(destination), Some(path)) =
+                (&params.rtp_destination, &params.session_description_path)
+            {
+                rtp::write_session_description(Path::new(path), rtp::resolve(destination).await?)?;
+            }
+        }
+
+        let announcer = match params.negotiation_port {
+            Some(port) => Some(StreamAnnouncer::bind(port, stream_description(params)).await?),
+            None => None,
+        };
+
+        let clock_responder = match params.clock_sync_port {
+            Some(port) => Some(ClockSyncResponder::bind(port).await?),
+            None => None,
+        };
+
+        let feedback = match params.feedback_port {
+            Some(port) => Some(FeedbackReceiver::bind(port).await?),
+            None => None,
+        };
+
+        let pools = ServerPools::new(params);
+        let error_handling = build_error_handling_pipeline(&pools, params)?;
+        let frame_dump = params.frame_dump_path.clone().map(build_frame_dump_pipeline);
+        let stop_signal = params
+            .stop_signal
+            .clone()
+            .unwrap_or_else(|| Arc::new(Notify::new()));
+        let main = build_main_pipeline(
+            capturer,
+            &pools,
+            &error_handling,
+            frame_dump.as_ref(),
+            feedback.as_ref(),
+            stop_signal.clone(),
+            params,
+        )
+        .await?;
+
+        Ok(Self {
+            main,
+            error_handling,
+            frame_dump,
+            announcer,
+            clock_responder,
+            feedback,
+            limits: params.limits,
+            stop_signal,
+            _pools: pools,
+        })
+    }
+
+    pub async fn run(self) {
+        let mut pipelines = vec![self.main, self.error_handling];
+        pipelines.extend(self.frame_dump);
+        run_all(
+            pipelines,
+            self.limits,
+            self.stop_signal,
+            std::future::pending(),
+        )
+        .await;
+    }
+}
+
+// Everything needed to tell how the stream of a run was encoded
+#[derive(Serialize)]
+struct RunMetadata<'a> {
+    codec: Codec,
+    width: usize,
+    height: usize,
+    frame_rate: i32,
+    encoder: &'a EncoderSettings,
+    // Options of the X264Encoder when it encodes the run, backend being unused then
+    x264_options: Option<String>,
+    backend: EncoderSetup,
+}
+
+fn write_metadata(path: &Path, params: &ServerParameters) -> std::io::Result<()> {
+    let metadata = RunMetadata {
+        codec: params.codec,
+        width: params.width,
+        height: params.height,
+        frame_rate: params.frame_rate(),
+        encoder: &params.encoder,
+        x264_options: params.x264_options(),
+        backend: params.encoder.setup(params.codec),
+    };
+
+    let file = File::create(path)?;
+    serde_json::to_writer_pretty(file, &metadata)?;
+    Ok(())
+}
+
+pub fn stream_description(params: &ServerParameters) -> StreamDescription {
+    StreamDescription {
+        width: params.width,
+        height: params.height,
+        pixel_format: "yuv420p".to_string(),
+        codec: params.codec.stream_name().to_string(),
+    }
+}
+
+pub fn build_error_handling_pipeline(
+    pools: &ServerPools,
+    params: &ServerParameters,
+) -> std::io::Result<AscodePipeline> {
+    let mut component = Component::new()
+        .append(pools.raw_frame.redeemer().soft())
+        .append(pools.y_channel.redeemer().soft())
+        .append(pools.cr_channel.redeemer().soft())
+        .append(pools.cb_channel.redeemer().soft())
+        .append(pools.encoded_frame.redeemer().soft())
+        .append(pools.send_queue.dequeuer());
+
+    if params.console_drop_reasons {
+        component = component.append(
+            ConsoleDropReasonLogger::new()
+                .log(DropReason::StaleFrame)
+                .log(DropReason::ConnectionError)
+                .log(DropReason::CodecError)
+                .log(DropReason::NoAvailableBuffers),
+        );
+    }
+
+    let component = component
+        .append(KeyChecker::new("capture_timestamp"))
+        .append(KeyChecker::new("frame_id"))
+        .append(DropsCSVSerializer::new(Path::new(&params.drops_csv_path))?);
+
+    Ok(AscodePipeline::new()
+        .tag("ErrorsHandler")
+        .link(component)
+        .bind()
+        .feedable())
+}
+
+pub async fn build_main_pipeline<C: FrameProcessor + Send + 'static>(
+    capturer: C,
+    pools: &ServerPools,
+    error_handling_pipeline: &AscodePipeline,
+    frame_dump_pipeline: Option<&AscodePipeline>,
+    feedback: Option<&FeedbackReceiver>,
+    stop_signal: Arc<Notify>,
+    params: &ServerParameters,
+) -> std::io::Result<AscodePipeline> {
+    let (rate_controller, retunes) = if params.rate_controller.enabled {
+        let (targets, retunes) = watch::channel(params.encoder.target());
+        let rate_controller = RateController::new(
+            params.rate_controller.clone(),
+            targets,
+            pools.send_queue.clone(),
+            feedback.map(FeedbackReceiver::subscribe),
+            Path::new(&params.rate_log_path),
+        )?;
+
+        (Some(rate_controller), Some(retunes))
+    } else {
+        (None, None)
+    };
+
+    let (keyframe_listener, refreshes) = match feedback {
+        Some(feedback) if params.keyframe_requests => {
+            let (requests, refreshes) = watch::channel(0);
+            let listener = KeyframeRequestListener::new(feedback.subscribe(), requests);
+
+            (Some(listener), Some(refreshes))
+        }
+        _ => (None, None),
+    };
+    let forced_keyframes = keyframe_listener.is_some();
+
+    let encoder = match params.x264_options() {
+        Some(options) => {
+            VideoEncoder::x264(params.width, params.height, &params.encoder, &options)?
+        }
+        None => {
+            let mut encoder = FFmpegEncoder::new(
+                params.codec,
+                params.width,
+                params.height,
+                params.frame_rate(),
+                &params.encoder,
+            )?;
+
+            if let Some(retunes) = retunes {
+                encoder = encoder.retuned_by(retunes);
+            }
+            if let Some(refreshes) = refreshes {
+                encoder = encoder.refreshed_by(refreshes);
+            }
+
+            VideoEncoder::FFmpeg(encoder)
+        }
+    };
+
+    let mut encoding_component = Component::new()
+        .append(TimestampDiffCalculator::new(
+            "capturing_component_processing_finished",
+            "capturing_to_encoding_component_delay",
+        ))
+        .append(TimestampDiffCalculator::new(
+            "capture_timestamp",
+            "capture_delay",
+        ))
+        .append(ThresholdBasedFrameDropper::new(
+            "capture_delay",
+            params.capture_delay_threshold,
+        ))
+        .append(DropAnnotator::new(DropStage::CaptureDelay))
+        .append(OnErrorSwitch::new(error_handling_pipeline))
+        .append(TimestampAdder::new(
+            "color_space_conversion_start_timestamp",
+        ))
+        .append(pools.y_channel.borrower())
+        .append(pools.cr_channel.borrower())
+        .append(pools.cb_channel.borrower())
+        .append(DropAnnotator::new(DropStage::ConversionBuffers))
+        .append(OnErrorSwitch::new(error_handling_pipeline))
+        .append(RGBAToYUV420PConverter::new());
+
+    if let Some(frame_dump_pipeline) = frame_dump_pipeline {
+        encoding_component = encoding_component.append(CloneSwitch::new(frame_dump_pipeline));
+    }
+
+    let encoding_component = encoding_component
+        .append(pools.raw_frame.redeemer())
+        .append(TimestampDiffCalculator::new(
+            "color_space_conversion_start_timestamp",
+            "color_space_conversion_time",
+        ))
+        .append(pools.encoded_frame.borrower())
+        .append(DropAnnotator::new(DropStage::EncodingBuffers))
+        .append(OnErrorSwitch::new(error_handling_pipeline))
+        .append(TimestampAdder::new("encoding_start_timestamp"))
+        .append(encoder)
+        .append(pools.y_channel.redeemer())
+        .append(pools.cr_channel.redeemer())
+        .append(pools.cb_channel.redeemer())
+        .append(TimestampDiffCalculator::new(
+            "encoding_start_timestamp",
+            "encoding_time",
+        ))
+        .append(DropAnnotator::new(DropStage::Encoding))
+        .append(OnErrorSwitch::new(error_handling_pipeline))
+        .append(FrameIdStamper::encoded())
+        .append(pools.send_queue.enqueuer())
+        .append(TimestampAdder::new(
+            "encoding_component_processing_finished",
+        ));
+
+    let mut logging_component = Component::new();
+
+    if let Some(max_frames) = params.limits.max_frames {
+        logging_component =
+            logging_component.append(FrameCountLimiter::new(max_frames, stop_signal));
+    }
+
+    if params.console_stats {
+        logging_component = logging_component
+            .append(
+                ConsoleAverageStatsLogger::new()
+                    .header("--- Computational times")
+                    .log("encoded_size")
+                    .log("capture_time")
+                    .log("color_space_conversion_time")
+                    .log("encoding_time")
+                    .log("transmission_time")
+                    .log("total_time"),
+            )
+            .append(
+                ConsoleAverageStatsLogger::new()
+                    .header("--- Components communication delays")
+                    .log("capturing_to_encoding_component_delay")
+                    .log("encoding_to_transmission_component_delay"),
+            )
+            .append(
+                ConsoleAverageStatsLogger::new()
+                    .header("--- Delay times")
+                    .log("capture_delay")
+                    .log("pre_transmission_delay"),
+            );
+    }
+
+    let mut stats_serializer = CSVFrameDataSerializer::new(&params.stats_csv_path)
+        .log("frame_id")
+        .log("capture_timestamp")
+        .log("encoded_size")
+        .log("capture_time")
+        .log("color_space_conversion_time")
+        .log("encoding_time")
+        .log("transmission_time")
+        .log("total_time")
+        .log("capture_delay")
+        .log("pre_transmission_delay")
+        .log("send_queue_bytes");
+
+    for stat in &params.extra_logged_stats {
+        stats_serializer = stats_serializer.log(stat);
+    }
+
+    if params.transport == TransportKind::Rtp {
+        for stat in RTP_SENDER_STATS {
+            stats_serializer = stats_serializer.log(stat);
+        }
+    }
+
+    if params.transport == TransportKind::Udp {
+        for stat in UDP_SENDER_STATS {
+            stats_serializer = stats_serializer.log(stat);
+        }
+    }
+
+    if forced_keyframes {
+        stats_serializer = stats_serializer.log("forced_keyframe");
+    }
+
+    if params.rate_controller.enabled {
+        stats_serializer = stats_serializer.log("encoder_reopened");
+    }
+
+    if params.link_state.is_some() {
+        for stat in LINK_STATE_STATS {
+            stats_serializer = stats_serializer.log(stat);
+        }
+    }
+
+    let logging_component = logging_component.append(stats_serializer);
+
+    let mut capturing_component = Component::new()
+        .append(Ticker::new(params.tick_interval))
+        .append(FrameIdStamper::new())
+        .append(TimestampAdder::new("process_start_timestamp"))
+        .append(pools.raw_frame.borrower())
+        .append(DropAnnotator::new(DropStage::CaptureBuffers))
+        .append(OnErrorSwitch::new(error_handling_pipeline))
+        .append(TimestampAdder::new("capture_timestamp"))
+        .append(capturer)
+        .append(DropAnnotator::new(DropStage::Capture))
+        .append(OnErrorSwitch::new(error_handling_pipeline))
+        .append(TimestampDiffCalculator::new(
+            "capture_timestamp",
+            "capture_time",
+        ));
+
+    if let Some(link_state) = &params.link_state {
+        capturing_component = capturing_component.append(LinkStateStamper::new(link_state.clone()));
+    }
+
+    if let Some(keyframe_listener) = keyframe_listener {
+        capturing_component = capturing_component.append(keyframe_listener);
+    }
+
+    let capturing_component = capturing_component.append(TimestampAdder::new(
+        "capturing_component_processing_finished",
+    ));
+
+    let mut transmission_component = Component::new()
+        .append(TimestampDiffCalculator::new(
+            "encoding_component_processing_finished",
+            "encoding_to_transmission_component_delay",
+        ))
+        .append(TimestampDiffCalculator::new(
+            "capture_timestamp",
+            "pre_transmission_delay",
+        ))
+        .append(ThresholdBasedFrameDropper::new(
+            "pre_transmission_delay",
+            params.pre_transmission_delay_threshold,
+        ))
+        .append(DropAnnotator::new(DropStage::PreTransmissionDelay))
+        .append(OnErrorSwitch::new(error_handling_pipeline))
+        .append(TimestampAdder::new("transmission_start_timestamp"))
+        .append(
+            FrameSender::bind(
+                params.transport,
+                params.transport_port,
+                params.srt_latency,
+                params.rtp_destination.as_deref(),
+                &params.fec,
+                params.nack,
+            )
+            .await?,
+        )
+        .append(pools.send_queue.dequeuer())
+        .append(pools.encoded_frame.redeemer())
+        .append(TimestampDiffCalculator::new(
+            "transmission_start_timestamp",
+            "transmission_time",
+        ))
+        .append(TimestampDiffCalculator::new(
+            "process_start_timestamp",
+            "total_time",
+        ))
+        .append(DropAnnotator::new(DropStage::Transmission))
+        .append(OnErrorSwitch::new(error_handling_pipeline));
+
+    if let Some(rate_controller) = rate_controller {
+        transmission_component = transmission_component.append(rate_controller);
+    }
+
+    Ok(AscodePipeline::new()
+        .tag("ServerMain")
+        .link(capturing_component)
+        .link(encoding_component)
+        .link(transmission_component)
+        .link(logging_component)
+        .bind())
+}
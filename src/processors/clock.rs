@@ -0,0 +1,62 @@
+use async_trait::async_trait;
+use remotia::{traits::FrameProcessor, types::FrameData};
+use tokio::sync::watch;
+
+use crate::{
+    clock::ClockEstimate,
+    time::{now_timestamp, now_timestamp_micros},
+};
+
+pub const CLOCK_OFFSET_STATS: [&str; 3] = [
+    "server_clock_lead",
+    "server_clock_lag",
+    "clock_offset_uncertainty",
+];
+
+// Translates the server's capture_timestamp to the local clock as local_capture_timestamp.
+// The offset is logged in microseconds split by sign, since stats are unsigned: at most one of
+// server_clock_lead and server_clock_lag is non-zero. Without estimates the clocks are assumed
+// to be shared and the timestamp is copied as is. An estimate off by more than the actual delay
+// would place the capture in the future, so the result never goes past the local time.
+pub struct ClockOffsetCorrector {
+    estimates: Option<watch::Receiver<Option<ClockEstimate>>>,
+}
+
+impl ClockOffsetCorrector {
+    pub fn new(estimates: Option<watch::Receiver<Option<ClockEstimate>>>) -> Self {
+        Self { estimates }
+    }
+}
+
+#[async_trait]
+impl FrameProcessor for ClockOffsetCorrector {
+    async fn process(&mut self, mut frame_data: FrameData) -> Option<FrameData> {
+        let capture_timestamp = frame_data.get("capture_timestamp");
+        let estimate = self
+            .estimates
+            .as_ref()
+            .and_then(|estimates| *estimates.borrow());
+
+        let (local_capture_timestamp, offset, uncertainty) = match estimate {
+            Some(estimate) => {
+                let now = now_timestamp_micros();
+                (
+                    estimate.local_timestamp(capture_timestamp, now),
+                    estimate.offset_at(now),
+                    estimate.uncertainty_at(now),
+                )
+            }
+            None => (capture_timestamp, 0, 0),
+        };
+
+        frame_data.set(
+            "local_capture_timestamp",
+            local_capture_timestamp.min(now_timestamp()),
+        );
+        frame_data.set("server_clock_lead", offset.max(0) as u128);
+        frame_data.set("server_clock_lag", offset.min(0).unsigned_abs() as u128);
+        frame_data.set("clock_offset_uncertainty", uncertainty as u128);
+
+        Some(frame_data)
+    }
+}
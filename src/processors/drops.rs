@@ -0,0 +1,151 @@
+use std::{fs::File, path::Path};
+
+use async_trait::async_trait;
+use log::warn;
+use remotia::{traits::FrameProcessor, types::FrameData};
+
+// Point of the pipelines where a frame can be dropped, stored in the drop_stage stat
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DropStage {
+    CaptureBuffers = 1,
+    Capture,
+    CaptureDelay,
+    ConversionBuffers,
+    EncodingBuffers,
+    Encoding,
+    PreTransmissionDelay,
+    Transmission,
+    ReceptionBuffers,
+    Reception,
+    DecodingBuffers,
+    Decoding,
+    PreRenderDelay,
+    Rendering,
+}
+
+const STAGES: [DropStage; 14] = [
+    DropStage::CaptureBuffers,
+    DropStage::Capture,
+    DropStage::CaptureDelay,
+    DropStage::ConversionBuffers,
+    DropStage::EncodingBuffers,
+    DropStage::Encoding,
+    DropStage::PreTransmissionDelay,
+    DropStage::Transmission,
+    DropStage::ReceptionBuffers,
+    DropStage::Reception,
+    DropStage::DecodingBuffers,
+    DropStage::Decoding,
+    DropStage::PreRenderDelay,
+    DropStage::Rendering,
+];
+
+impl DropStage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::CaptureBuffers => "capture_buffers",
+            Self::Capture => "capture",
+            Self::CaptureDelay => "capture_delay",
+            Self::ConversionBuffers => "conversion_buffers",
+            Self::EncodingBuffers => "encoding_buffers",
+            Self::Encoding => "encoding",
+            Self::PreTransmissionDelay => "pre_transmission_delay",
+            Self::Transmission => "transmission",
+            Self::ReceptionBuffers => "reception_buffers",
+            Self::Reception => "reception",
+            Self::DecodingBuffers => "decoding_buffers",
+            Self::Decoding => "decoding",
+            Self::PreRenderDelay => "pre_render_delay",
+            Self::Rendering => "rendering",
+        }
+    }
+
+    // Stat compared by the ThresholdBasedFrameDropper of the stage
+    pub fn delay_stat(&self) -> Option<&'static str> {
+        match self {
+            Self::CaptureDelay => Some("capture_delay"),
+            Self::PreTransmissionDelay => Some("pre_transmission_delay"),
+            Self::PreRenderDelay => Some("pre_render_frame_delay"),
+            _ => None,
+        }
+    }
+
+    fn from_stat(value: u128) -> Option<Self> {
+        STAGES.iter().copied().find(|stage| *stage as u128 == value)
+    }
+}
+
+// Placed right before an OnErrorSwitch, marks the frames it is about to divert with the stage
+pub struct DropAnnotator {
+    stage: DropStage,
+}
+
+impl DropAnnotator {
+    pub fn new(stage: DropStage) -> Self {
+        Self { stage }
+    }
+}
+
+#[async_trait]
+impl FrameProcessor for DropAnnotator {
+    async fn process(&mut self, mut frame_data: FrameData) -> Option<FrameData> {
+        if frame_data.get_drop_reason().is_some() {
+            frame_data.set("drop_stage", self.stage as u128);
+        }
+
+        Some(frame_data)
+    }
+}
+
+// Writes the frames reaching an error-handling pipeline, with the reason and stage of the drop
+// and, for stale frames, the delay that exceeded the threshold. Expects a KeyChecker on frame_id
+// and capture_timestamp in front of it, as frames can fail before those stats are set
+pub struct DropsCSVSerializer {
+    writer: csv::Writer<File>,
+}
+
+impl DropsCSVSerializer {
+    pub fn new(path: &Path) -> std::io::Result<Self> {
+        let mut writer = csv::Writer::from_path(path)?;
+        writer.write_record([
+            "frame_id",
+            "capture_timestamp",
+            "drop_reason",
+            "drop_stage",
+            "drop_delay",
+        ])?;
+
+        Ok(Self { writer })
+    }
+}
+
+#[async_trait]
+impl FrameProcessor for DropsCSVSerializer {
+    async fn process(&mut self, frame_data: FrameData) -> Option<FrameData> {
+        let stage = DropStage::from_stat(frame_data.get("drop_stage"));
+        let delay = stage
+            .and_then(|stage| stage.delay_stat())
+            .map(|stat| frame_data.get(stat).to_string());
+
+        let record = [
+            frame_data.get("frame_id").to_string(),
+            frame_data.get("capture_timestamp").to_string(),
+            frame_data
+                .get_drop_reason()
+                .map(|reason| format!("{:?}", reason))
+                .unwrap_or_default(),
+            stage.map(|stage| stage.as_str()).unwrap_or_default().to_string(),
+            delay.unwrap_or_default(),
+        ];
+
+        if let Err(err) = self
+            .writer
+            .write_record(&record)
+            .and_then(|_| self.writer.flush().map_err(csv::Error::from))
+        {
+            warn!("Unable to log the dropped frame: {}", err);
+        }
+
+        Some(frame_data)
+    }
+}
@@ -0,0 +1,84 @@
+use std::{collections::BTreeSet, time::Duration};
+
+use async_trait::async_trait;
+use remotia::{traits::FrameProcessor, types::FrameData};
+
+use crate::{
+    feedback::{Feedback, FeedbackSender},
+    time::now_timestamp,
+};
+
+// Missing identifiers remembered to recognize late frames, older ones stay counted as lost
+const MAX_MISSING_IDS: usize = 4096;
+
+// Counts received and missing frame identifiers, reporting them to the server every `interval`.
+// Duplicated frames are not counted.
+pub struct LossReporter {
+    sender: FeedbackSender,
+    interval: u128,
+
+    highest_id: Option<u128>,
+    missing_ids: BTreeSet<u128>,
+    received: u64,
+    lost: u64,
+    last_report: u128,
+}
+
+impl LossReporter {
+    pub fn new(sender: FeedbackSender, interval: Duration) -> Self {
+        Self {
+            sender,
+            interval: interval.as_millis(),
+            highest_id: None,
+            missing_ids: BTreeSet::new(),
+            received: 0,
+            lost: 0,
+            last_report: now_timestamp(),
+        }
+    }
+
+    fn count(&mut self, frame_id: u128) {
+        match self.highest_id {
+            Some(highest_id) if frame_id > highest_id => {
+                self.lost += (frame_id - highest_id - 1) as u64;
+                self.highest_id = Some(frame_id);
+
+                let first_missing =
+                    (highest_id + 1).max(frame_id.saturating_sub(MAX_MISSING_IDS as u128));
+                self.missing_ids.extend(first_missing..frame_id);
+                while self.missing_ids.len() > MAX_MISSING_IDS {
+                    self.missing_ids.pop_first();
+                }
+            }
+            // A late frame was counted as lost when the gap was seen
+            Some(_) if self.missing_ids.remove(&frame_id) => {
+                self.lost = self.lost.saturating_sub(1)
+            }
+            Some(_) => return,
+            None => self.highest_id = Some(frame_id),
+        }
+
+        self.received += 1;
+    }
+}
+
+#[async_trait]
+impl FrameProcessor for LossReporter {
+    async fn process(&mut self, frame_data: FrameData) -> Option<FrameData> {
+        self.count(frame_data.get("frame_id"));
+
+        let now = now_timestamp();
+        if now - self.last_report >= self.interval {
+            self.sender.send(&Feedback::LossReport {
+                received: self.received,
+                lost: self.lost,
+            });
+
+            self.received = 0;
+            self.lost = 0;
+            self.last_report = now;
+        }
+
+        Some(frame_data)
+    }
+}
@@ -0,0 +1,340 @@
+use std::{
+    fs::File,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use log::{debug, info, warn};
+use remotia::{error::DropReason, traits::FrameProcessor, types::FrameData};
+use tokio::sync::{
+    broadcast::{self, error::TryRecvError},
+    watch,
+};
+
+use crate::{
+    feedback::{Feedback, FeedbackSender},
+    time::now_timestamp,
+};
+
+// Server side: counts the keyframe requests of the client, the encoder forcing a keyframe on the
+// next frame whenever the count grows. Requests received between two frames are served by one
+// keyframe.
+pub struct KeyframeRequestListener {
+    feedback: broadcast::Receiver<Feedback>,
+    requests: watch::Sender<u64>,
+    count: u64,
+}
+
+impl KeyframeRequestListener {
+    pub fn new(feedback: broadcast::Receiver<Feedback>, requests: watch::Sender<u64>) -> Self {
+        Self {
+            feedback,
+            requests,
+            count: 0,
+        }
+    }
+
+    fn receive(&mut self) -> bool {
+        let mut requested = false;
+
+        loop {
+            match self.feedback.try_recv() {
+                Ok(Feedback::KeyframeRequest { frame_id }) => {
+                    debug!("The client asked for a keyframe after frame {}", frame_id);
+                    requested = true;
+                }
+                Ok(Feedback::LossReport { .. }) => {}
+                Err(TryRecvError::Lagged(skipped)) => {
+                    debug!("Skipped {} feedback messages", skipped);
+                }
+                Err(TryRecvError::Empty) | Err(TryRecvError::Closed) => return requested,
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl FrameProcessor for KeyframeRequestListener {
+    async fn process(&mut self, frame_data: FrameData) -> Option<FrameData> {
+        if self.receive() {
+            self.count += 1;
+            // The encoder keeps the receiver as long as the pipeline runs
+            self.requests.send(self.count).ok();
+        }
+
+        Some(frame_data)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum LossEvent {
+    Gap,
+    CodecError,
+    NoDecodedFrames,
+}
+
+impl LossEvent {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Gap => "gap",
+            Self::CodecError => "codec_error",
+            Self::NoDecodedFrames => "no_decoded_frames",
+        }
+    }
+}
+
+// A loss waiting for a keyframe, from the first request to the keyframe being decoded
+struct Episode {
+    frame_id: u128,
+    event: LossEvent,
+    requested_at: u128,
+    last_request: u128,
+    requests: u64,
+    // Frames decoded with missing references or failing to decode meanwhile
+    corrupted_frames: u64,
+}
+
+struct RequesterState {
+    episode: Option<Episode>,
+    writer: csv::Writer<File>,
+}
+
+impl RequesterState {
+    fn log(&mut self, episode: &Episode, recovery_frame_id: Option<u128>) -> csv::Result<()> {
+        let recovery_time = recovery_frame_id.map(|_| now_timestamp() - episode.requested_at);
+
+        self.writer.write_record([
+            episode.requested_at.to_string(),
+            episode.frame_id.to_string(),
+            episode.event.as_str().to_string(),
+            episode.requests.to_string(),
+            recovery_frame_id
+                .map(|id| id.to_string())
+                .unwrap_or_default(),
+            recovery_time
+                .map(|time| time.to_string())
+                .unwrap_or_default(),
+            episode.corrupted_frames.to_string(),
+        ])?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+impl Drop for RequesterState {
+    // An episode still open when the run ends is logged without recovery
+    fn drop(&mut self) {
+        if let Some(episode) = self.episode.take() {
+            self.log(&episode, None).ok();
+        }
+    }
+}
+
+// Client side: asks the server for a keyframe when a frame is lost or fails to decode, repeating
+// the request every `interval` until a keyframe is decoded, and logs every such episode to its
+// own CSV. A gap detector goes after the SequenceTracker, an error detector in the error handling
+// pipeline and a recovery detector after the decoder.
+#[derive(Clone)]
+pub struct KeyframeRequester {
+    sender: FeedbackSender,
+    interval: u128,
+    state: Arc<Mutex<RequesterState>>,
+}
+
+impl KeyframeRequester {
+    pub fn new(
+        sender: FeedbackSender,
+        interval: Duration,
+        log_path: &Path,
+    ) -> std::io::Result<Self> {
+        let mut writer = csv::Writer::from_path(log_path)?;
+        writer.write_record([
+            "timestamp",
+            "frame_id",
+            "event",
+            "requests",
+            "recovery_frame_id",
+            "recovery_time",
+            "corrupted_frames",
+        ])?;
+        writer.flush()?;
+
+        Ok(Self {
+            sender,
+            interval: interval.as_millis(),
+            state: Arc::new(Mutex::new(RequesterState {
+                episode: None,
+                writer,
+            })),
+        })
+    }
+
+    pub fn gap_detector(&self) -> GapDetector {
+        GapDetector {
+            requester: self.clone(),
+            highest_id: None,
+        }
+    }
+
+    pub fn error_detector(&self) -> DecodingErrorDetector {
+        DecodingErrorDetector {
+            requester: self.clone(),
+        }
+    }
+
+    pub fn recovery_detector(&self) -> RecoveryDetector {
+        RecoveryDetector {
+            requester: self.clone(),
+        }
+    }
+
+    // Opens an episode on `event`, or repeats the request of the open one when due
+    fn request(&self, frame_id: u128, event: Option<LossEvent>) {
+        let now = now_timestamp();
+        let mut guard = self.state.lock().unwrap();
+        let state = &mut *guard;
+
+        if let Some(episode) = &mut state.episode {
+            if now - episode.last_request < self.interval {
+                return;
+            }
+            episode.last_request = now;
+            episode.requests += 1;
+        } else if let Some(event) = event {
+            debug!(
+                "Requesting a keyframe after the {:?} of frame {}",
+                event, frame_id
+            );
+            state.episode = Some(Episode {
+                frame_id,
+                event,
+                requested_at: now,
+                last_request: now,
+                requests: 1,
+                corrupted_frames: 0,
+            });
+        } else {
+            return;
+        }
+
+        self.sender.send(&Feedback::KeyframeRequest {
+            frame_id: frame_id as u64,
+        });
+    }
+
+    // Closes the open episode on a keyframe, otherwise counts a corrupted frame. Returns whether the
+    // frame is corrupted.
+    fn decoded(&self, frame_id: u128, keyframe: bool) -> bool {
+        let mut guard = self.state.lock().unwrap();
+        let state = &mut *guard;
+
+        let episode = match &mut state.episode {
+            Some(episode) => episode,
+            None => return false,
+        };
+
+        if !keyframe {
+            episode.corrupted_frames += 1;
+            return true;
+        }
+
+        let episode = state.episode.take().unwrap();
+        info!(
+            "Recovered at frame {} from the {} of frame {} after {} ms and {} corrupted frames",
+            frame_id,
+            episode.event.as_str(),
+            episode.frame_id,
+            now_timestamp() - episode.requested_at,
+            episode.corrupted_frames
+        );
+
+        if let Err(err) = state.log(&episode, Some(frame_id)) {
+            warn!("Unable to log the keyframe request: {}", err);
+        }
+
+        false
+    }
+
+    fn failed(&self) {
+        if let Some(episode) = &mut self.state.lock().unwrap().episode {
+            episode.corrupted_frames += 1;
+        }
+    }
+}
+
+// Frames missing from the encoded_frame_id sequence, which only numbers the frames the encoder
+// produced: the ones dropped by the server before encoding leave no gap, as no later frame
+// references them
+pub struct GapDetector {
+    requester: KeyframeRequester,
+    highest_id: Option<u128>,
+}
+
+#[async_trait]
+impl FrameProcessor for GapDetector {
+    async fn process(&mut self, frame_data: FrameData) -> Option<FrameData> {
+        let encoded_frame_id = frame_data.get("encoded_frame_id");
+
+        match self.highest_id {
+            Some(highest_id) if encoded_frame_id > highest_id + 1 => {
+                self.requester
+                    .request(frame_data.get("frame_id"), Some(LossEvent::Gap));
+            }
+            _ => {}
+        }
+
+        if self
+            .highest_id
+            .map_or(true, |highest_id| encoded_frame_id > highest_id)
+        {
+            self.highest_id = Some(encoded_frame_id);
+        }
+
+        Some(frame_data)
+    }
+}
+
+// Placed after the KeyChecker of the error handling pipeline
+pub struct DecodingErrorDetector {
+    requester: KeyframeRequester,
+}
+
+#[async_trait]
+impl FrameProcessor for DecodingErrorDetector {
+    async fn process(&mut self, frame_data: FrameData) -> Option<FrameData> {
+        let event = match frame_data.get_drop_reason() {
+            Some(DropReason::CodecError) => LossEvent::CodecError,
+            Some(DropReason::NoDecodedFrames) => LossEvent::NoDecodedFrames,
+            _ => return Some(frame_data),
+        };
+
+        self.requester
+            .request(frame_data.get("frame_id"), Some(event));
+        self.requester.failed();
+
+        Some(frame_data)
+    }
+}
+
+// Reads the decoded_keyframe stat of the decoder, setting corrupted_frame
+pub struct RecoveryDetector {
+    requester: KeyframeRequester,
+}
+
+#[async_trait]
+impl FrameProcessor for RecoveryDetector {
+    async fn process(&mut self, mut frame_data: FrameData) -> Option<FrameData> {
+        let frame_id = frame_data.get("frame_id");
+        let keyframe = frame_data.get("decoded_keyframe") == 1;
+
+        let corrupted = self.requester.decoded(frame_id, keyframe);
+        if corrupted {
+            self.requester.request(frame_id, None);
+        }
+
+        frame_data.set("corrupted_frame", corrupted as u128);
+        Some(frame_data)
+    }
+}
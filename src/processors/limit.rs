@@ -0,0 +1,36 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use log::info;
+use remotia::{traits::FrameProcessor, types::FrameData};
+use tokio::sync::Notify;
+
+pub struct FrameCountLimiter {
+    max_frames: u64,
+    processed_frames: u64,
+    stop_signal: Arc<Notify>,
+}
+
+impl FrameCountLimiter {
+    pub fn new(max_frames: u64, stop_signal: Arc<Notify>) -> Self {
+        Self {
+            max_frames,
+            processed_frames: 0,
+            stop_signal,
+        }
+    }
+}
+
+#[async_trait]
+impl FrameProcessor for FrameCountLimiter {
+    async fn process(&mut self, frame_data: FrameData) -> Option<FrameData> {
+        self.processed_frames += 1;
+
+        if self.processed_frames == self.max_frames {
+            info!("Processed {} frames, stopping", self.processed_frames);
+            self.stop_signal.notify_one();
+        }
+
+        Some(frame_data)
+    }
+}
@@ -0,0 +1,8 @@
+pub mod clock;
+pub mod drops;
+pub mod feedback;
+pub mod keyframe;
+pub mod limit;
+pub mod quality;
+pub mod rate;
+pub mod sequence;
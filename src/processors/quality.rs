@@ -0,0 +1,99 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use log::debug;
+use remotia::{traits::FrameProcessor, types::FrameData};
+
+use crate::quality::FrameQuality;
+
+// Stats are integers: psnr is in 1/10000 dB and ssim in 1/10000, negative SSIM being clamped to 0
+pub const QUALITY_STATS_SCALE: f64 = 10_000.0;
+
+type References = Arc<Mutex<BTreeMap<u128, Vec<u8>>>>;
+
+// Pair of processors measuring the quality of a codec inside a single pipeline: the keeper copies
+// each raw frame before encoding and the meter compares the decoded frame against that copy,
+// matching them by capture_timestamp
+pub fn quality_meter(
+    width: usize,
+    height: usize,
+    max_pending: usize,
+) -> std::io::Result<(QualityReferenceKeeper, QualityMeter)> {
+    FrameQuality::check_resolution(width, height)?;
+
+    let references: References = Arc::new(Mutex::new(BTreeMap::new()));
+
+    Ok((
+        QualityReferenceKeeper {
+            references: references.clone(),
+            max_pending,
+        },
+        QualityMeter {
+            references,
+            width,
+            height,
+        },
+    ))
+}
+
+pub struct QualityReferenceKeeper {
+    references: References,
+    // Frames dropped before decoding never claim their reference
+    max_pending: usize,
+}
+
+#[async_trait]
+impl FrameProcessor for QualityReferenceKeeper {
+    async fn process(&mut self, mut frame_data: FrameData) -> Option<FrameData> {
+        let capture_timestamp = frame_data.get("capture_timestamp");
+        let pixels = frame_data
+            .get_writable_buffer_ref("raw_frame_buffer")
+            .unwrap()
+            .to_vec();
+
+        let mut references = self.references.lock().unwrap();
+        references.insert(capture_timestamp, pixels);
+        while references.len() > self.max_pending {
+            let oldest = *references.keys().next().unwrap();
+            references.remove(&oldest);
+        }
+
+        Some(frame_data)
+    }
+}
+
+pub struct QualityMeter {
+    references: References,
+    width: usize,
+    height: usize,
+}
+
+#[async_trait]
+impl FrameProcessor for QualityMeter {
+    async fn process(&mut self, mut frame_data: FrameData) -> Option<FrameData> {
+        let capture_timestamp = frame_data.get("capture_timestamp");
+        let reference = self.references.lock().unwrap().remove(&capture_timestamp);
+
+        let reference = match reference {
+            Some(reference) => reference,
+            None => {
+                debug!("No reference kept for frame {}", capture_timestamp);
+                return Some(frame_data);
+            }
+        };
+
+        let decoded = frame_data
+            .get_writable_buffer_ref("raw_frame_buffer")
+            .unwrap();
+        // The resolution was checked by quality_meter
+        let quality = FrameQuality::compare(&reference, decoded, self.width, self.height).unwrap();
+
+        frame_data.set("psnr", (quality.psnr_y * QUALITY_STATS_SCALE).round() as u128);
+        frame_data.set("ssim", (quality.ssim.max(0.0) * QUALITY_STATS_SCALE).round() as u128);
+
+        Some(frame_data)
+    }
+}
@@ -0,0 +1,387 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{Error, ErrorKind},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use log::{debug, warn};
+use remotia::{traits::FrameProcessor, types::FrameData};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{
+    broadcast::{self, error::TryRecvError},
+    watch,
+};
+
+use crate::{
+    codecs::settings::{EncoderTarget, MAX_BITRATE},
+    feedback::Feedback,
+    time::now_timestamp,
+};
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateControllerSettings {
+    pub enabled: bool,
+
+    // Any signal above its threshold lowers the target; u64 as the TOML parser does not handle u128
+    pub transmission_time_threshold: u64,
+    pub send_queue_threshold: u64,
+    // Percentage of frames missing in the last client report
+    pub loss_threshold: f64,
+
+    // Bounds and steps used with cbr and vbr, in kbit/s: additive increase, multiplicative decrease
+    pub min_bitrate: u64,
+    pub max_bitrate: u64,
+    pub bitrate_increase: u64,
+    pub bitrate_decrease: f64,
+
+    // Bounds and step used with crf
+    pub min_crf: u32,
+    pub max_crf: u32,
+    pub crf_step: u32,
+
+    // Milliseconds between two adjustments, leaving time for the previous one to show its effect
+    pub adjustment_interval: u64,
+}
+
+impl Default for RateControllerSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            transmission_time_threshold: 30,
+            send_queue_threshold: 1_000_000,
+            loss_threshold: 2.0,
+            min_bitrate: 1000,
+            max_bitrate: 40000,
+            bitrate_increase: 500,
+            bitrate_decrease: 0.85,
+            min_crf: 18,
+            max_crf: 40,
+            crf_step: 1,
+            adjustment_interval: 250,
+        }
+    }
+}
+
+impl RateControllerSettings {
+    pub fn validate(&self) -> std::io::Result<()> {
+        let invalid = |message: &str| Err(Error::new(ErrorKind::InvalidData, message.to_string()));
+
+        if self.min_bitrate == 0 || self.min_bitrate > self.max_bitrate {
+            return invalid("min_bitrate must be greater than zero and at most max_bitrate");
+        }
+
+        if self.max_bitrate > MAX_BITRATE {
+            return invalid(&format!("max_bitrate must be at most {} kbit/s", MAX_BITRATE));
+        }
+
+        if self.bitrate_decrease <= 0.0 || self.bitrate_decrease >= 1.0 {
+            return invalid("bitrate_decrease must be between 0 and 1");
+        }
+
+        if self.min_crf > self.max_crf || self.max_crf > 63 {
+            return invalid("min_crf must be at most max_crf, itself at most 63");
+        }
+
+        if !(0.0..=100.0).contains(&self.loss_threshold) {
+            return invalid("loss_threshold must be a percentage");
+        }
+
+        Ok(())
+    }
+}
+
+// Encoded bytes handed to the transport and not sent yet, keyed by frame_id: the enqueuer goes after
+// the encoder and a dequeuer after the sender, another one in the error handling pipeline
+#[derive(Clone, Default)]
+pub struct SendQueue {
+    frames: Arc<Mutex<BTreeMap<u128, u128>>>,
+}
+
+impl SendQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn bytes(&self) -> u128 {
+        self.frames.lock().unwrap().values().sum()
+    }
+
+    pub fn enqueuer(&self) -> SendQueueEnqueuer {
+        SendQueueEnqueuer {
+            queue: self.clone(),
+        }
+    }
+
+    pub fn dequeuer(&self) -> SendQueueDequeuer {
+        SendQueueDequeuer {
+            queue: self.clone(),
+        }
+    }
+}
+
+// Sets send_queue_bytes, including the frame itself
+pub struct SendQueueEnqueuer {
+    queue: SendQueue,
+}
+
+#[async_trait]
+impl FrameProcessor for SendQueueEnqueuer {
+    async fn process(&mut self, mut frame_data: FrameData) -> Option<FrameData> {
+        let frame_id = frame_data.get("frame_id");
+        let encoded_size = frame_data.get("encoded_size");
+
+        let bytes = {
+            let mut frames = self.queue.frames.lock().unwrap();
+            frames.insert(frame_id, encoded_size);
+            frames.values().sum()
+        };
+        frame_data.set("send_queue_bytes", bytes);
+
+        Some(frame_data)
+    }
+}
+
+pub struct SendQueueDequeuer {
+    queue: SendQueue,
+}
+
+#[async_trait]
+impl FrameProcessor for SendQueueDequeuer {
+    async fn process(&mut self, frame_data: FrameData) -> Option<FrameData> {
+        let frame_id = frame_data.get("frame_id");
+        self.queue.frames.lock().unwrap().remove(&frame_id);
+
+        Some(frame_data)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum RateDecision {
+    Hold,
+    Increase,
+    Decrease,
+}
+
+impl RateDecision {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Hold => "hold",
+            Self::Increase => "increase",
+            Self::Decrease => "decrease",
+        }
+    }
+}
+
+// Goes after the transmission: decides a new encoder target for every sent frame and publishes it
+// to the FFmpegEncoder, logging each decision with the signals it was based on
+pub struct RateController {
+    settings: RateControllerSettings,
+    targets: watch::Sender<EncoderTarget>,
+    target: EncoderTarget,
+    send_queue: SendQueue,
+
+    feedback: Option<broadcast::Receiver<Feedback>>,
+    client_loss: f64,
+
+    last_adjustment: u128,
+    writer: csv::Writer<File>,
+}
+
+impl RateController {
+    pub fn new(
+        settings: RateControllerSettings,
+        targets: watch::Sender<EncoderTarget>,
+        send_queue: SendQueue,
+        feedback: Option<broadcast::Receiver<Feedback>>,
+        log_path: &Path,
+    ) -> std::io::Result<Self> {
+        let mut writer = csv::Writer::from_path(log_path)?;
+        writer.write_record([
+            "frame_id",
+            "timestamp",
+            "transmission_time",
+            "send_queue_bytes",
+            "client_loss",
+            "decision",
+            "reason",
+            "target",
+        ])?;
+        writer.flush()?;
+
+        let target = *targets.borrow();
+
+        Ok(Self {
+            settings,
+            targets,
+            target,
+            send_queue,
+            feedback,
+            client_loss: 0.0,
+            last_adjustment: 0,
+            writer,
+        })
+    }
+
+    // Keeps the loss of the most recent report
+    fn poll_feedback(&mut self) {
+        let feedback = match &mut self.feedback {
+            Some(feedback) => feedback,
+            None => return,
+        };
+
+        loop {
+            match feedback.try_recv() {
+                Ok(Feedback::LossReport { received, lost }) => {
+                    let expected = received + lost;
+                    if expected > 0 {
+                        self.client_loss = lost as f64 * 100.0 / expected as f64;
+                    }
+                }
+                Ok(Feedback::KeyframeRequest { .. }) => {}
+                Err(TryRecvError::Lagged(skipped)) => {
+                    debug!("Skipped {} feedback messages", skipped);
+                }
+                Err(TryRecvError::Empty) | Err(TryRecvError::Closed) => return,
+            }
+        }
+    }
+
+    fn congestion(&self, transmission_time: u128, send_queue_bytes: u128) -> Option<&'static str> {
+        if transmission_time > self.settings.transmission_time_threshold as u128 {
+            Some("transmission_time")
+        } else if send_queue_bytes > self.settings.send_queue_threshold as u128 {
+            Some("send_queue")
+        } else if self.client_loss > self.settings.loss_threshold {
+            Some("client_loss")
+        } else {
+            None
+        }
+    }
+
+    // Raising the target again needs every signal well below its threshold
+    fn has_headroom(&self, transmission_time: u128, send_queue_bytes: u128) -> bool {
+        transmission_time * 2 <= self.settings.transmission_time_threshold as u128
+            && send_queue_bytes * 2 <= self.settings.send_queue_threshold as u128
+            && self.client_loss * 2.0 <= self.settings.loss_threshold
+    }
+
+    fn adjusted(&self, decision: RateDecision) -> EncoderTarget {
+        let settings = &self.settings;
+        match (self.target, decision) {
+            (EncoderTarget::Bitrate(bitrate), RateDecision::Increase) => EncoderTarget::Bitrate(
+                (bitrate + settings.bitrate_increase).min(settings.max_bitrate),
+            ),
+            (EncoderTarget::Bitrate(bitrate), RateDecision::Decrease) => {
+                let decreased = (bitrate as f64 * settings.bitrate_decrease) as u64;
+                EncoderTarget::Bitrate(decreased.max(settings.min_bitrate))
+            }
+            // A higher CRF lowers the quality, hence the bitrate
+            (EncoderTarget::Crf(crf), RateDecision::Increase) => {
+                EncoderTarget::Crf(crf.saturating_sub(settings.crf_step).max(settings.min_crf))
+            }
+            (EncoderTarget::Crf(crf), RateDecision::Decrease) => {
+                EncoderTarget::Crf((crf + settings.crf_step).min(settings.max_crf))
+            }
+            (target, RateDecision::Hold) => target,
+        }
+    }
+
+    fn decide(
+        &self,
+        transmission_time: u128,
+        send_queue_bytes: u128,
+        now: u128,
+    ) -> (RateDecision, &'static str) {
+        if now - self.last_adjustment < self.settings.adjustment_interval as u128 {
+            return (RateDecision::Hold, "interval");
+        }
+
+        let decision = match self.congestion(transmission_time, send_queue_bytes) {
+            Some(reason) => (RateDecision::Decrease, reason),
+            None if self.has_headroom(transmission_time, send_queue_bytes) => {
+                (RateDecision::Increase, "headroom")
+            }
+            None => return (RateDecision::Hold, "stable"),
+        };
+
+        if self.adjusted(decision.0) == self.target {
+            (RateDecision::Hold, "limit")
+        } else {
+            decision
+        }
+    }
+
+    fn log(
+        &mut self,
+        frame_id: u128,
+        now: u128,
+        transmission_time: u128,
+        send_queue_bytes: u128,
+        decision: RateDecision,
+        reason: &str,
+    ) -> csv::Result<()> {
+        let target = match self.target {
+            EncoderTarget::Bitrate(bitrate) => bitrate as u128,
+            EncoderTarget::Crf(crf) => crf as u128,
+        };
+
+        self.writer.write_record([
+            frame_id.to_string(),
+            now.to_string(),
+            transmission_time.to_string(),
+            send_queue_bytes.to_string(),
+            format!("{:.2}", self.client_loss),
+            decision.as_str().to_string(),
+            reason.to_string(),
+            target.to_string(),
+        ])?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl FrameProcessor for RateController {
+    async fn process(&mut self, frame_data: FrameData) -> Option<FrameData> {
+        let frame_id = frame_data.get("frame_id");
+        let transmission_time = frame_data.get("transmission_time");
+        let send_queue_bytes = self.send_queue.bytes();
+        let now = now_timestamp();
+
+        self.poll_feedback();
+
+        let (decision, reason) = self.decide(transmission_time, send_queue_bytes, now);
+        if decision != RateDecision::Hold {
+            self.target = self.adjusted(decision);
+            self.last_adjustment = now;
+            debug!(
+                "Frame {}: {} to {:?} ({})",
+                frame_id,
+                decision.as_str(),
+                self.target,
+                reason
+            );
+
+            if self.targets.send(self.target).is_err() {
+                warn!("The encoder is gone, the new target is ignored");
+            }
+        }
+
+        if let Err(err) = self.log(
+            frame_id,
+            now,
+            transmission_time,
+            send_queue_bytes,
+            decision,
+            reason,
+        ) {
+            warn!("Unable to log the rate decision: {}", err);
+        }
+
+        Some(frame_data)
+    }
+}
@@ -0,0 +1,240 @@
+use std::{collections::BTreeSet, fs::File, path::Path};
+
+use async_trait::async_trait;
+use log::{debug, warn};
+use remotia::{traits::FrameProcessor, types::FrameData};
+
+use crate::time::now_timestamp;
+
+// Identifiers further behind the highest one received are forgotten
+const DUPLICATES_WINDOW: u128 = 4096;
+
+// Numbers every frame leaving the ticker, before anything can drop it, so that frame_id
+// identifies the same frame in all the server and client logs
+pub struct FrameIdStamper {
+    stat: &'static str,
+    next_id: u128,
+}
+
+impl FrameIdStamper {
+    pub fn new() -> Self {
+        Self {
+            stat: "frame_id",
+            next_id: 0,
+        }
+    }
+
+    // Numbers the frames coming out of the encoder in encoded_frame_id instead, so that the client
+    // tells the frames lost on the way, which the next ones may reference, from the ones dropped
+    // before encoding
+    pub fn encoded() -> Self {
+        Self {
+            stat: "encoded_frame_id",
+            next_id: 0,
+        }
+    }
+}
+
+impl Default for FrameIdStamper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl FrameProcessor for FrameIdStamper {
+    async fn process(&mut self, mut frame_data: FrameData) -> Option<FrameData> {
+        frame_data.set(self.stat, self.next_id);
+        self.next_id += 1;
+
+        Some(frame_data)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum SequenceEvent {
+    // `count` identifiers were skipped before this one
+    Gap,
+    Duplicate,
+    // Arrived after a frame with a higher identifier
+    OutOfOrder,
+}
+
+impl SequenceEvent {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Gap => "gap",
+            Self::Duplicate => "duplicate",
+            Self::OutOfOrder => "out_of_order",
+        }
+    }
+}
+
+// Checks the frame_id of received frames, logging every irregularity to its own CSV
+pub struct SequenceTracker {
+    highest_id: Option<u128>,
+    received_ids: BTreeSet<u128>,
+    writer: csv::Writer<File>,
+}
+
+impl SequenceTracker {
+    pub fn new(log_path: &Path) -> std::io::Result<Self> {
+        let mut writer = csv::Writer::from_path(log_path)?;
+        writer.write_record(["timestamp", "frame_id", "event", "count"])?;
+        writer.flush()?;
+
+        Ok(Self {
+            highest_id: None,
+            received_ids: BTreeSet::new(),
+            writer,
+        })
+    }
+
+    fn track(&mut self, frame_id: u128) -> Option<(SequenceEvent, u128)> {
+        if !self.received_ids.insert(frame_id) {
+            return Some((SequenceEvent::Duplicate, 1));
+        }
+
+        let event = match self.highest_id {
+            Some(highest_id) if frame_id < highest_id => Some((SequenceEvent::OutOfOrder, 1)),
+            Some(highest_id) if frame_id > highest_id + 1 => {
+                Some((SequenceEvent::Gap, frame_id - highest_id - 1))
+            }
+            _ => None,
+        };
+
+        if self.highest_id.map_or(true, |highest_id| frame_id > highest_id) {
+            self.highest_id = Some(frame_id);
+            self.received_ids = self
+                .received_ids
+                .split_off(&frame_id.saturating_sub(DUPLICATES_WINDOW));
+        }
+
+        event
+    }
+
+    fn log(&mut self, frame_id: u128, event: SequenceEvent, count: u128) -> csv::Result<()> {
+        self.writer.write_record([
+            now_timestamp().to_string(),
+            frame_id.to_string(),
+            event.as_str().to_string(),
+            count.to_string(),
+        ])?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl FrameProcessor for SequenceTracker {
+    async fn process(&mut self, frame_data: FrameData) -> Option<FrameData> {
+        let frame_id = frame_data.get("frame_id");
+
+        if let Some((event, count)) = self.track(frame_id) {
+            debug!("Frame {}: {:?} ({})", frame_id, event, count);
+            if let Err(err) = self.log(frame_id, event, count) {
+                warn!("Unable to log the sequence event: {}", err);
+            }
+        }
+
+        Some(frame_data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::testing::TempDir;
+
+    fn track_all(ids: &[u128]) -> Vec<Option<(SequenceEvent, u128)>> {
+        let directory = TempDir::new("sequence");
+        let mut tracker = SequenceTracker::new(&directory.path("sequence.csv")).unwrap();
+        ids.iter().map(|id| tracker.track(*id)).collect()
+    }
+
+    #[test]
+    fn accepts_consecutive_frames() {
+        assert!(track_all(&[0, 1, 2, 3]).iter().all(Option::is_none));
+        // The first frame received sets the start of the sequence
+        assert!(track_all(&[7, 8]).iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn reports_gaps_then_late_frames_as_out_of_order() {
+        assert_eq!(
+            track_all(&[0, 1, 4, 2, 3, 5, 9]),
+            [
+                None,
+                None,
+                Some((SequenceEvent::Gap, 2)),
+                Some((SequenceEvent::OutOfOrder, 1)),
+                Some((SequenceEvent::OutOfOrder, 1)),
+                None,
+                Some((SequenceEvent::Gap, 3)),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_duplicates_in_and_out_of_order() {
+        assert_eq!(
+            track_all(&[0, 0, 1, 3, 2, 2, 3, 1]),
+            [
+                None,
+                Some((SequenceEvent::Duplicate, 1)),
+                None,
+                Some((SequenceEvent::Gap, 1)),
+                Some((SequenceEvent::OutOfOrder, 1)),
+                Some((SequenceEvent::Duplicate, 1)),
+                Some((SequenceEvent::Duplicate, 1)),
+                Some((SequenceEvent::Duplicate, 1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn forgets_identifiers_behind_the_duplicates_window() {
+        let far = DUPLICATES_WINDOW + 10;
+        assert_eq!(
+            track_all(&[0, 20, far, 20, 0]),
+            [
+                None,
+                Some((SequenceEvent::Gap, 19)),
+                Some((SequenceEvent::Gap, far - 21)),
+                Some((SequenceEvent::Duplicate, 1)),
+                // Too old to tell from a late frame
+                Some((SequenceEvent::OutOfOrder, 1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn logs_every_event() {
+        let directory = TempDir::new("sequence-log");
+        let path = directory.path("sequence.csv");
+        let mut tracker = SequenceTracker::new(&path).unwrap();
+
+        for id in [0, 3, 1, 1] {
+            if let Some((event, count)) = tracker.track(id) {
+                tracker.log(id, event, count).unwrap();
+            }
+        }
+
+        let content = fs::read_to_string(&path).unwrap();
+        let rows: Vec<Vec<&str>> = content
+            .lines()
+            .skip(1)
+            .map(|line| line.split(',').skip(1).collect())
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                ["3", "gap", "2"],
+                ["1", "out_of_order", "1"],
+                ["1", "duplicate", "1"],
+            ]
+        );
+    }
+}
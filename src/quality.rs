@@ -0,0 +1,259 @@
+use std::io::{Error, ErrorKind};
+
+use serde::Serialize;
+
+// Identical frames have an infinite PSNR, reported as this value instead
+pub const MAX_PSNR: f64 = 100.0;
+
+const SSIM_WINDOW: usize = 11;
+const SSIM_SIGMA: f64 = 1.5;
+const SSIM_C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+const SSIM_C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+
+// Weights of each scale from Wang et al., "Multi-scale structural similarity for image quality
+// assessment"
+const MS_SSIM_WEIGHTS: [f64; 5] = [0.0448, 0.2856, 0.3001, 0.2363, 0.1333];
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug, Default)]
+pub struct FrameQuality {
+    pub psnr_y: f64,
+    pub psnr_yuv: f64,
+    pub ssim: f64,
+    pub ms_ssim: f64,
+}
+
+impl FrameQuality {
+    // SSIM needs at least one full window
+    pub fn check_resolution(width: usize, height: usize) -> std::io::Result<()> {
+        if width < SSIM_WINDOW || height < SSIM_WINDOW {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "{}x{} is below the {}x{} SSIM window",
+                    width, height, SSIM_WINDOW, SSIM_WINDOW
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+
+    // Both frames are RGBA with the same resolution
+    pub fn compare(
+        reference: &[u8],
+        distorted: &[u8],
+        width: usize,
+        height: usize,
+    ) -> std::io::Result<Self> {
+        Self::check_resolution(width, height)?;
+
+        let reference = YuvFrame::from_rgba(reference, width, height);
+        let distorted = YuvFrame::from_rgba(distorted, width, height);
+
+        let psnr_y = psnr(&reference.y, &distorted.y);
+        let psnr_u = psnr(&reference.u, &distorted.u);
+        let psnr_v = psnr(&reference.v, &distorted.v);
+
+        Ok(Self {
+            psnr_y,
+            // Luma weighted 6:1:1 against chroma, as in the JCT-VC common test conditions
+            psnr_yuv: (6.0 * psnr_y + psnr_u + psnr_v) / 8.0,
+            ssim: ssim(&reference.y, &distorted.y).0,
+            ms_ssim: ms_ssim(&reference.y, &distorted.y),
+        })
+    }
+}
+
+#[derive(Clone)]
+struct Plane {
+    width: usize,
+    height: usize,
+    samples: Vec<f64>,
+}
+
+impl Plane {
+    fn downsample(&self) -> Self {
+        let (width, height) = (self.width / 2, self.height / 2);
+        let mut samples = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let index = 2 * y * self.width + 2 * x;
+                let sum = self.samples[index]
+                    + self.samples[index + 1]
+                    + self.samples[index + self.width]
+                    + self.samples[index + self.width + 1];
+                samples.push(sum / 4.0);
+            }
+        }
+
+        Self {
+            width,
+            height,
+            samples,
+        }
+    }
+
+    fn map2(&self, other: &Plane, operation: impl Fn(f64, f64) -> f64) -> Self {
+        Self {
+            width: self.width,
+            height: self.height,
+            samples: self
+                .samples
+                .iter()
+                .zip(&other.samples)
+                .map(|(a, b)| operation(*a, *b))
+                .collect(),
+        }
+    }
+
+    // Gaussian weighted means over every full window, shrinking the plane by the window size
+    fn filter(&self, kernel: &[f64]) -> Self {
+        let width = self.width + 1 - kernel.len();
+        let height = self.height + 1 - kernel.len();
+
+        let mut horizontal = Vec::with_capacity(width * self.height);
+        for y in 0..self.height {
+            let row = &self.samples[y * self.width..(y + 1) * self.width];
+            for x in 0..width {
+                horizontal.push(weighted_sum(&row[x..], kernel));
+            }
+        }
+
+        let mut samples = Vec::with_capacity(width * height);
+        let mut column = vec![0.0; kernel.len()];
+        for y in 0..height {
+            for x in 0..width {
+                for (offset, value) in column.iter_mut().enumerate() {
+                    *value = horizontal[(y + offset) * width + x];
+                }
+                samples.push(weighted_sum(&column, kernel));
+            }
+        }
+
+        Self {
+            width,
+            height,
+            samples,
+        }
+    }
+}
+
+fn weighted_sum(values: &[f64], kernel: &[f64]) -> f64 {
+    values.iter().zip(kernel).map(|(value, weight)| value * weight).sum()
+}
+
+struct YuvFrame {
+    y: Plane,
+    u: Plane,
+    v: Plane,
+}
+
+impl YuvFrame {
+    // Full-range BT.601, without chroma subsampling
+    fn from_rgba(pixels: &[u8], width: usize, height: usize) -> Self {
+        let mut planes = [(); 3].map(|_| Vec::with_capacity(width * height));
+        for pixel in pixels.chunks_exact(4) {
+            let (r, g, b) = (pixel[0] as f64, pixel[1] as f64, pixel[2] as f64);
+            planes[0].push(0.299 * r + 0.587 * g + 0.114 * b);
+            planes[1].push(-0.168_736 * r - 0.331_264 * g + 0.5 * b + 128.0);
+            planes[2].push(0.5 * r - 0.418_688 * g - 0.081_312 * b + 128.0);
+        }
+
+        let [y, u, v] = planes.map(|samples| Plane {
+            width,
+            height,
+            samples,
+        });
+
+        Self { y, u, v }
+    }
+}
+
+fn psnr(reference: &Plane, distorted: &Plane) -> f64 {
+    let squared_error = reference
+        .map2(distorted, |a, b| (a - b) * (a - b))
+        .samples
+        .iter()
+        .sum::<f64>();
+    let mse = squared_error / reference.samples.len() as f64;
+
+    if mse == 0.0 {
+        MAX_PSNR
+    } else {
+        (10.0 * (255.0 * 255.0 / mse).log10()).min(MAX_PSNR)
+    }
+}
+
+fn gaussian_kernel() -> Vec<f64> {
+    let center = (SSIM_WINDOW / 2) as f64;
+    let kernel: Vec<f64> = (0..SSIM_WINDOW)
+        .map(|index| {
+            let distance = index as f64 - center;
+            (-distance * distance / (2.0 * SSIM_SIGMA * SSIM_SIGMA)).exp()
+        })
+        .collect();
+
+    let sum: f64 = kernel.iter().sum();
+    kernel.into_iter().map(|weight| weight / sum).collect()
+}
+
+// Mean SSIM and mean contrast-structure term of the luma planes
+fn ssim(reference: &Plane, distorted: &Plane) -> (f64, f64) {
+    let kernel = gaussian_kernel();
+
+    let mean_x = reference.filter(&kernel);
+    let mean_y = distorted.filter(&kernel);
+    let mean_xx = reference.map2(reference, |a, b| a * b).filter(&kernel);
+    let mean_yy = distorted.map2(distorted, |a, b| a * b).filter(&kernel);
+    let mean_xy = reference.map2(distorted, |a, b| a * b).filter(&kernel);
+
+    let (mut ssim_sum, mut cs_sum) = (0.0, 0.0);
+    for index in 0..mean_x.samples.len() {
+        let (mu_x, mu_y) = (mean_x.samples[index], mean_y.samples[index]);
+        let sigma_xx = mean_xx.samples[index] - mu_x * mu_x;
+        let sigma_yy = mean_yy.samples[index] - mu_y * mu_y;
+        let sigma_xy = mean_xy.samples[index] - mu_x * mu_y;
+
+        let luminance = (2.0 * mu_x * mu_y + SSIM_C1) / (mu_x * mu_x + mu_y * mu_y + SSIM_C1);
+        let contrast_structure = (2.0 * sigma_xy + SSIM_C2) / (sigma_xx + sigma_yy + SSIM_C2);
+
+        ssim_sum += luminance * contrast_structure;
+        cs_sum += contrast_structure;
+    }
+
+    let count = mean_x.samples.len() as f64;
+    (ssim_sum / count, cs_sum / count)
+}
+
+// Scales that do not fit the SSIM window are left out, renormalizing the remaining weights
+fn ms_ssim(reference: &Plane, distorted: &Plane) -> f64 {
+    let mut scales = 1;
+    while scales < MS_SSIM_WEIGHTS.len()
+        && (reference.width >> scales).min(reference.height >> scales) >= SSIM_WINDOW
+    {
+        scales += 1;
+    }
+
+    let weights = &MS_SSIM_WEIGHTS[..scales];
+    let weights_sum: f64 = weights.iter().sum();
+
+    let (mut reference, mut distorted) = (reference.clone(), distorted.clone());
+    let mut score = 1.0;
+    for (scale, weight) in weights.iter().enumerate() {
+        let (ssim, contrast_structure) = ssim(&reference, &distorted);
+        let weight = weight / weights_sum;
+
+        // Only the coarsest scale contributes its luminance term
+        let term = if scale == scales - 1 {
+            ssim
+        } else {
+            contrast_structure
+        };
+        score *= term.max(0.0).powf(weight);
+
+        reference = reference.downsample();
+        distorted = distorted.downsample();
+    }
+
+    score
+}
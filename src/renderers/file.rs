@@ -0,0 +1,40 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use async_trait::async_trait;
+use log::error;
+use remotia::{traits::FrameProcessor, types::FrameData};
+
+// Appends every rendered frame to a raw RGBA stream, e.g. for `ffplay -f rawvideo -pixel_format rgba`
+pub struct FileRenderer {
+    frame_size: usize,
+    writer: BufWriter<File>,
+}
+
+impl FileRenderer {
+    pub fn new(path: &Path, width: usize, height: usize) -> std::io::Result<Self> {
+        Ok(Self {
+            frame_size: width * height * 4,
+            writer: BufWriter::new(File::create(path)?),
+        })
+    }
+}
+
+#[async_trait]
+impl FrameProcessor for FileRenderer {
+    async fn process(&mut self, mut frame_data: FrameData) -> Option<FrameData> {
+        let buffer = frame_data
+            .get_writable_buffer_ref("raw_frame_buffer")
+            .unwrap();
+
+        let length = self.frame_size.min(buffer.len());
+        if let Err(err) = self.writer.write_all(&buffer[..length]) {
+            error!("Unable to write the rendered frame: {}", err);
+        }
+
+        Some(frame_data)
+    }
+}
@@ -0,0 +1,29 @@
+use async_trait::async_trait;
+use remotia::{traits::FrameProcessor, types::FrameData};
+
+// Stands in for a windowed renderer: the frame is copied to a private surface and discarded
+pub struct NullRenderer {
+    surface: Vec<u8>,
+}
+
+impl NullRenderer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            surface: vec![0; width * height * 4],
+        }
+    }
+}
+
+#[async_trait]
+impl FrameProcessor for NullRenderer {
+    async fn process(&mut self, mut frame_data: FrameData) -> Option<FrameData> {
+        let buffer = frame_data
+            .get_writable_buffer_ref("raw_frame_buffer")
+            .unwrap();
+
+        let length = self.surface.len().min(buffer.len());
+        self.surface[..length].copy_from_slice(&buffer[..length]);
+
+        Some(frame_data)
+    }
+}
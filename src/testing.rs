@@ -0,0 +1,45 @@
+use std::{
+    fs,
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+static NEXT_DIRECTORY: AtomicUsize = AtomicUsize::new(0);
+
+// Directory unique to a test, the tests running in parallel, removed with its files when dropped
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    pub fn new(test: &str) -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "paper-experiments-{}-{}-{}",
+            test,
+            std::process::id(),
+            NEXT_DIRECTORY.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&path).unwrap();
+
+        Self { path }
+    }
+
+    // Path of a file the test creates itself
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.path.join(name)
+    }
+
+    pub fn write(&self, name: &str, content: &str) -> PathBuf {
+        let path = self.path(name);
+        fs::write(&path, content).unwrap();
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        if let Err(err) = fs::remove_dir_all(&self.path) {
+            eprintln!("Unable to remove {:?}: {}", self.path, err);
+        }
+    }
+}
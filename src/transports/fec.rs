@@ -0,0 +1,300 @@
+use std::io::{Error, ErrorKind};
+
+use reed_solomon_erasure::galois_8::ReedSolomon;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum FecScheme {
+    None,
+    // One parity packet per group, recovering a single loss
+    Xor,
+    // `repair_packets` per group, recovering as many losses
+    ReedSolomon,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct FecSettings {
+    pub scheme: FecScheme,
+    // Data packets protected together, the last group of a frame may be shorter
+    pub group_size: usize,
+    // Reed-Solomon only, the overhead being repair_packets / group_size
+    pub repair_packets: usize,
+}
+
+impl Default for FecSettings {
+    fn default() -> Self {
+        Self {
+            scheme: FecScheme::None,
+            group_size: 10,
+            repair_packets: 2,
+        }
+    }
+}
+
+impl FecSettings {
+    pub fn validate(&self) -> std::io::Result<()> {
+        let invalid = |message: &str| Err(Error::new(ErrorKind::InvalidData, message.to_string()));
+
+        if self.group_size == 0 || self.group_size > 255 {
+            return invalid("FEC group_size must be between 1 and 255");
+        }
+
+        if self.scheme == FecScheme::ReedSolomon
+            && (self.repair_packets == 0 || self.group_size + self.repair_packets > 256)
+        {
+            return invalid(
+                "Reed-Solomon repair_packets must be greater than zero, at most 256 shards in total",
+            );
+        }
+
+        Ok(())
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.scheme != FecScheme::None
+    }
+}
+
+// Packets of a group are coded as equally sized shards: their length as a big endian u16, the
+// packet, then zeros
+fn shard(packet: &[u8], shard_size: usize) -> Vec<u8> {
+    let mut shard = Vec::with_capacity(shard_size);
+    shard.extend_from_slice(&(packet.len() as u16).to_be_bytes());
+    shard.extend_from_slice(packet);
+    shard.resize(shard_size, 0);
+    shard
+}
+
+fn unshard(shard: &[u8]) -> Option<Vec<u8>> {
+    let length = u16::from_be_bytes([*shard.first()?, *shard.get(1)?]) as usize;
+    shard.get(2..2 + length).map(<[u8]>::to_vec)
+}
+
+fn xor_into(target: &mut [u8], source: &[u8]) {
+    for (target, source) in target.iter_mut().zip(source) {
+        *target ^= source;
+    }
+}
+
+// Repair packets protecting a group of data packets, all of the same size
+pub fn encode(settings: &FecSettings, packets: &[&[u8]]) -> Vec<Vec<u8>> {
+    let shard_size = 2 + packets.iter().map(|packet| packet.len()).max().unwrap_or(0);
+    let mut shards: Vec<Vec<u8>> = packets
+        .iter()
+        .map(|packet| shard(packet, shard_size))
+        .collect();
+
+    match settings.scheme {
+        FecScheme::None => Vec::new(),
+        FecScheme::Xor => {
+            let mut parity = vec![0; shard_size];
+            for shard in &shards {
+                xor_into(&mut parity, shard);
+            }
+            vec![parity]
+        }
+        FecScheme::ReedSolomon => {
+            let coder = ReedSolomon::new(shards.len(), settings.repair_packets).unwrap();
+            shards.resize(shards.len() + settings.repair_packets, vec![0; shard_size]);
+            coder.encode(&mut shards).unwrap();
+            shards.split_off(packets.len())
+        }
+    }
+}
+
+// Fills the missing data packets of a group from the received ones and its repair packets,
+// returning how many were recovered. Nothing is recovered when too many packets are missing.
+pub fn recover(
+    scheme: FecScheme,
+    packets: &mut [Option<Vec<u8>>],
+    repairs: &[Option<Vec<u8>>],
+) -> usize {
+    let missing = packets.iter().filter(|packet| packet.is_none()).count();
+    let received_repairs = repairs.iter().flatten().count();
+    if missing == 0 || missing > received_repairs {
+        return 0;
+    }
+
+    let shard_size = match repairs.iter().flatten().next() {
+        Some(repair) => repair.len(),
+        None => return 0,
+    };
+    if packets
+        .iter()
+        .flatten()
+        .any(|packet| packet.len() + 2 > shard_size)
+    {
+        return 0;
+    }
+
+    let recovered: Vec<Option<Vec<u8>>> = match scheme {
+        FecScheme::None => return 0,
+        FecScheme::Xor => {
+            let mut parity = repairs.iter().flatten().next().unwrap().clone();
+            for packet in packets.iter().flatten() {
+                xor_into(&mut parity, &shard(packet, shard_size));
+            }
+            packets
+                .iter()
+                .map(|packet| match packet {
+                    Some(_) => None,
+                    None => Some(parity.clone()),
+                })
+                .collect()
+        }
+        FecScheme::ReedSolomon => {
+            let coder = match ReedSolomon::new(packets.len(), repairs.len()) {
+                Ok(coder) => coder,
+                Err(_) => return 0,
+            };
+
+            let mut shards: Vec<Option<Vec<u8>>> = packets
+                .iter()
+                .map(|packet| packet.as_ref().map(|packet| shard(packet, shard_size)))
+                .chain(repairs.iter().cloned())
+                .collect();
+            if shards
+                .iter()
+                .flatten()
+                .any(|shard| shard.len() != shard_size)
+                || coder.reconstruct_data(&mut shards).is_err()
+            {
+                return 0;
+            }
+
+            shards.truncate(packets.len());
+            shards
+        }
+    };
+
+    let mut count = 0;
+    for (packet, shard) in packets.iter_mut().zip(recovered) {
+        if packet.is_none() {
+            if let Some(recovered) = shard.as_deref().and_then(unshard) {
+                *packet = Some(recovered);
+                count += 1;
+            }
+        }
+    }
+
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packets() -> Vec<Vec<u8>> {
+        (0..5u8)
+            .map(|index| {
+                (0..100 + index as usize * 7)
+                    .map(|byte| byte as u8 ^ index)
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn settings(scheme: FecScheme, repair_packets: usize) -> FecSettings {
+        FecSettings {
+            scheme,
+            group_size: 5,
+            repair_packets,
+        }
+    }
+
+    fn protect(settings: &FecSettings, packets: &[Vec<u8>]) -> Vec<Option<Vec<u8>>> {
+        let packets: Vec<&[u8]> = packets.iter().map(Vec::as_slice).collect();
+        encode(settings, &packets).into_iter().map(Some).collect()
+    }
+
+    fn lose(packets: &[Vec<u8>], lost: &[usize]) -> Vec<Option<Vec<u8>>> {
+        packets
+            .iter()
+            .enumerate()
+            .map(|(index, packet)| (!lost.contains(&index)).then(|| packet.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn no_repairs_without_fec() {
+        let packets = packets();
+        assert!(protect(&settings(FecScheme::None, 2), &packets).is_empty());
+    }
+
+    #[test]
+    fn xor_recovers_a_single_loss() {
+        let packets = packets();
+        let repairs = protect(&settings(FecScheme::Xor, 2), &packets);
+        assert_eq!(repairs.len(), 1);
+
+        let mut received = lose(&packets, &[3]);
+        assert_eq!(recover(FecScheme::Xor, &mut received, &repairs), 1);
+        assert_eq!(received, lose(&packets, &[]));
+    }
+
+    #[test]
+    fn xor_gives_up_on_two_losses() {
+        let packets = packets();
+        let repairs = protect(&settings(FecScheme::Xor, 2), &packets);
+
+        let mut received = lose(&packets, &[0, 4]);
+        assert_eq!(recover(FecScheme::Xor, &mut received, &repairs), 0);
+        assert_eq!(received, lose(&packets, &[0, 4]));
+    }
+
+    #[test]
+    fn reed_solomon_recovers_as_many_losses_as_repairs() {
+        let packets = packets();
+        let repairs = protect(&settings(FecScheme::ReedSolomon, 2), &packets);
+        assert_eq!(repairs.len(), 2);
+
+        let mut received = lose(&packets, &[1, 4]);
+        assert_eq!(recover(FecScheme::ReedSolomon, &mut received, &repairs), 2);
+        assert_eq!(received, lose(&packets, &[]));
+    }
+
+    #[test]
+    fn reed_solomon_recovers_with_a_lost_repair() {
+        let packets = packets();
+        let mut repairs = protect(&settings(FecScheme::ReedSolomon, 2), &packets);
+        repairs[0] = None;
+
+        let mut received = lose(&packets, &[2]);
+        assert_eq!(recover(FecScheme::ReedSolomon, &mut received, &repairs), 1);
+        assert_eq!(received, lose(&packets, &[]));
+    }
+
+    #[test]
+    fn reed_solomon_gives_up_on_more_losses_than_repairs() {
+        let packets = packets();
+        let mut repairs = protect(&settings(FecScheme::ReedSolomon, 2), &packets);
+        repairs[1] = None;
+
+        let mut received = lose(&packets, &[0, 3]);
+        assert_eq!(recover(FecScheme::ReedSolomon, &mut received, &repairs), 0);
+        assert_eq!(received, lose(&packets, &[0, 3]));
+    }
+
+    #[test]
+    fn nothing_to_recover_without_losses() {
+        let packets = packets();
+        let repairs = protect(&settings(FecScheme::Xor, 2), &packets);
+
+        let mut received = lose(&packets, &[]);
+        assert_eq!(recover(FecScheme::Xor, &mut received, &repairs), 0);
+    }
+
+    #[test]
+    fn rejects_invalid_settings() {
+        assert!(settings(FecScheme::Xor, 2).validate().is_ok());
+        assert!(FecSettings {
+            group_size: 0,
+            ..settings(FecScheme::Xor, 2)
+        }
+        .validate()
+        .is_err());
+        assert!(settings(FecScheme::ReedSolomon, 0).validate().is_err());
+    }
+}
@@ -0,0 +1,210 @@
+pub mod fec;
+pub mod nack;
+pub mod quic;
+pub mod rtp;
+pub mod tcp;
+pub mod udp;
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use remotia::{traits::FrameProcessor, types::FrameData};
+use remotia_srt::{receiver::SRTFrameReceiver, sender::SRTFrameSender};
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+
+use crate::{clock::ClockEstimate, time::now_timestamp};
+
+use self::{
+    fec::FecSettings,
+    nack::NackPolicy,
+    quic::{QuicFrameReceiver, QuicFrameSender},
+    rtp::{RtpFrameReceiver, RtpFrameSender},
+    tcp::{TcpFrameReceiver, TcpFrameSender},
+    udp::{UdpFrameReceiver, UdpFrameSender},
+};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum TransportKind {
+    Srt,
+    Udp,
+    Tcp,
+    Quic,
+    // H.264 only
+    Rtp,
+}
+
+impl TransportKind {
+    // TCP cannot go through the UDP relay emulating the network
+    pub fn is_datagram_based(&self) -> bool {
+        *self != Self::Tcp
+    }
+}
+
+// Stats travelling with the encoded frame, in this order
+const HEADER_STATS: [&str; 4] = [
+    "frame_id",
+    "capture_timestamp",
+    "encoded_size",
+    "encoded_frame_id",
+];
+const HEADER_SIZE: usize = HEADER_STATS.len() * 8;
+
+// Header followed by the first encoded_size bytes of encoded_frame_buffer
+fn serialize_frame(frame_data: &mut FrameData) -> Vec<u8> {
+    let encoded_size = frame_data.get("encoded_size") as usize;
+
+    let mut message = Vec::with_capacity(HEADER_SIZE + encoded_size);
+    for stat in HEADER_STATS {
+        message.extend_from_slice(&(frame_data.get(stat) as u64).to_le_bytes());
+    }
+
+    let encoded = frame_data
+        .get_writable_buffer_ref("encoded_frame_buffer")
+        .unwrap();
+    message.extend_from_slice(&encoded[..encoded_size]);
+
+    message
+}
+
+// Fills encoded_frame_buffer and the header stats. Fails when the message is malformed or does
+// not fit the buffer.
+fn deserialize_frame(message: &[u8], frame_data: &mut FrameData) -> Result<(), String> {
+    if message.len() < HEADER_SIZE {
+        return Err(format!(
+            "{} bytes message is shorter than the header",
+            message.len()
+        ));
+    }
+
+    let (header, encoded) = message.split_at(HEADER_SIZE);
+    let header: Vec<u128> = header
+        .chunks_exact(8)
+        .map(|value| u64::from_le_bytes(value.try_into().unwrap()) as u128)
+        .collect();
+
+    let encoded_size = header[2] as usize;
+    if encoded_size != encoded.len() {
+        return Err(format!(
+            "{} encoded bytes announced, {} received",
+            encoded_size,
+            encoded.len()
+        ));
+    }
+
+    store_frame(frame_data, header[0], header[3], header[1], encoded)
+}
+
+// Sets the stats a received frame carries, and reception_delay as the SRT receiver does
+fn store_frame(
+    frame_data: &mut FrameData,
+    frame_id: u128,
+    encoded_frame_id: u128,
+    capture_timestamp: u128,
+    encoded: &[u8],
+) -> Result<(), String> {
+    let buffer = frame_data
+        .get_writable_buffer_ref("encoded_frame_buffer")
+        .unwrap();
+    if encoded.len() > buffer.len() {
+        return Err(format!("{} bytes frame exceeds the buffer", encoded.len()));
+    }
+    buffer[..encoded.len()].copy_from_slice(encoded);
+
+    frame_data.set("frame_id", frame_id);
+    frame_data.set("encoded_frame_id", encoded_frame_id);
+    frame_data.set("capture_timestamp", capture_timestamp);
+    frame_data.set("encoded_size", encoded.len() as u128);
+    frame_data.set(
+        "reception_delay",
+        now_timestamp().saturating_sub(capture_timestamp),
+    );
+
+    Ok(())
+}
+
+// Server side of the transport selected at runtime
+pub enum FrameSender {
+    Srt(SRTFrameSender),
+    Udp(UdpFrameSender),
+    Tcp(TcpFrameSender),
+    Quic(QuicFrameSender),
+    Rtp(RtpFrameSender),
+}
+
+impl FrameSender {
+    // `latency` is only used by SRT, `rtp_destination` by RTP, `fec` and `nack` by UDP
+    pub async fn bind(
+        kind: TransportKind,
+        port: u16,
+        latency: Duration,
+        rtp_destination: Option<&str>,
+        fec: &FecSettings,
+        nack: Option<NackPolicy>,
+    ) -> std::io::Result<Self> {
+        Ok(match kind {
+            TransportKind::Srt => Self::Srt(SRTFrameSender::new(port, latency).await),
+            TransportKind::Udp => Self::Udp(UdpFrameSender::bind(port, fec.clone(), nack).await?),
+            TransportKind::Tcp => Self::Tcp(TcpFrameSender::bind(port).await?),
+            TransportKind::Quic => Self::Quic(QuicFrameSender::bind(port)?),
+            TransportKind::Rtp => Self::Rtp(RtpFrameSender::bind(port, rtp_destination).await?),
+        })
+    }
+}
+
+#[async_trait]
+impl FrameProcessor for FrameSender {
+    async fn process(&mut self, frame_data: FrameData) -> Option<FrameData> {
+        match self {
+            Self::Srt(sender) => sender.process(frame_data).await,
+            Self::Udp(sender) => sender.process(frame_data).await,
+            Self::Tcp(sender) => sender.process(frame_data).await,
+            Self::Quic(sender) => sender.process(frame_data).await,
+            Self::Rtp(sender) => sender.process(frame_data).await,
+        }
+    }
+}
+
+// Client side of the transport selected at runtime
+pub enum FrameReceiver {
+    Srt(SRTFrameReceiver),
+    Udp(UdpFrameReceiver),
+    Tcp(TcpFrameReceiver),
+    Quic(QuicFrameReceiver),
+    Rtp(RtpFrameReceiver),
+}
+
+impl FrameReceiver {
+    // `latency` is only used by SRT, `nack` and `estimates` by UDP
+    pub async fn connect(
+        kind: TransportKind,
+        address: &str,
+        latency: Duration,
+        nack: Option<NackPolicy>,
+        estimates: Option<watch::Receiver<Option<ClockEstimate>>>,
+    ) -> std::io::Result<Self> {
+        Ok(match kind {
+            TransportKind::Srt => Self::Srt(SRTFrameReceiver::new(address, latency).await),
+            TransportKind::Udp => {
+                Self::Udp(UdpFrameReceiver::connect(address, nack, estimates).await?)
+            }
+            TransportKind::Tcp => Self::Tcp(TcpFrameReceiver::new(address)),
+            TransportKind::Quic => Self::Quic(QuicFrameReceiver::new(address)?),
+            TransportKind::Rtp => Self::Rtp(RtpFrameReceiver::connect(address).await?),
+        })
+    }
+}
+
+#[async_trait]
+impl FrameProcessor for FrameReceiver {
+    async fn process(&mut self, frame_data: FrameData) -> Option<FrameData> {
+        match self {
+            Self::Srt(receiver) => receiver.process(frame_data).await,
+            Self::Udp(receiver) => receiver.process(frame_data).await,
+            Self::Tcp(receiver) => receiver.process(frame_data).await,
+            Self::Quic(receiver) => receiver.process(frame_data).await,
+            Self::Rtp(receiver) => receiver.process(frame_data).await,
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use std::{
+    io::{Error, ErrorKind},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct NackSettings {
+    pub enabled: bool,
+    // Milliseconds after capture past which a frame is no longer worth retransmitting, defaulting
+    // to the pre_render_delay threshold of the client
+    pub deadline: Option<u64>,
+    // Milliseconds without news of a missing fragment before it is requested, and again
+    pub interval: u64,
+    // Milliseconds kept before the deadline for a retransmitted fragment to reach the client and
+    // its frame to be decoded, about half the RTT plus the decoding time
+    pub margin: u64,
+}
+
+impl Default for NackSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            deadline: None,
+            interval: 20,
+            margin: 20,
+        }
+    }
+}
+
+impl NackSettings {
+    pub fn validate(&self, pre_render_delay_threshold: u128) -> std::io::Result<()> {
+        let invalid = |message: &str| Err(Error::new(ErrorKind::InvalidData, message.to_string()));
+
+        if self.deadline == Some(0) {
+            return invalid("NACK deadline must be greater than zero");
+        }
+
+        if self.margin >= self.deadline(pre_render_delay_threshold) {
+            return invalid("NACK margin must be shorter than the deadline");
+        }
+
+        if self.interval == 0 {
+            return invalid("NACK interval must be greater than zero");
+        }
+
+        Ok(())
+    }
+
+    pub fn policy(&self, pre_render_delay_threshold: u128) -> Option<NackPolicy> {
+        self.enabled.then(|| NackPolicy {
+            deadline: Duration::from_millis(self.deadline(pre_render_delay_threshold)),
+            interval: Duration::from_millis(self.interval),
+            margin: Duration::from_millis(self.margin),
+        })
+    }
+
+    fn deadline(&self, pre_render_delay_threshold: u128) -> u64 {
+        self.deadline.unwrap_or(pre_render_delay_threshold as u64)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct NackPolicy {
+    pub deadline: Duration,
+    pub interval: Duration,
+    // Only retransmitted while the frame is younger than deadline - margin
+    pub margin: Duration,
+}
+
+// Fragment indices of a frame requested by the receiver, as little endian u16 after the frame_id
+pub fn serialize(packet_type: u8, frame_id: u64, indices: &[u16]) -> Vec<u8> {
+    let mut datagram = Vec::with_capacity(1 + 8 + 2 * indices.len());
+    datagram.push(packet_type);
+    datagram.extend_from_slice(&frame_id.to_le_bytes());
+    for index in indices {
+        datagram.extend_from_slice(&index.to_le_bytes());
+    }
+    datagram
+}
+
+pub fn parse(datagram: &[u8]) -> Option<(u64, Vec<u16>)> {
+    if datagram.len() < 9 || (datagram.len() - 9) % 2 != 0 {
+        return None;
+    }
+
+    let frame_id = u64::from_le_bytes(datagram[1..9].try_into().unwrap());
+    let indices = datagram[9..]
+        .chunks_exact(2)
+        .map(|index| u16::from_le_bytes([index[0], index[1]]))
+        .collect();
+
+    Some((frame_id, indices))
+}
@@ -0,0 +1,269 @@
+use std::{
+    io::{Error, ErrorKind},
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use log::{debug, info};
+use quinn::{ClientConfig, Connection, Endpoint, Incoming, IncomingUniStreams, ServerConfig};
+use remotia::{error::DropReason, traits::FrameProcessor, types::FrameData};
+use rustls::{
+    client::{ServerCertVerified, ServerCertVerifier},
+    Certificate, PrivateKey, ServerName,
+};
+use tokio::{
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    task::JoinHandle,
+};
+
+use super::{deserialize_frame, serialize_frame};
+
+const SERVER_NAME: &str = "localhost";
+const RECONNECT_INTERVAL: Duration = Duration::from_millis(500);
+const MAX_MESSAGE_SIZE: usize = 64 * 1024 * 1024;
+
+fn other_error(err: impl std::fmt::Display) -> Error {
+    Error::new(ErrorKind::Other, err.to_string())
+}
+
+// Generated at every start, the receiver does not verify it
+fn self_signed_config() -> std::io::Result<ServerConfig> {
+    let certificate =
+        rcgen::generate_simple_self_signed(vec![SERVER_NAME.to_string()]).map_err(other_error)?;
+    let key = PrivateKey(certificate.serialize_private_key_der());
+    let certificate = Certificate(certificate.serialize_der().map_err(other_error)?);
+
+    ServerConfig::with_single_cert(vec![certificate], key).map_err(other_error)
+}
+
+// Server side: each frame goes on its own unidirectional stream, so that a frame waiting for a
+// retransmission does not hold back the following ones
+pub struct QuicFrameSender {
+    // Dropping the endpoint would close the connections
+    _endpoint: Endpoint,
+    incoming: Incoming,
+    connection: Option<Connection>,
+}
+
+impl QuicFrameSender {
+    pub fn bind(port: u16) -> std::io::Result<Self> {
+        let address = SocketAddr::from(([0, 0, 0, 0], port));
+        let (endpoint, incoming) = Endpoint::server(self_signed_config()?, address)?;
+        info!("Waiting for a QUIC receiver on port {}", port);
+
+        Ok(Self {
+            _endpoint: endpoint,
+            incoming,
+            connection: None,
+        })
+    }
+
+    async fn send(&mut self, frame_data: &mut FrameData) -> std::io::Result<()> {
+        if self.connection.is_none() {
+            let connecting = self
+                .incoming
+                .next()
+                .await
+                .ok_or_else(|| Error::new(ErrorKind::NotConnected, "QUIC endpoint closed"))?;
+            let connection = connecting.await.map_err(other_error)?.connection;
+            info!("Sending frames to {}", connection.remote_address());
+            self.connection = Some(connection);
+        }
+        let connection = self.connection.as_ref().unwrap();
+
+        let message = serialize_frame(frame_data);
+        let result = async {
+            let mut stream = connection.open_uni().await.map_err(other_error)?;
+            stream.write_all(&message).await.map_err(other_error)?;
+
+            // Finishing completes once the receiver acknowledged the whole frame
+            tokio::spawn(async move {
+                if let Err(err) = stream.finish().await {
+                    debug!("Unable to finish the frame stream: {}", err);
+                }
+            });
+
+            Ok(())
+        }
+        .await;
+
+        if result.is_err() {
+            self.connection = None;
+        }
+
+        result
+    }
+}
+
+#[async_trait]
+impl FrameProcessor for QuicFrameSender {
+    async fn process(&mut self, mut frame_data: FrameData) -> Option<FrameData> {
+        if let Err(err) = self.send(&mut frame_data).await {
+            debug!("Unable to send the frame: {}", err);
+            frame_data.set_drop_reason(Some(DropReason::ConnectionError));
+        }
+
+        Some(frame_data)
+    }
+}
+
+// Only encryption matters between two local processes
+struct SkipServerVerification;
+
+impl ServerCertVerifier for SkipServerVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+// Client side: connects on the first frame and again after any connection error. Every stream is
+// read concurrently and frames are delivered as soon as they are complete, overtaking the ones
+// still waiting for a retransmission.
+pub struct QuicFrameReceiver {
+    address: String,
+    endpoint: Endpoint,
+    reader: Option<StreamReader>,
+}
+
+// Accepts the streams of one connection, reading each on its own task. The channel closes once
+// the connection is lost and every stream already accepted has been read.
+struct StreamReader {
+    messages: UnboundedReceiver<std::io::Result<Vec<u8>>>,
+    task: JoinHandle<()>,
+}
+
+impl StreamReader {
+    fn spawn(streams: IncomingUniStreams) -> Self {
+        let (sender, messages) = mpsc::unbounded_channel();
+        let task = tokio::spawn(accept_streams(streams, sender));
+
+        Self { messages, task }
+    }
+}
+
+impl Drop for StreamReader {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn accept_streams(
+    mut streams: IncomingUniStreams,
+    sender: UnboundedSender<std::io::Result<Vec<u8>>>,
+) {
+    while let Some(stream) = streams.next().await {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                debug!("QUIC connection lost: {}", err);
+                return;
+            }
+        };
+
+        let sender = sender.clone();
+        tokio::spawn(async move {
+            let message = stream
+                .read_to_end(MAX_MESSAGE_SIZE)
+                .await
+                .map_err(other_error);
+            // The receiver may have reconnected in the meantime
+            let _ = sender.send(message);
+        });
+    }
+}
+
+impl QuicFrameReceiver {
+    pub fn new(address: &str) -> std::io::Result<Self> {
+        let crypto = rustls::ClientConfig::builder()
+            .with_safe_defaults()
+            .with_custom_certificate_verifier(Arc::new(SkipServerVerification))
+            .with_no_client_auth();
+
+        let mut endpoint = Endpoint::client(SocketAddr::from(([0, 0, 0, 0], 0)))?;
+        endpoint.set_default_client_config(ClientConfig::new(Arc::new(crypto)));
+
+        Ok(Self {
+            address: address.to_string(),
+            endpoint,
+            reader: None,
+        })
+    }
+
+    async fn try_connect(&self) -> std::io::Result<IncomingUniStreams> {
+        let address = tokio::net::lookup_host(&self.address)
+            .await?
+            .next()
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "unresolved QUIC sender address"))?;
+
+        let connection = self
+            .endpoint
+            .connect(address, SERVER_NAME)
+            .map_err(other_error)?
+            .await
+            .map_err(other_error)?;
+
+        Ok(connection.uni_streams)
+    }
+
+    async fn connect(&self) -> IncomingUniStreams {
+        loop {
+            match self.try_connect().await {
+                Ok(streams) => {
+                    info!("Receiving frames from {}", self.address);
+                    return streams;
+                }
+                Err(err) => {
+                    debug!("QUIC sender at {} not available: {}", self.address, err);
+                    tokio::time::sleep(RECONNECT_INTERVAL).await;
+                }
+            }
+        }
+    }
+
+    // A failed stream only drops its frame, the connection is reopened once it is closed
+    async fn receive(&mut self) -> std::io::Result<Vec<u8>> {
+        if self.reader.is_none() {
+            self.reader = Some(StreamReader::spawn(self.connect().await));
+        }
+        let reader = self.reader.as_mut().unwrap();
+
+        match reader.messages.recv().await {
+            Some(message) => message,
+            None => {
+                self.reader = None;
+                Err(Error::new(
+                    ErrorKind::NotConnected,
+                    "QUIC connection closed",
+                ))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl FrameProcessor for QuicFrameReceiver {
+    async fn process(&mut self, mut frame_data: FrameData) -> Option<FrameData> {
+        let received = match self.receive().await {
+            Ok(message) => deserialize_frame(&message, &mut frame_data),
+            Err(err) => Err(err.to_string()),
+        };
+
+        if let Err(err) = received {
+            debug!("Unable to receive the frame: {}", err);
+            frame_data.set_drop_reason(Some(DropReason::ConnectionError));
+        }
+
+        Some(frame_data)
+    }
+}
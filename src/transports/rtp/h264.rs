@@ -0,0 +1,264 @@
+// H.264 payload format of RFC 6184, in non-interleaved mode (packetization-mode=1)
+
+const START_CODE: [u8; 4] = [0, 0, 0, 1];
+
+const STAP_A: u8 = 24;
+const FU_A: u8 = 28;
+
+const NAL_TYPE_MASK: u8 = 0x1f;
+const FORBIDDEN_AND_NRI_MASK: u8 = 0xe0;
+const FU_START: u8 = 0x80;
+const FU_END: u8 = 0x40;
+
+// NAL units of an Annex B byte stream, without their start codes
+pub fn nal_units(stream: &[u8]) -> Vec<&[u8]> {
+    let mut starts = Vec::new();
+    let mut index = 0;
+    while index + 3 <= stream.len() {
+        if stream[index..index + 3] == [0, 0, 1] {
+            starts.push(index + 3);
+            index += 3;
+        } else {
+            index += 1;
+        }
+    }
+
+    starts
+        .iter()
+        .enumerate()
+        .map(|(position, &start)| {
+            let mut end = match starts.get(position + 1) {
+                Some(next) => next - 3,
+                None => stream.len(),
+            };
+            // Trailing zeros belong to the next four bytes start code
+            while end > start && stream[end - 1] == 0 {
+                end -= 1;
+            }
+            &stream[start..end]
+        })
+        .filter(|nal| !nal.is_empty())
+        .collect()
+}
+
+// RTP payloads carrying the given NAL units in order: runs of small units are aggregated in STAP-A
+// packets, units larger than `max_payload` are split in FU-A fragments
+pub fn packetize(nals: &[&[u8]], max_payload: usize) -> Vec<Vec<u8>> {
+    let mut payloads = Vec::new();
+    let mut aggregated: Vec<&[u8]> = Vec::new();
+
+    for &nal in nals {
+        if nal.len() > max_payload {
+            flush_aggregate(&mut aggregated, &mut payloads);
+            fragment(nal, max_payload, &mut payloads);
+            continue;
+        }
+
+        // STAP-A header, then a 16 bits size before each unit
+        let aggregate_size = 1 + aggregated.iter().map(|nal| 2 + nal.len()).sum::<usize>();
+        if aggregate_size + 2 + nal.len() > max_payload {
+            flush_aggregate(&mut aggregated, &mut payloads);
+        }
+        aggregated.push(nal);
+    }
+    flush_aggregate(&mut aggregated, &mut payloads);
+
+    payloads
+}
+
+fn flush_aggregate(aggregated: &mut Vec<&[u8]>, payloads: &mut Vec<Vec<u8>>) {
+    match aggregated.len() {
+        0 => {}
+        1 => payloads.push(aggregated[0].to_vec()),
+        _ => {
+            // F is the OR of the aggregated units, NRI their maximum
+            let forbidden = aggregated.iter().fold(0, |bit, nal| bit | (nal[0] & 0x80));
+            let nri = aggregated.iter().map(|nal| nal[0] & 0x60).max().unwrap();
+
+            let mut payload = vec![forbidden | nri | STAP_A];
+            for nal in aggregated.iter() {
+                payload.extend_from_slice(&(nal.len() as u16).to_be_bytes());
+                payload.extend_from_slice(nal);
+            }
+            payloads.push(payload);
+        }
+    }
+    aggregated.clear();
+}
+
+fn fragment(nal: &[u8], max_payload: usize, payloads: &mut Vec<Vec<u8>>) {
+    let header = nal[0];
+    let indicator = (header & FORBIDDEN_AND_NRI_MASK) | FU_A;
+    let nal_type = header & NAL_TYPE_MASK;
+
+    // The NAL header is carried by the FU indicator and header instead
+    let chunks: Vec<&[u8]> = nal[1..].chunks(max_payload - 2).collect();
+    let last = chunks.len() - 1;
+
+    for (index, chunk) in chunks.into_iter().enumerate() {
+        let mut fu_header = nal_type;
+        if index == 0 {
+            fu_header |= FU_START;
+        }
+        if index == last {
+            fu_header |= FU_END;
+        }
+
+        let mut payload = Vec::with_capacity(2 + chunk.len());
+        payload.push(indicator);
+        payload.push(fu_header);
+        payload.extend_from_slice(chunk);
+        payloads.push(payload);
+    }
+}
+
+// Appends the NAL units of an RTP payload to an Annex B byte stream. The payloads of a frame must
+// be given in sequence number order.
+pub fn depacketize(payload: &[u8], stream: &mut Vec<u8>) -> Result<(), String> {
+    let header = *payload.first().ok_or("empty payload")?;
+
+    match header & NAL_TYPE_MASK {
+        1..=23 => {
+            stream.extend_from_slice(&START_CODE);
+            stream.extend_from_slice(payload);
+        }
+        STAP_A => {
+            let mut units = &payload[1..];
+            while !units.is_empty() {
+                if units.len() < 2 {
+                    return Err("truncated STAP-A unit size".to_string());
+                }
+                let size = u16::from_be_bytes([units[0], units[1]]) as usize;
+                if units.len() < 2 + size {
+                    return Err(format!("truncated {} bytes STAP-A unit", size));
+                }
+
+                stream.extend_from_slice(&START_CODE);
+                stream.extend_from_slice(&units[2..2 + size]);
+                units = &units[2 + size..];
+            }
+        }
+        FU_A => {
+            let fu_header = *payload.get(1).ok_or("truncated FU-A header")?;
+            if fu_header & FU_START != 0 {
+                stream.extend_from_slice(&START_CODE);
+                stream.push((header & FORBIDDEN_AND_NRI_MASK) | (fu_header & NAL_TYPE_MASK));
+            }
+            stream.extend_from_slice(&payload[2..]);
+        }
+        nal_type => return Err(format!("unsupported NAL unit type {}", nal_type)),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // NAL unit of the given type with NRI 3, without zero bytes
+    fn nal(nal_type: u8, size: usize) -> Vec<u8> {
+        let mut nal = vec![0x60 | nal_type];
+        nal.extend((1..size).map(|index| (index % 251) as u8 + 1));
+        nal
+    }
+
+    fn annex_b(nals: &[Vec<u8>]) -> Vec<u8> {
+        nals.iter()
+            .flat_map(|nal| START_CODE.iter().chain(nal.iter()).copied())
+            .collect()
+    }
+
+    fn depacketize_all(payloads: &[Vec<u8>]) -> Vec<u8> {
+        let mut stream = Vec::new();
+        for payload in payloads {
+            depacketize(payload, &mut stream).unwrap();
+        }
+        stream
+    }
+
+    #[test]
+    fn splits_an_annex_b_stream() {
+        let nals = vec![nal(7, 10), nal(8, 4), nal(5, 50)];
+        let mut stream = annex_b(&nals);
+        // Three bytes start code before the last unit
+        stream.remove(4 + 10 + 4 + 4);
+
+        let split: Vec<Vec<u8>> = nal_units(&stream).into_iter().map(<[u8]>::to_vec).collect();
+        assert_eq!(split, nals);
+    }
+
+    #[test]
+    fn sends_a_small_unit_as_is() {
+        let nals = vec![nal(5, 100)];
+        let payloads = packetize(&[&nals[0]], 1200);
+
+        assert_eq!(payloads, nals);
+        assert_eq!(depacketize_all(&payloads), annex_b(&nals));
+    }
+
+    #[test]
+    fn aggregates_small_units_in_stap_a() {
+        let nals = vec![nal(7, 10), nal(8, 4), nal(5, 300)];
+        let units: Vec<&[u8]> = nals.iter().map(Vec::as_slice).collect();
+        let payloads = packetize(&units, 1200);
+
+        assert_eq!(payloads.len(), 1);
+        assert_eq!(payloads[0][0] & NAL_TYPE_MASK, STAP_A);
+        assert_eq!(payloads[0][0] & 0x60, 0x60);
+        assert_eq!(depacketize_all(&payloads), annex_b(&nals));
+    }
+
+    #[test]
+    fn fragments_large_units_in_fu_a() {
+        let nals = vec![nal(7, 10), nal(5, 3000), nal(1, 20)];
+        let units: Vec<&[u8]> = nals.iter().map(Vec::as_slice).collect();
+        let payloads = packetize(&units, 1200);
+
+        // The SPS alone, three fragments, then the last slice alone
+        assert_eq!(payloads.len(), 5);
+        assert!(payloads.iter().all(|payload| payload.len() <= 1200));
+
+        let fragments = &payloads[1..4];
+        assert!(fragments
+            .iter()
+            .all(|payload| payload[0] & NAL_TYPE_MASK == FU_A && payload[1] & NAL_TYPE_MASK == 5));
+        assert_eq!(fragments[0][1] & (FU_START | FU_END), FU_START);
+        assert_eq!(fragments[1][1] & (FU_START | FU_END), 0);
+        assert_eq!(fragments[2][1] & (FU_START | FU_END), FU_END);
+
+        assert_eq!(depacketize_all(&payloads), annex_b(&nals));
+    }
+
+    #[test]
+    fn reordered_fragments_corrupt_the_unit() {
+        let nals = vec![nal(5, 3000)];
+        let mut payloads = packetize(&[&nals[0]], 1200);
+        payloads.swap(1, 2);
+
+        assert_ne!(depacketize_all(&payloads), annex_b(&nals));
+    }
+
+    #[test]
+    fn lost_fragment_truncates_the_unit() {
+        let nals = vec![nal(5, 3000)];
+        let mut payloads = packetize(&[&nals[0]], 1200);
+        let lost = payloads.remove(1);
+
+        assert_eq!(
+            depacketize_all(&payloads).len(),
+            annex_b(&nals).len() - (lost.len() - 2)
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_payloads() {
+        let mut stream = Vec::new();
+        assert!(depacketize(&[], &mut stream).is_err());
+        // STAP-A announcing more bytes than it carries
+        assert!(depacketize(&[STAP_A, 0, 10, 0x65, 1], &mut stream).is_err());
+        assert!(depacketize(&[FU_A], &mut stream).is_err());
+        // STAP-B is not part of the non-interleaved mode
+        assert!(depacketize(&[25, 0, 1, 0x65], &mut stream).is_err());
+    }
+}
@@ -0,0 +1,888 @@
+pub mod h264;
+pub mod rtcp;
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{Error, ErrorKind},
+    net::SocketAddr,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use log::{debug, info};
+use remotia::{error::DropReason, traits::FrameProcessor, types::FrameData};
+use tokio::net::UdpSocket;
+
+use self::rtcp::{ReportBlock, RtcpPacket, SenderReport};
+use super::store_frame;
+use crate::time::now_timestamp;
+
+const VERSION: u8 = 2;
+const PAYLOAD_TYPE: u8 = 96;
+const CLOCK_RATE: u32 = 90_000;
+
+// Keeps every datagram below the usual 1500 bytes MTU
+const MAX_PAYLOAD: usize = 1200;
+const MAX_DATAGRAM_SIZE: usize = 65536;
+
+const CNAME: &str = "paper-experiments";
+const RTCP_INTERVAL: Duration = Duration::from_secs(1);
+
+// The receiver reports early when nothing arrived for this long, doubling as a hello towards a
+// sender that did not learn its address yet
+const IDLE_REPORT_INTERVAL: Duration = Duration::from_millis(500);
+
+// Frames still missing packets beyond this many are given up
+const MAX_PENDING_FRAMES: usize = 64;
+
+// One-byte header extension of RFC 8285, identifying the frame a packet belongs to
+const ONE_BYTE_EXTENSION_PROFILE: u16 = 0xbede;
+const FRAME_ID_EXTENSION: u8 = 1;
+const CAPTURE_TIMESTAMP_EXTENSION: u8 = 2;
+const FIRST_SEQUENCE_EXTENSION: u8 = 3;
+const ENCODED_FRAME_ID_EXTENSION: u8 = 4;
+
+// Packets sent for the frame, and the last receiver report: cumulative lost packets, jitter and
+// round trip time in microseconds
+pub const RTP_SENDER_STATS: [&str; 4] = [
+    "rtp_packets",
+    "rtcp_lost_packets",
+    "rtcp_jitter",
+    "rtcp_round_trip_time",
+];
+
+// Packets received for the frame, cumulative lost packets and jitter in microseconds
+pub const RTP_RECEIVER_STATS: [&str; 3] = ["rtp_packets", "rtp_lost_packets", "rtp_jitter"];
+
+fn rtp_timestamp(capture_timestamp: u128) -> u32 {
+    (capture_timestamp * (CLOCK_RATE / 1000) as u128) as u32
+}
+
+fn jitter_to_micros(jitter: u32) -> u128 {
+    jitter as u128 * 1_000_000 / CLOCK_RATE as u128
+}
+
+// SDP of the stream sent to `destination`, for players such as ffplay or GStreamer
+pub fn write_session_description(path: &Path, destination: SocketAddr) -> std::io::Result<()> {
+    let address_type = if destination.is_ipv4() { "IP4" } else { "IP6" };
+    let description = format!(
+        "v=0\r\n\
+         o=- 0 0 IN {address_type} {address}\r\n\
+         s=paper-experiments\r\n\
+         c=IN {address_type} {address}\r\n\
+         t=0 0\r\n\
+         m=video {port} RTP/AVP {payload_type}\r\n\
+         a=rtpmap:{payload_type} H264/{clock_rate}\r\n\
+         a=fmtp:{payload_type} packetization-mode=1\r\n",
+        address_type = address_type,
+        address = destination.ip(),
+        port = destination.port(),
+        payload_type = PAYLOAD_TYPE,
+        clock_rate = CLOCK_RATE,
+    );
+
+    fs::write(path, description)
+}
+
+pub async fn resolve(address: &str) -> std::io::Result<SocketAddr> {
+    tokio::net::lookup_host(address)
+        .await?
+        .next()
+        .ok_or_else(|| {
+            Error::new(
+                ErrorKind::NotFound,
+                format!("unresolved address {}", address),
+            )
+        })
+}
+
+struct RtpPacket<'a> {
+    marker: bool,
+    sequence: u16,
+    ssrc: u32,
+    frame_id: u64,
+    encoded_frame_id: u64,
+    capture_timestamp: u64,
+    first_sequence: u16,
+    payload: &'a [u8],
+}
+
+impl<'a> RtpPacket<'a> {
+    fn serialize(&self) -> Vec<u8> {
+        let mut packet = Vec::with_capacity(12 + 36 + self.payload.len());
+
+        // Version, no padding, header extension, no CSRC
+        packet.push((VERSION << 6) | 0x10);
+        packet.push(((self.marker as u8) << 7) | PAYLOAD_TYPE);
+        packet.extend_from_slice(&self.sequence.to_be_bytes());
+        packet.extend_from_slice(&rtp_timestamp(self.capture_timestamp as u128).to_be_bytes());
+        packet.extend_from_slice(&self.ssrc.to_be_bytes());
+
+        let mut extension = Vec::new();
+        for (id, value) in [
+            (FRAME_ID_EXTENSION, &self.frame_id.to_be_bytes()[..]),
+            (
+                CAPTURE_TIMESTAMP_EXTENSION,
+                &self.capture_timestamp.to_be_bytes()[..],
+            ),
+            (
+                FIRST_SEQUENCE_EXTENSION,
+                &self.first_sequence.to_be_bytes()[..],
+            ),
+            (
+                ENCODED_FRAME_ID_EXTENSION,
+                &self.encoded_frame_id.to_be_bytes()[..],
+            ),
+        ] {
+            extension.push((id << 4) | (value.len() as u8 - 1));
+            extension.extend_from_slice(value);
+        }
+        extension.resize((extension.len() + 3) / 4 * 4, 0);
+
+        packet.extend_from_slice(&ONE_BYTE_EXTENSION_PROFILE.to_be_bytes());
+        packet.extend_from_slice(&((extension.len() / 4) as u16).to_be_bytes());
+        packet.extend_from_slice(&extension);
+        packet.extend_from_slice(self.payload);
+
+        packet
+    }
+
+    fn parse(datagram: &'a [u8]) -> Result<Self, String> {
+        if datagram.len() < 12 || datagram[0] >> 6 != VERSION {
+            return Err("malformed RTP header".to_string());
+        }
+
+        let padding = datagram[0] & 0x20 != 0;
+        let has_extension = datagram[0] & 0x10 != 0;
+        let csrc_count = (datagram[0] & 0x0f) as usize;
+
+        let mut packet = Self {
+            marker: datagram[1] & 0x80 != 0,
+            sequence: u16::from_be_bytes([datagram[2], datagram[3]]),
+            ssrc: u32::from_be_bytes(datagram[8..12].try_into().unwrap()),
+            frame_id: 0,
+            encoded_frame_id: 0,
+            capture_timestamp: 0,
+            first_sequence: 0,
+            payload: &[],
+        };
+
+        let mut offset = 12 + 4 * csrc_count;
+        let mut found = 0;
+        if has_extension {
+            if datagram.len() < offset + 4 {
+                return Err("truncated RTP header extension".to_string());
+            }
+            let profile = u16::from_be_bytes([datagram[offset], datagram[offset + 1]]);
+            let words = u16::from_be_bytes([datagram[offset + 2], datagram[offset + 3]]) as usize;
+            let end = offset + 4 + words * 4;
+            if datagram.len() < end {
+                return Err("truncated RTP header extension".to_string());
+            }
+
+            if profile == ONE_BYTE_EXTENSION_PROFILE {
+                let mut elements = &datagram[offset + 4..end];
+                while let Some(&element) = elements.first() {
+                    // Padding between elements
+                    if element == 0 {
+                        elements = &elements[1..];
+                        continue;
+                    }
+
+                    let id = element >> 4;
+                    let length = (element & 0x0f) as usize + 1;
+                    if id == 15 || elements.len() < 1 + length {
+                        break;
+                    }
+                    let value = &elements[1..1 + length];
+
+                    match (id, length) {
+                        (FRAME_ID_EXTENSION, 8) => {
+                            packet.frame_id = u64::from_be_bytes(value.try_into().unwrap());
+                            found += 1;
+                        }
+                        (CAPTURE_TIMESTAMP_EXTENSION, 8) => {
+                            packet.capture_timestamp =
+                                u64::from_be_bytes(value.try_into().unwrap());
+                            found += 1;
+                        }
+                        (FIRST_SEQUENCE_EXTENSION, 2) => {
+                            packet.first_sequence = u16::from_be_bytes([value[0], value[1]]);
+                            found += 1;
+                        }
+                        (ENCODED_FRAME_ID_EXTENSION, 8) => {
+                            packet.encoded_frame_id =
+                                u64::from_be_bytes(value.try_into().unwrap());
+                            found += 1;
+                        }
+                        _ => {}
+                    }
+                    elements = &elements[1 + length..];
+                }
+            }
+
+            offset = end;
+        }
+
+        if found < 4 {
+            return Err("RTP packet without the frame extensions".to_string());
+        }
+
+        let mut end = datagram.len();
+        if padding {
+            let padding_size = *datagram.last().unwrap() as usize;
+            end = end.saturating_sub(padding_size);
+        }
+        if end <= offset {
+            return Err("RTP packet without payload".to_string());
+        }
+        packet.payload = &datagram[offset..end];
+
+        Ok(packet)
+    }
+}
+
+// Loss and jitter reported back by the receiver
+#[derive(Default)]
+struct ReceptionReport {
+    cumulative_lost: u32,
+    jitter: u32,
+    round_trip_time: u32,
+}
+
+// Server side: packetizes each encoded H.264 frame following RFC 6184, with RTCP multiplexed on the
+// same port. Packets go to `destination` when given, and RTCP to the port next to it as players
+// expect, otherwise to the receiver that sent the last RTCP packet.
+pub struct RtpFrameSender {
+    socket: UdpSocket,
+    destination: Option<SocketAddr>,
+    peer: Option<SocketAddr>,
+
+    ssrc: u32,
+    sequence: u16,
+    packet_count: u32,
+    octet_count: u32,
+    last_sender_report: Option<Instant>,
+
+    report: ReceptionReport,
+}
+
+impl RtpFrameSender {
+    pub async fn bind(port: u16, destination: Option<&str>) -> std::io::Result<Self> {
+        let socket = UdpSocket::bind(("0.0.0.0", port)).await?;
+        let destination = match destination {
+            Some(destination) => Some(resolve(destination).await?),
+            None => None,
+        };
+
+        match destination {
+            Some(destination) => info!("Sending RTP from port {} to {}", port, destination),
+            None => info!("Waiting for an RTP receiver on port {}", port),
+        }
+
+        Ok(Self {
+            socket,
+            destination,
+            peer: None,
+            ssrc: rand::random(),
+            sequence: rand::random(),
+            packet_count: 0,
+            octet_count: 0,
+            last_sender_report: None,
+            report: ReceptionReport::default(),
+        })
+    }
+
+    fn handle_rtcp(&mut self, datagram: &[u8], address: SocketAddr) {
+        let reports = match rtcp::parse(datagram) {
+            Ok(reports) => reports,
+            Err(err) => {
+                debug!("Ignoring an RTCP packet from {}: {}", address, err);
+                return;
+            }
+        };
+
+        if self.destination.is_none() && self.peer != Some(address) {
+            info!("Sending RTP to {}", address);
+            self.peer = Some(address);
+        }
+
+        for report in reports {
+            if let RtcpPacket::ReceiverReport(_, Some(block)) = report {
+                if block.ssrc != self.ssrc {
+                    continue;
+                }
+
+                self.report.cumulative_lost = block.cumulative_lost;
+                self.report.jitter = block.jitter;
+                if block.last_sender_report != 0 {
+                    self.report.round_trip_time = rtcp::compact_ntp(rtcp::ntp_now())
+                        .wrapping_sub(block.last_sender_report)
+                        .wrapping_sub(block.delay_since_last_sender_report);
+                }
+            }
+        }
+    }
+
+    // Waits for the first receiver unless a destination is set, then only handles the RTCP
+    // packets already received
+    async fn targets(&mut self) -> std::io::Result<(SocketAddr, SocketAddr)> {
+        let mut buffer = [0; MAX_DATAGRAM_SIZE];
+
+        while let Ok((size, address)) = self.socket.try_recv_from(&mut buffer) {
+            self.handle_rtcp(&buffer[..size], address);
+        }
+
+        if let Some(destination) = self.destination {
+            let mut rtcp_destination = destination;
+            rtcp_destination.set_port(destination.port() + 1);
+            return Ok((destination, rtcp_destination));
+        }
+
+        while self.peer.is_none() {
+            let (size, address) = self.socket.recv_from(&mut buffer).await?;
+            self.handle_rtcp(&buffer[..size], address);
+        }
+
+        let peer = self.peer.unwrap();
+        Ok((peer, peer))
+    }
+
+    async fn send(&mut self, frame_data: &mut FrameData) -> std::io::Result<()> {
+        let (rtp_target, rtcp_target) = self.targets().await?;
+
+        let frame_id = frame_data.get("frame_id") as u64;
+        let encoded_frame_id = frame_data.get("encoded_frame_id") as u64;
+        let capture_timestamp = frame_data.get("capture_timestamp") as u64;
+        let encoded_size = frame_data.get("encoded_size") as usize;
+
+        let payloads = {
+            let encoded = frame_data
+                .get_writable_buffer_ref("encoded_frame_buffer")
+                .unwrap();
+            h264::packetize(&h264::nal_units(&encoded[..encoded_size]), MAX_PAYLOAD)
+        };
+
+        let first_sequence = self.sequence;
+        for (index, payload) in payloads.iter().enumerate() {
+            let packet = RtpPacket {
+                marker: index == payloads.len() - 1,
+                sequence: self.sequence,
+                ssrc: self.ssrc,
+                frame_id,
+                encoded_frame_id,
+                capture_timestamp,
+                first_sequence,
+                payload,
+            };
+            self.socket.send_to(&packet.serialize(), rtp_target).await?;
+
+            self.sequence = self.sequence.wrapping_add(1);
+            self.packet_count = self.packet_count.wrapping_add(1);
+            self.octet_count = self.octet_count.wrapping_add(payload.len() as u32);
+        }
+
+        frame_data.set("rtp_packets", payloads.len() as u128);
+        frame_data.set("rtcp_lost_packets", self.report.cumulative_lost as u128);
+        frame_data.set("rtcp_jitter", jitter_to_micros(self.report.jitter));
+        frame_data.set(
+            "rtcp_round_trip_time",
+            rtcp::compact_to_micros(self.report.round_trip_time),
+        );
+
+        if self
+            .last_sender_report
+            .map_or(true, |last| last.elapsed() >= RTCP_INTERVAL)
+        {
+            let report = RtcpPacket::SenderReport(SenderReport {
+                ssrc: self.ssrc,
+                ntp_timestamp: rtcp::ntp_now(),
+                rtp_timestamp: rtp_timestamp(now_timestamp()),
+                packet_count: self.packet_count,
+                octet_count: self.octet_count,
+            });
+            self.socket
+                .send_to(&rtcp::serialize(&report, CNAME), rtcp_target)
+                .await?;
+            self.last_sender_report = Some(Instant::now());
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl FrameProcessor for RtpFrameSender {
+    async fn process(&mut self, mut frame_data: FrameData) -> Option<FrameData> {
+        // Known even when sending fails, so that server.csv always has the stats
+        for stat in RTP_SENDER_STATS {
+            frame_data.set(stat, 0);
+        }
+
+        if let Err(err) = self.send(&mut frame_data).await {
+            debug!("Unable to send the frame: {}", err);
+            frame_data.set_drop_reason(Some(DropReason::ConnectionError));
+        }
+
+        Some(frame_data)
+    }
+}
+
+// Reception statistics of RFC 3550 about the sender, reset whenever its SSRC changes
+struct SourceStatistics {
+    ssrc: u32,
+    base_sequence: u32,
+    max_sequence: u16,
+    cycles: u32,
+    received: u32,
+    expected_prior: u32,
+    received_prior: u32,
+
+    // In RTP timestamp units
+    transit: Option<i64>,
+    jitter: f64,
+
+    // Compact NTP timestamp of the last sender report, and when it arrived
+    last_sender_report: Option<(u32, Instant)>,
+}
+
+impl SourceStatistics {
+    fn new(ssrc: u32, sequence: u16) -> Self {
+        Self {
+            ssrc,
+            base_sequence: sequence as u32,
+            max_sequence: sequence,
+            cycles: 0,
+            received: 0,
+            expected_prior: 0,
+            received_prior: 0,
+            transit: None,
+            jitter: 0.0,
+            last_sender_report: None,
+        }
+    }
+
+    fn update(&mut self, sequence: u16, rtp_timestamp: u32, arrival: u32) {
+        // Ahead of the highest sequence number, possibly wrapping around
+        let delta = sequence.wrapping_sub(self.max_sequence);
+        if delta != 0 && delta < 0x8000 {
+            if sequence < self.max_sequence {
+                self.cycles += 1 << 16;
+            }
+            self.max_sequence = sequence;
+        }
+        self.received += 1;
+
+        let transit = arrival.wrapping_sub(rtp_timestamp) as i32 as i64;
+        if let Some(previous) = self.transit {
+            let difference = (transit - previous).abs() as f64;
+            self.jitter += (difference - self.jitter) / 16.0;
+        }
+        self.transit = Some(transit);
+    }
+
+    fn highest_sequence(&self) -> u32 {
+        self.cycles + self.max_sequence as u32
+    }
+
+    fn expected(&self) -> u32 {
+        self.highest_sequence() - self.base_sequence + 1
+    }
+
+    // Duplicates can make it negative, reported as zero
+    fn cumulative_lost(&self) -> u32 {
+        self.expected().saturating_sub(self.received)
+    }
+
+    fn report_block(&mut self) -> ReportBlock {
+        let expected = self.expected();
+        let expected_interval = expected - self.expected_prior;
+        let received_interval = self.received - self.received_prior;
+        self.expected_prior = expected;
+        self.received_prior = self.received;
+
+        let lost_interval = expected_interval.saturating_sub(received_interval);
+        let fraction_lost = if expected_interval == 0 {
+            0
+        } else {
+            ((lost_interval << 8) / expected_interval) as u8
+        };
+
+        let (last_sender_report, delay_since_last_sender_report) = match self.last_sender_report {
+            Some((ntp, received_at)) => (ntp, rtcp::compact_duration(received_at.elapsed())),
+            None => (0, 0),
+        };
+
+        ReportBlock {
+            ssrc: self.ssrc,
+            fraction_lost,
+            cumulative_lost: self.cumulative_lost(),
+            highest_sequence: self.highest_sequence(),
+            jitter: self.jitter as u32,
+            last_sender_report,
+            delay_since_last_sender_report,
+        }
+    }
+}
+
+struct PartialFrame {
+    encoded_frame_id: u64,
+    capture_timestamp: u64,
+    // Keyed by the offset from the first sequence number of the frame
+    payloads: BTreeMap<u16, Vec<u8>>,
+    // Known once the packet with the marker bit arrived
+    count: Option<usize>,
+}
+
+impl PartialFrame {
+    fn is_complete(&self) -> bool {
+        self.count == Some(self.payloads.len())
+    }
+
+    fn assemble(&self) -> Result<Vec<u8>, String> {
+        let mut stream = Vec::new();
+        for payload in self.payloads.values() {
+            h264::depacketize(payload, &mut stream)?;
+        }
+        Ok(stream)
+    }
+}
+
+// Client side: reassembles the frames from their RTP packets, delivering them in order and giving
+// up on any frame still incomplete when a later one completes. Reports every RTCP_INTERVAL.
+pub struct RtpFrameReceiver {
+    socket: UdpSocket,
+    ssrc: u32,
+    started: Instant,
+
+    source: Option<SourceStatistics>,
+    last_report: Instant,
+
+    frames: BTreeMap<u64, PartialFrame>,
+    last_delivered: Option<u64>,
+}
+
+impl RtpFrameReceiver {
+    pub async fn connect(address: &str) -> std::io::Result<Self> {
+        let socket = UdpSocket::bind(("0.0.0.0", 0)).await?;
+        socket.connect(address).await?;
+
+        let mut receiver = Self {
+            socket,
+            ssrc: rand::random(),
+            started: Instant::now(),
+            source: None,
+            last_report: Instant::now(),
+            frames: BTreeMap::new(),
+            last_delivered: None,
+        };
+        receiver.send_report().await;
+
+        Ok(receiver)
+    }
+
+    async fn send_report(&mut self) {
+        let block = self.source.as_mut().map(SourceStatistics::report_block);
+        let report = RtcpPacket::ReceiverReport(self.ssrc, block);
+
+        self.socket
+            .send(&rtcp::serialize(&report, CNAME))
+            .await
+            .ok();
+        self.last_report = Instant::now();
+    }
+
+    fn handle_rtcp(&mut self, datagram: &[u8]) {
+        let reports = match rtcp::parse(datagram) {
+            Ok(reports) => reports,
+            Err(err) => {
+                debug!("Ignoring an RTCP packet: {}", err);
+                return;
+            }
+        };
+
+        for report in reports {
+            if let RtcpPacket::SenderReport(report) = report {
+                if let Some(source) = self
+                    .source
+                    .as_mut()
+                    .filter(|source| source.ssrc == report.ssrc)
+                {
+                    source.last_sender_report =
+                        Some((rtcp::compact_ntp(report.ntp_timestamp), Instant::now()));
+                }
+            }
+        }
+    }
+
+    // Returns the frame completed by the packet, if any
+    fn handle_rtp(&mut self, datagram: &[u8]) -> Option<(u64, PartialFrame)> {
+        let packet = match RtpPacket::parse(datagram) {
+            Ok(packet) => packet,
+            Err(err) => {
+                debug!("Ignoring an RTP packet: {}", err);
+                return None;
+            }
+        };
+
+        if self
+            .source
+            .as_ref()
+            .map_or(true, |source| source.ssrc != packet.ssrc)
+        {
+            info!("Receiving RTP from SSRC {:08x}", packet.ssrc);
+            self.source = Some(SourceStatistics::new(packet.ssrc, packet.sequence));
+            self.frames.clear();
+            self.last_delivered = None;
+        }
+
+        let arrival = (self.started.elapsed().as_micros() * CLOCK_RATE as u128 / 1_000_000) as u32;
+        self.source.as_mut().unwrap().update(
+            packet.sequence,
+            rtp_timestamp(packet.capture_timestamp as u128),
+            arrival,
+        );
+
+        if self
+            .last_delivered
+            .map_or(false, |last| packet.frame_id <= last)
+        {
+            return None;
+        }
+
+        let frame = self
+            .frames
+            .entry(packet.frame_id)
+            .or_insert_with(|| PartialFrame {
+                encoded_frame_id: packet.encoded_frame_id,
+                capture_timestamp: packet.capture_timestamp,
+                payloads: BTreeMap::new(),
+                count: None,
+            });
+        let offset = packet.sequence.wrapping_sub(packet.first_sequence);
+        frame.payloads.insert(offset, packet.payload.to_vec());
+        if packet.marker {
+            frame.count = Some(offset as usize + 1);
+        }
+
+        if !frame.is_complete() {
+            while self.frames.len() > MAX_PENDING_FRAMES {
+                let oldest = *self.frames.keys().next().unwrap();
+                self.frames.remove(&oldest);
+            }
+            return None;
+        }
+
+        let frame_id = packet.frame_id;
+        let later_frames = self.frames.split_off(&(frame_id + 1));
+        let frame = self.frames.remove(&frame_id).unwrap();
+        if !self.frames.is_empty() {
+            debug!("Gave up on {} incomplete frames", self.frames.len());
+        }
+        self.frames = later_frames;
+        self.last_delivered = Some(frame_id);
+
+        Some((frame_id, frame))
+    }
+
+    async fn receive(&mut self) -> (u64, PartialFrame) {
+        let mut buffer = [0; MAX_DATAGRAM_SIZE];
+        loop {
+            if self.last_report.elapsed() >= RTCP_INTERVAL {
+                self.send_report().await;
+            }
+
+            let received =
+                tokio::time::timeout(IDLE_REPORT_INTERVAL, self.socket.recv(&mut buffer)).await;
+            match received {
+                Ok(Ok(size)) => {
+                    let datagram = &buffer[..size];
+                    if rtcp::is_rtcp(datagram) {
+                        self.handle_rtcp(datagram);
+                    } else if let Some(frame) = self.handle_rtp(datagram) {
+                        return frame;
+                    }
+                }
+                // Nothing listening yet on the sender side
+                Ok(Err(err)) => {
+                    debug!("Unable to receive from the sender: {}", err);
+                    tokio::time::sleep(IDLE_REPORT_INTERVAL).await;
+                    self.send_report().await;
+                }
+                Err(_) => self.send_report().await,
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl FrameProcessor for RtpFrameReceiver {
+    async fn process(&mut self, mut frame_data: FrameData) -> Option<FrameData> {
+        let (frame_id, frame) = self.receive().await;
+
+        let source = self.source.as_ref().unwrap();
+        frame_data.set("rtp_packets", frame.payloads.len() as u128);
+        frame_data.set("rtp_lost_packets", source.cumulative_lost() as u128);
+        frame_data.set("rtp_jitter", jitter_to_micros(source.jitter as u32));
+
+        let stored = frame.assemble().and_then(|stream| {
+            store_frame(
+                &mut frame_data,
+                frame_id as u128,
+                frame.encoded_frame_id as u128,
+                frame.capture_timestamp as u128,
+                &stream,
+            )
+        });
+
+        if let Err(err) = stored {
+            debug!("Malformed frame: {}", err);
+            frame_data.set_drop_reason(Some(DropReason::ConnectionError));
+        }
+
+        Some(frame_data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packets(frame_id: u64, first_sequence: u16, payloads: &[Vec<u8>]) -> Vec<Vec<u8>> {
+        payloads
+            .iter()
+            .enumerate()
+            .map(|(index, payload)| {
+                RtpPacket {
+                    marker: index == payloads.len() - 1,
+                    sequence: first_sequence.wrapping_add(index as u16),
+                    ssrc: 0x1234_5678,
+                    frame_id,
+                    encoded_frame_id: frame_id,
+                    capture_timestamp: 1_650_000_000_000,
+                    first_sequence,
+                    payload,
+                }
+                .serialize()
+            })
+            .collect()
+    }
+
+    fn insert(frame: &mut PartialFrame, datagram: &[u8]) {
+        let packet = RtpPacket::parse(datagram).unwrap();
+        let offset = packet.sequence.wrapping_sub(packet.first_sequence);
+        frame.payloads.insert(offset, packet.payload.to_vec());
+        if packet.marker {
+            frame.count = Some(offset as usize + 1);
+        }
+    }
+
+    fn partial_frame() -> PartialFrame {
+        PartialFrame {
+            encoded_frame_id: 0,
+            capture_timestamp: 0,
+            payloads: BTreeMap::new(),
+            count: None,
+        }
+    }
+
+    fn idr_payloads() -> (Vec<u8>, Vec<Vec<u8>>) {
+        let mut idr = vec![0x65];
+        idr.extend((1..3000).map(|index| (index % 251) as u8 + 1));
+        let payloads = h264::packetize(&[&idr], MAX_PAYLOAD);
+
+        let mut stream = vec![0, 0, 0, 1];
+        stream.extend_from_slice(&idr);
+        (stream, payloads)
+    }
+
+    #[test]
+    fn parses_serialized_packets() {
+        let payload = vec![0x41, 1, 2, 3];
+        let datagram = &packets(42, 65535, std::slice::from_ref(&payload))[0];
+        let packet = RtpPacket::parse(datagram).unwrap();
+
+        assert!(packet.marker);
+        assert_eq!(packet.sequence, 65535);
+        assert_eq!(packet.ssrc, 0x1234_5678);
+        assert_eq!(packet.frame_id, 42);
+        assert_eq!(packet.encoded_frame_id, 42);
+        assert_eq!(packet.capture_timestamp, 1_650_000_000_000);
+        assert_eq!(packet.first_sequence, 65535);
+        assert_eq!(packet.payload, &payload[..]);
+    }
+
+    #[test]
+    fn rejects_packets_without_the_frame_extensions() {
+        let mut datagram = packets(42, 0, &[vec![0x41, 1]]).remove(0);
+        // Clears the extension bit
+        datagram[0] &= !0x10;
+        assert!(RtpPacket::parse(&datagram).is_err());
+        assert!(RtpPacket::parse(&datagram[..8]).is_err());
+    }
+
+    #[test]
+    fn reassembles_reordered_packets() {
+        let (stream, payloads) = idr_payloads();
+        let mut datagrams = packets(7, 65534, &payloads);
+        datagrams.reverse();
+
+        let mut frame = partial_frame();
+        for datagram in &datagrams {
+            insert(&mut frame, datagram);
+        }
+
+        assert!(frame.is_complete());
+        assert_eq!(frame.assemble().unwrap(), stream);
+    }
+
+    #[test]
+    fn frame_with_a_lost_packet_stays_incomplete() {
+        let (_, payloads) = idr_payloads();
+        let datagrams = packets(7, 100, &payloads);
+
+        let mut frame = partial_frame();
+        for (index, datagram) in datagrams.iter().enumerate() {
+            if index != 1 {
+                insert(&mut frame, datagram);
+            }
+        }
+        assert!(!frame.is_complete());
+
+        insert(&mut frame, &datagrams[1]);
+        assert!(frame.is_complete());
+    }
+
+    #[test]
+    fn counts_lost_packets_across_the_wraparound() {
+        let mut source = SourceStatistics::new(1, 65530);
+        for sequence in [65530, 65531, 65533, 2, 1, 3] {
+            source.update(sequence, 0, 0);
+        }
+
+        // 65532 and 65534 to 0 are missing, 1 arrived out of order
+        assert_eq!(source.highest_sequence(), (1 << 16) + 3);
+        assert_eq!(source.expected(), 10);
+        assert_eq!(source.cumulative_lost(), 4);
+
+        let block = source.report_block();
+        assert_eq!(block.cumulative_lost, 4);
+        // 4 out of 10 in 1/256 units
+        assert_eq!(block.fraction_lost, 102);
+
+        // Nothing new since the last report
+        assert_eq!(source.report_block().fraction_lost, 0);
+    }
+
+    #[test]
+    fn duplicates_do_not_count_as_negative_losses() {
+        let mut source = SourceStatistics::new(1, 10);
+        for sequence in [10, 11, 11, 11, 12] {
+            source.update(sequence, 0, 0);
+        }
+
+        assert_eq!(source.cumulative_lost(), 0);
+    }
+}
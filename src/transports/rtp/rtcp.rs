@@ -0,0 +1,282 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// RTCP packets of RFC 3550 used by the RTP transport, sent as compound packets followed by a
+// CNAME description
+
+const VERSION: u8 = 2;
+
+const SENDER_REPORT: u8 = 200;
+const RECEIVER_REPORT: u8 = 201;
+const SOURCE_DESCRIPTION: u8 = 202;
+
+const CNAME: u8 = 1;
+
+// Seconds between 1900, the NTP epoch, and 1970
+const NTP_UNIX_OFFSET: u64 = 2_208_988_800;
+
+pub fn ntp_now() -> u64 {
+    let since_epoch = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let fraction = (since_epoch.subsec_nanos() as u64 * (1 << 32)) / 1_000_000_000;
+
+    ((since_epoch.as_secs() + NTP_UNIX_OFFSET) << 32) | fraction
+}
+
+// Middle 32 bits of an NTP timestamp, the unit of LSR and DLSR (1/65536 seconds)
+pub fn compact_ntp(ntp: u64) -> u32 {
+    (ntp >> 16) as u32
+}
+
+pub fn compact_duration(duration: Duration) -> u32 {
+    (duration.as_secs_f64() * 65536.0) as u32
+}
+
+pub fn compact_to_micros(compact: u32) -> u128 {
+    compact as u128 * 1_000_000 / 65536
+}
+
+// With RTCP multiplexed on the RTP port (RFC 5761), payload types 192 to 223 are RTCP
+pub fn is_rtcp(datagram: &[u8]) -> bool {
+    datagram.len() >= 8 && (192..=223).contains(&datagram[1])
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SenderReport {
+    pub ssrc: u32,
+    pub ntp_timestamp: u64,
+    pub rtp_timestamp: u32,
+    pub packet_count: u32,
+    pub octet_count: u32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ReportBlock {
+    pub ssrc: u32,
+    pub fraction_lost: u8,
+    // 24 bits on the wire
+    pub cumulative_lost: u32,
+    pub highest_sequence: u32,
+    pub jitter: u32,
+    pub last_sender_report: u32,
+    pub delay_since_last_sender_report: u32,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum RtcpPacket {
+    SenderReport(SenderReport),
+    // Reporter SSRC and at most one block, about the only source of the session
+    ReceiverReport(u32, Option<ReportBlock>),
+}
+
+fn header(count: usize, packet_type: u8, words: usize) -> [u8; 4] {
+    let length = (words - 1) as u16;
+    let [high, low] = length.to_be_bytes();
+    [(VERSION << 6) | count as u8, packet_type, high, low]
+}
+
+fn push_block(packet: &mut Vec<u8>, block: &ReportBlock) {
+    packet.extend_from_slice(&block.ssrc.to_be_bytes());
+    packet.push(block.fraction_lost);
+    packet.extend_from_slice(&block.cumulative_lost.min(0x7f_ffff).to_be_bytes()[1..]);
+    packet.extend_from_slice(&block.highest_sequence.to_be_bytes());
+    packet.extend_from_slice(&block.jitter.to_be_bytes());
+    packet.extend_from_slice(&block.last_sender_report.to_be_bytes());
+    packet.extend_from_slice(&block.delay_since_last_sender_report.to_be_bytes());
+}
+
+fn push_description(packet: &mut Vec<u8>, ssrc: u32, cname: &str) {
+    let cname = &cname.as_bytes()[..cname.len().min(255)];
+
+    // SSRC, CNAME item, then at least one null octet ending the item list on a word boundary
+    let chunk_size = 4 + 2 + cname.len();
+    let padded_size = (chunk_size / 4 + 1) * 4;
+
+    packet.extend_from_slice(&header(1, SOURCE_DESCRIPTION, 1 + padded_size / 4));
+    packet.extend_from_slice(&ssrc.to_be_bytes());
+    packet.push(CNAME);
+    packet.push(cname.len() as u8);
+    packet.extend_from_slice(cname);
+    packet.resize(packet.len() + padded_size - chunk_size, 0);
+}
+
+pub fn serialize(report: &RtcpPacket, cname: &str) -> Vec<u8> {
+    let mut packet = Vec::new();
+
+    let ssrc = match report {
+        RtcpPacket::SenderReport(report) => {
+            packet.extend_from_slice(&header(0, SENDER_REPORT, 7));
+            packet.extend_from_slice(&report.ssrc.to_be_bytes());
+            packet.extend_from_slice(&report.ntp_timestamp.to_be_bytes());
+            packet.extend_from_slice(&report.rtp_timestamp.to_be_bytes());
+            packet.extend_from_slice(&report.packet_count.to_be_bytes());
+            packet.extend_from_slice(&report.octet_count.to_be_bytes());
+            report.ssrc
+        }
+        RtcpPacket::ReceiverReport(ssrc, block) => {
+            let count = usize::from(block.is_some());
+            packet.extend_from_slice(&header(count, RECEIVER_REPORT, 2 + 6 * count));
+            packet.extend_from_slice(&ssrc.to_be_bytes());
+            if let Some(block) = block {
+                push_block(&mut packet, block);
+            }
+            *ssrc
+        }
+    };
+
+    push_description(&mut packet, ssrc, cname);
+    packet
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn parse_block(block: &[u8]) -> ReportBlock {
+    ReportBlock {
+        ssrc: read_u32(block, 0),
+        fraction_lost: block[4],
+        cumulative_lost: read_u32(block, 4) & 0xff_ffff,
+        highest_sequence: read_u32(block, 8),
+        jitter: read_u32(block, 12),
+        last_sender_report: read_u32(block, 16),
+        delay_since_last_sender_report: read_u32(block, 20),
+    }
+}
+
+// Reports of a compound packet, ignoring the other packet types. Fails at the first malformed
+// packet, keeping none of the compound.
+pub fn parse(compound: &[u8]) -> Result<Vec<RtcpPacket>, String> {
+    let mut reports = Vec::new();
+    let mut remaining = compound;
+
+    while !remaining.is_empty() {
+        if remaining.len() < 4 || remaining[0] >> 6 != VERSION {
+            return Err("malformed RTCP header".to_string());
+        }
+
+        let count = (remaining[0] & 0x1f) as usize;
+        let size = (u16::from_be_bytes([remaining[2], remaining[3]]) as usize + 1) * 4;
+        if remaining.len() < size {
+            return Err(format!("truncated {} bytes RTCP packet", size));
+        }
+        let packet = &remaining[..size];
+
+        match packet[1] {
+            SENDER_REPORT if size >= 28 => {
+                reports.push(RtcpPacket::SenderReport(SenderReport {
+                    ssrc: read_u32(packet, 4),
+                    ntp_timestamp: ((read_u32(packet, 8) as u64) << 32)
+                        | read_u32(packet, 12) as u64,
+                    rtp_timestamp: read_u32(packet, 16),
+                    packet_count: read_u32(packet, 20),
+                    octet_count: read_u32(packet, 24),
+                }));
+            }
+            RECEIVER_REPORT if size >= 8 + 24 * count => {
+                let block = (count > 0).then(|| parse_block(&packet[8..32]));
+                reports.push(RtcpPacket::ReceiverReport(read_u32(packet, 4), block));
+            }
+            SENDER_REPORT | RECEIVER_REPORT => {
+                return Err(format!("truncated {} bytes RTCP report", size));
+            }
+            _ => {}
+        }
+
+        remaining = &remaining[size..];
+    }
+
+    Ok(reports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sender_report() -> SenderReport {
+        SenderReport {
+            ssrc: 0xdead_beef,
+            ntp_timestamp: 0x1122_3344_5566_7788,
+            rtp_timestamp: 90_000,
+            packet_count: 1000,
+            octet_count: 1_200_000,
+        }
+    }
+
+    fn report_block() -> ReportBlock {
+        ReportBlock {
+            ssrc: 0xdead_beef,
+            fraction_lost: 25,
+            cumulative_lost: 300,
+            highest_sequence: (2 << 16) + 17,
+            jitter: 450,
+            last_sender_report: 0x3344_5566,
+            delay_since_last_sender_report: 65536,
+        }
+    }
+
+    #[test]
+    fn parses_serialized_reports() {
+        for report in [
+            RtcpPacket::SenderReport(sender_report()),
+            RtcpPacket::ReceiverReport(7, Some(report_block())),
+            RtcpPacket::ReceiverReport(7, None),
+        ] {
+            let compound = serialize(&report, "paper-experiments");
+            assert_eq!(compound.len() % 4, 0);
+            assert!(is_rtcp(&compound));
+            // The source description is skipped
+            assert_eq!(parse(&compound).unwrap(), vec![report]);
+        }
+    }
+
+    #[test]
+    fn clamps_the_cumulative_loss_to_24_bits() {
+        let block = ReportBlock {
+            cumulative_lost: 0x100_0000,
+            ..report_block()
+        };
+        let compound = serialize(&RtcpPacket::ReceiverReport(7, Some(block)), "");
+
+        match &parse(&compound).unwrap()[..] {
+            [RtcpPacket::ReceiverReport(7, Some(parsed))] => {
+                assert_eq!(parsed.cumulative_lost, 0x7f_ffff);
+                assert_eq!(parsed.fraction_lost, block.fraction_lost);
+            }
+            reports => panic!("unexpected reports {:?}", reports),
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_compounds() {
+        let compound = serialize(&RtcpPacket::SenderReport(sender_report()), "cname");
+        assert!(parse(&compound[..compound.len() - 4]).is_err());
+
+        let mut wrong_version = compound.clone();
+        wrong_version[0] &= 0x3f;
+        assert!(parse(&wrong_version).is_err());
+
+        // A sender report too short for its fields
+        let mut truncated = header(0, SENDER_REPORT, 2).to_vec();
+        truncated.extend_from_slice(&[0; 4]);
+        assert!(parse(&truncated).is_err());
+    }
+
+    #[test]
+    fn tells_rtcp_from_rtp() {
+        let compound = serialize(&RtcpPacket::ReceiverReport(7, None), "cname");
+        assert!(is_rtcp(&compound));
+
+        // Dynamic payload type 96 with the marker bit
+        let mut rtp = vec![0x90, 0x80 | 96];
+        rtp.extend_from_slice(&[0; 10]);
+        assert!(!is_rtcp(&rtp));
+    }
+
+    #[test]
+    fn converts_compact_ntp_durations() {
+        assert_eq!(compact_ntp(0x1122_3344_5566_7788), 0x3344_5566);
+        assert_eq!(compact_duration(Duration::from_millis(500)), 32768);
+        assert_eq!(compact_to_micros(32768), 500_000);
+    }
+}
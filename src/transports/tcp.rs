@@ -0,0 +1,149 @@
+use std::{
+    io::{Error, ErrorKind},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use log::{debug, info};
+use remotia::{error::DropReason, traits::FrameProcessor, types::FrameData};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+use super::{deserialize_frame, serialize_frame};
+
+const RECONNECT_INTERVAL: Duration = Duration::from_millis(500);
+
+// Anything longer is taken as a desynchronized stream
+const MAX_MESSAGE_SIZE: usize = 64 * 1024 * 1024;
+
+// Server side: one receiver at a time, each frame prefixed by its length as a little endian u32.
+// A frame failing to be written is dropped and the next one waits for a new receiver.
+pub struct TcpFrameSender {
+    listener: TcpListener,
+    stream: Option<TcpStream>,
+}
+
+impl TcpFrameSender {
+    pub async fn bind(port: u16) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(("0.0.0.0", port)).await?;
+        info!("Waiting for a TCP receiver on port {}", port);
+
+        Ok(Self {
+            listener,
+            stream: None,
+        })
+    }
+
+    async fn send(&mut self, frame_data: &mut FrameData) -> std::io::Result<()> {
+        if self.stream.is_none() {
+            let (stream, address) = self.listener.accept().await?;
+            stream.set_nodelay(true)?;
+            info!("Sending frames to {}", address);
+            self.stream = Some(stream);
+        }
+        let stream = self.stream.as_mut().unwrap();
+
+        let message = serialize_frame(frame_data);
+        let result = async {
+            stream.write_u32_le(message.len() as u32).await?;
+            stream.write_all(&message).await
+        }
+        .await;
+
+        if result.is_err() {
+            self.stream = None;
+        }
+
+        result
+    }
+}
+
+#[async_trait]
+impl FrameProcessor for TcpFrameSender {
+    async fn process(&mut self, mut frame_data: FrameData) -> Option<FrameData> {
+        if let Err(err) = self.send(&mut frame_data).await {
+            debug!("Unable to send the frame: {}", err);
+            frame_data.set_drop_reason(Some(DropReason::ConnectionError));
+        }
+
+        Some(frame_data)
+    }
+}
+
+// Client side: connects on the first frame and again after any error
+pub struct TcpFrameReceiver {
+    address: String,
+    stream: Option<TcpStream>,
+}
+
+impl TcpFrameReceiver {
+    pub fn new(address: &str) -> Self {
+        Self {
+            address: address.to_string(),
+            stream: None,
+        }
+    }
+
+    async fn connect(&self) -> TcpStream {
+        loop {
+            match TcpStream::connect(&self.address).await {
+                Ok(stream) => {
+                    stream.set_nodelay(true).ok();
+                    info!("Receiving frames from {}", self.address);
+                    return stream;
+                }
+                Err(err) => {
+                    debug!("TCP sender at {} not available: {}", self.address, err);
+                    tokio::time::sleep(RECONNECT_INTERVAL).await;
+                }
+            }
+        }
+    }
+
+    async fn receive(&mut self) -> std::io::Result<Vec<u8>> {
+        if self.stream.is_none() {
+            self.stream = Some(self.connect().await);
+        }
+        let stream = self.stream.as_mut().unwrap();
+
+        let result = async {
+            let length = stream.read_u32_le().await? as usize;
+            if length > MAX_MESSAGE_SIZE {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("{} bytes frame announced", length),
+                ));
+            }
+
+            let mut message = vec![0; length];
+            stream.read_exact(&mut message).await?;
+            Ok(message)
+        }
+        .await;
+
+        if result.is_err() {
+            self.stream = None;
+        }
+
+        result
+    }
+}
+
+#[async_trait]
+impl FrameProcessor for TcpFrameReceiver {
+    async fn process(&mut self, mut frame_data: FrameData) -> Option<FrameData> {
+        let received = match self.receive().await {
+            Ok(message) => deserialize_frame(&message, &mut frame_data),
+            Err(err) => Err(err.to_string()),
+        };
+
+        if let Err(err) = received {
+            debug!("Unable to receive the frame: {}", err);
+            frame_data.set_drop_reason(Some(DropReason::ConnectionError));
+        }
+
+        Some(frame_data)
+    }
+}
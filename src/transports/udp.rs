@@ -0,0 +1,765 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use log::{debug, info};
+use remotia::{error::DropReason, traits::FrameProcessor, types::FrameData};
+use tokio::{
+    net::UdpSocket,
+    sync::{watch, Notify},
+    task::JoinHandle,
+};
+
+use super::{
+    deserialize_frame,
+    fec::{self, FecScheme, FecSettings},
+    nack::{self, NackPolicy},
+    serialize_frame,
+};
+use crate::{
+    clock::ClockEstimate,
+    time::{now_timestamp, now_timestamp_micros},
+};
+
+// Keeps every datagram below the usual 1500 bytes MTU
+const MAX_FRAGMENT_PAYLOAD: usize = 1200;
+const MAX_DATAGRAM_SIZE: usize = 65536;
+
+const HELLO: u8 = 0;
+const FRAGMENT: u8 = 1;
+const REPAIR: u8 = 2;
+// Same layout as FRAGMENT
+const RETRANSMISSION: u8 = 3;
+const NACK: u8 = 4;
+// Type, frame_id, capture_timestamp, index and count of the fragment
+const FRAGMENT_HEADER_SIZE: usize = 1 + 8 + 8 + 2 + 2;
+// Type, frame_id, capture_timestamp, count of the data fragments, FEC scheme, group size, repair
+// packets per group, group and index in the group of the repair packet
+const REPAIR_HEADER_SIZE: usize = 1 + 8 + 8 + 2 + 1 + 1 + 1 + 2 + 1;
+
+// Frames still missing fragments beyond this many are given up
+const MAX_PENDING_FRAMES: usize = 64;
+
+// Repair packets and bytes sent for the frame
+pub const UDP_SENDER_STATS: [&str; 2] = ["fec_repair_packets", "fec_overhead_bytes"];
+
+// For the frame: fragments recovered by FEC, requested, retransmitted, and arrived after its
+// deadline, including the fragments of given up frames arrived since the previous frame. Since the
+// previous frame: frames given up because of missing fragments, and the part of them given up once
+// their deadline expired.
+pub const UDP_RECEIVER_STATS: [&str; 6] = [
+    "fec_recovered_packets",
+    "nack_requested_packets",
+    "nack_retransmitted_packets",
+    "nack_late_packets",
+    "fec_unrecoverable_frames",
+    "nack_expired_frames",
+];
+
+// NACKs stay well below the MTU
+const MAX_NACK_INDICES: usize = 512;
+
+// The receiver says hello again when nothing arrived for this long, in case the sender restarted
+const HELLO_INTERVAL: Duration = Duration::from_millis(500);
+
+// How often the receiver looks for fragments to request while waiting
+const NACK_CHECK_INTERVAL: Duration = Duration::from_millis(5);
+
+fn chunks(message: &[u8]) -> Vec<&[u8]> {
+    if message.is_empty() {
+        vec![message]
+    } else {
+        message.chunks(MAX_FRAGMENT_PAYLOAD).collect()
+    }
+}
+
+// Header fields shared by the fragments and the repair packets of a frame
+#[derive(Clone, Copy)]
+struct FrameHeader {
+    frame_id: u64,
+    capture_timestamp: u64,
+}
+
+impl FrameHeader {
+    fn write(&self, datagram: &mut Vec<u8>) {
+        datagram.extend_from_slice(&self.frame_id.to_le_bytes());
+        datagram.extend_from_slice(&self.capture_timestamp.to_le_bytes());
+    }
+
+    // The datagram type is followed by the header
+    fn read(datagram: &[u8]) -> Self {
+        Self {
+            frame_id: u64::from_le_bytes(datagram[1..9].try_into().unwrap()),
+            capture_timestamp: u64::from_le_bytes(datagram[9..17].try_into().unwrap()),
+        }
+    }
+}
+
+fn fragment(header: FrameHeader, index: usize, count: usize, chunk: &[u8]) -> Vec<u8> {
+    let mut datagram = Vec::with_capacity(FRAGMENT_HEADER_SIZE + chunk.len());
+    datagram.push(FRAGMENT);
+    header.write(&mut datagram);
+    datagram.extend_from_slice(&(index as u16).to_le_bytes());
+    datagram.extend_from_slice(&(count as u16).to_le_bytes());
+    datagram.extend_from_slice(chunk);
+    datagram
+}
+
+fn scheme_id(scheme: FecScheme) -> u8 {
+    match scheme {
+        FecScheme::None => 0,
+        FecScheme::Xor => 1,
+        FecScheme::ReedSolomon => 2,
+    }
+}
+
+fn scheme_from_id(id: u8) -> Option<FecScheme> {
+    match id {
+        1 => Some(FecScheme::Xor),
+        2 => Some(FecScheme::ReedSolomon),
+        _ => None,
+    }
+}
+
+// Repair packets of every group of fragments, following them
+fn repairs(header: FrameHeader, chunks: &[&[u8]], settings: &FecSettings) -> Vec<Vec<u8>> {
+    if !settings.is_enabled() {
+        return Vec::new();
+    }
+
+    let mut datagrams = Vec::new();
+    for (group, packets) in chunks.chunks(settings.group_size).enumerate() {
+        let repairs = fec::encode(settings, packets);
+        let repair_count = repairs.len();
+
+        for (index, repair) in repairs.into_iter().enumerate() {
+            let mut datagram = Vec::with_capacity(REPAIR_HEADER_SIZE + repair.len());
+            datagram.push(REPAIR);
+            header.write(&mut datagram);
+            datagram.extend_from_slice(&(chunks.len() as u16).to_le_bytes());
+            datagram.push(scheme_id(settings.scheme));
+            datagram.push(settings.group_size as u8);
+            datagram.push(repair_count as u8);
+            datagram.extend_from_slice(&(group as u16).to_le_bytes());
+            datagram.push(index as u8);
+            datagram.extend_from_slice(&repair);
+            datagrams.push(datagram);
+        }
+    }
+
+    datagrams
+}
+
+// Fragments kept for retransmission until the deadline of their frame
+struct SentFrame {
+    capture_timestamp: u128,
+    fragments: Vec<Vec<u8>>,
+}
+
+#[derive(Default)]
+struct SenderState {
+    peer: Option<SocketAddr>,
+    history: BTreeMap<u64, SentFrame>,
+}
+
+// Answers the hellos and the NACKs of the receivers while the sender processes frames
+async fn listen(
+    socket: Arc<UdpSocket>,
+    state: Arc<Mutex<SenderState>>,
+    peer_found: Arc<Notify>,
+    nack: Option<NackPolicy>,
+) {
+    let mut buffer = [0; MAX_DATAGRAM_SIZE];
+    loop {
+        let (size, address) = match socket.recv_from(&mut buffer).await {
+            Ok(received) => received,
+            Err(err) => {
+                debug!("Unable to receive from the receiver: {}", err);
+                tokio::time::sleep(NACK_CHECK_INTERVAL).await;
+                continue;
+            }
+        };
+        let datagram = &buffer[..size];
+
+        match (datagram.first(), nack) {
+            (Some(&HELLO), _) => {
+                let mut state = state.lock().unwrap();
+                if state.peer != Some(address) {
+                    info!("Sending frames to {}", address);
+                    state.peer = Some(address);
+                    peer_found.notify_one();
+                }
+            }
+            (Some(&NACK), Some(nack)) => {
+                let retransmissions = match nack::parse(datagram) {
+                    Some((frame_id, indices)) => retransmissions(&state, nack, frame_id, &indices),
+                    None => continue,
+                };
+
+                for datagram in retransmissions {
+                    if let Err(err) = socket.send_to(&datagram, address).await {
+                        debug!("Unable to retransmit a fragment: {}", err);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// Requested fragments of a frame that can still arrive and be decoded before its deadline
+fn retransmissions(
+    state: &Mutex<SenderState>,
+    nack: NackPolicy,
+    frame_id: u64,
+    indices: &[u16],
+) -> Vec<Vec<u8>> {
+    let state = state.lock().unwrap();
+    let frame = match state.history.get(&frame_id) {
+        Some(frame) => frame,
+        None => return Vec::new(),
+    };
+
+    let age = now_timestamp().saturating_sub(frame.capture_timestamp);
+    if age + nack.margin.as_millis() >= nack.deadline.as_millis() {
+        debug!(
+            "Frame {} expired, {} fragments not retransmitted",
+            frame_id,
+            indices.len()
+        );
+        return Vec::new();
+    }
+
+    indices
+        .iter()
+        .filter_map(|&index| frame.fragments.get(index as usize))
+        .map(|fragment| {
+            let mut datagram = fragment.clone();
+            datagram[0] = RETRANSMISSION;
+            datagram
+        })
+        .collect()
+}
+
+// Server side: sends each frame as a burst of fragments to the last receiver that said hello,
+// followed by the FEC repair packets of each group of fragments. With a NACK policy, the fragments
+// requested by the receiver are sent again while their frame can still meet its deadline.
+pub struct UdpFrameSender {
+    socket: Arc<UdpSocket>,
+    state: Arc<Mutex<SenderState>>,
+    peer_found: Arc<Notify>,
+    listener: JoinHandle<()>,
+
+    fec: FecSettings,
+    nack: Option<NackPolicy>,
+}
+
+impl UdpFrameSender {
+    pub async fn bind(
+        port: u16,
+        fec: FecSettings,
+        nack: Option<NackPolicy>,
+    ) -> std::io::Result<Self> {
+        let socket = Arc::new(UdpSocket::bind(("0.0.0.0", port)).await?);
+        info!("Waiting for a UDP receiver on port {}", port);
+
+        let state = Arc::new(Mutex::new(SenderState::default()));
+        let peer_found = Arc::new(Notify::new());
+        let listener = tokio::spawn(listen(
+            socket.clone(),
+            state.clone(),
+            peer_found.clone(),
+            nack,
+        ));
+
+        Ok(Self {
+            socket,
+            state,
+            peer_found,
+            listener,
+            fec,
+            nack,
+        })
+    }
+
+    // Waits for the first receiver
+    async fn peer(&self) -> SocketAddr {
+        loop {
+            let peer = self.state.lock().unwrap().peer;
+            if let Some(peer) = peer {
+                return peer;
+            }
+            self.peer_found.notified().await;
+        }
+    }
+
+    async fn send(&mut self, frame_data: &mut FrameData) -> std::io::Result<()> {
+        let peer = self.peer().await;
+        let frame_id = frame_data.get("frame_id") as u64;
+        let capture_timestamp = frame_data.get("capture_timestamp");
+        let header = FrameHeader {
+            frame_id,
+            capture_timestamp: capture_timestamp as u64,
+        };
+        let message = serialize_frame(frame_data);
+        let chunks = chunks(&message);
+
+        let fragments: Vec<Vec<u8>> = chunks
+            .iter()
+            .enumerate()
+            .map(|(index, chunk)| fragment(header, index, chunks.len(), chunk))
+            .collect();
+
+        for datagram in &fragments {
+            self.socket.send_to(datagram, peer).await?;
+        }
+
+        let repairs = repairs(header, &chunks, &self.fec);
+        let mut overhead = 0;
+        for datagram in &repairs {
+            self.socket.send_to(datagram, peer).await?;
+            overhead += datagram.len();
+        }
+
+        frame_data.set("fec_repair_packets", repairs.len() as u128);
+        frame_data.set("fec_overhead_bytes", overhead as u128);
+
+        if let Some(nack) = self.nack {
+            let oldest = now_timestamp().saturating_sub(nack.deadline.as_millis());
+
+            let mut state = self.state.lock().unwrap();
+            state
+                .history
+                .retain(|_, frame| frame.capture_timestamp > oldest);
+            state.history.insert(
+                frame_id,
+                SentFrame {
+                    capture_timestamp,
+                    fragments,
+                },
+            );
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for UdpFrameSender {
+    fn drop(&mut self) {
+        self.listener.abort();
+    }
+}
+
+#[async_trait]
+impl FrameProcessor for UdpFrameSender {
+    async fn process(&mut self, mut frame_data: FrameData) -> Option<FrameData> {
+        // Known even when sending fails, so that server.csv always has the stats
+        for stat in UDP_SENDER_STATS {
+            frame_data.set(stat, 0);
+        }
+
+        if let Err(err) = self.send(&mut frame_data).await {
+            debug!("Unable to send the frame: {}", err);
+            frame_data.set_drop_reason(Some(DropReason::ConnectionError));
+        }
+
+        Some(frame_data)
+    }
+}
+
+struct RepairGroup {
+    scheme: FecScheme,
+    group_size: usize,
+    repairs: Vec<Option<Vec<u8>>>,
+}
+
+struct PartialFrame {
+    fragments: Vec<Option<Vec<u8>>>,
+    missing: usize,
+    // Keyed by group, known once one of its repair packets arrived
+    repair_groups: BTreeMap<usize, RepairGroup>,
+
+    // Local time in milliseconds past which the fragments are no longer requested, with a NACK
+    // policy. Counted from the capture like on the sender side, on the local clock.
+    deadline: Option<u128>,
+    last_arrival: Instant,
+    highest_index: usize,
+    last_nack: Option<Instant>,
+
+    counters: FrameCounters,
+}
+
+#[derive(Default)]
+struct FrameCounters {
+    recovered: usize,
+    requested: usize,
+    retransmitted: usize,
+    late: usize,
+}
+
+impl PartialFrame {
+    fn new(count: usize, local_capture_timestamp: u128, nack: Option<NackPolicy>) -> Self {
+        Self {
+            fragments: vec![None; count],
+            missing: count,
+            repair_groups: BTreeMap::new(),
+            deadline: nack.map(|nack| local_capture_timestamp + nack.deadline.as_millis()),
+            last_arrival: Instant::now(),
+            highest_index: 0,
+            last_nack: None,
+            counters: FrameCounters::default(),
+        }
+    }
+
+    fn insert(&mut self, index: usize, payload: &[u8], retransmitted: bool) {
+        match self.fragments.get_mut(index) {
+            Some(fragment) if fragment.is_none() => {
+                *fragment = Some(payload.to_vec());
+                self.missing -= 1;
+            }
+            // Duplicate, or beyond the announced count
+            _ => return,
+        }
+
+        self.highest_index = self.highest_index.max(index);
+        if retransmitted {
+            self.counters.retransmitted += 1;
+        }
+        if self.is_expired() {
+            self.counters.late += 1;
+        }
+
+        let groups: Vec<usize> = self.repair_groups.keys().copied().collect();
+        for group in groups {
+            let group_size = self.repair_groups[&group].group_size;
+            if index / group_size == group {
+                self.recover(group);
+            }
+        }
+    }
+
+    fn insert_repair(&mut self, group: usize, index: usize, repair: &[u8], header: RepairGroup) {
+        let repair_group = self.repair_groups.entry(group).or_insert(header);
+        match repair_group.repairs.get_mut(index) {
+            Some(slot) if slot.is_none() => *slot = Some(repair.to_vec()),
+            _ => return,
+        }
+
+        self.recover(group);
+    }
+
+    fn recover(&mut self, group: usize) {
+        let repair_group = &self.repair_groups[&group];
+        let start = group * repair_group.group_size;
+        let end = (start + repair_group.group_size).min(self.fragments.len());
+        if start >= end {
+            return;
+        }
+
+        let recovered = fec::recover(
+            repair_group.scheme,
+            &mut self.fragments[start..end],
+            &repair_group.repairs,
+        );
+        self.missing -= recovered;
+        self.counters.recovered += recovered;
+    }
+
+    fn is_expired(&self) -> bool {
+        self.deadline
+            .map_or(false, |deadline| now_timestamp() >= deadline)
+    }
+
+    // Fragments missing before the highest one received, or after it too once the frame went
+    // quiet for a NACK interval. None when the last request is too recent.
+    fn missing_indices(&self, nack: &NackPolicy) -> Vec<u16> {
+        if self.missing == 0
+            || self.is_expired()
+            || self
+                .last_nack
+                .map_or(false, |last| last.elapsed() < nack.interval)
+        {
+            return Vec::new();
+        }
+
+        let end = if self.last_arrival.elapsed() >= nack.interval {
+            self.fragments.len()
+        } else {
+            self.highest_index
+        };
+
+        self.fragments[..end]
+            .iter()
+            .enumerate()
+            .filter(|(_, fragment)| fragment.is_none())
+            .map(|(index, _)| index as u16)
+            .take(MAX_NACK_INDICES)
+            .collect()
+    }
+
+    fn assemble(self) -> (Vec<u8>, FrameCounters) {
+        (
+            self.fragments.into_iter().flatten().flatten().collect(),
+            self.counters,
+        )
+    }
+}
+
+// Client side: reassembles the fragments, recovering the lost ones from the repair packets when
+// possible and, with a NACK policy, requesting them until the deadline of their frame. The clock
+// estimates translate the capture timestamps of the server to compute the deadlines. Delivers
+// frames in order: an incomplete frame is given up once a later one completes, with a NACK policy
+// only once its deadline expired.
+pub struct UdpFrameReceiver {
+    socket: UdpSocket,
+    nack: Option<NackPolicy>,
+    estimates: Option<watch::Receiver<Option<ClockEstimate>>>,
+
+    frames: BTreeMap<u64, PartialFrame>,
+    last_delivered: Option<u64>,
+    last_datagram: Instant,
+
+    // Recently given up frames, whose fragments are counted as late
+    given_up: BTreeSet<u64>,
+    late_fragments: usize,
+    unrecoverable_frames: usize,
+    expired_frames: usize,
+}
+
+impl UdpFrameReceiver {
+    pub async fn connect(
+        address: &str,
+        nack: Option<NackPolicy>,
+        estimates: Option<watch::Receiver<Option<ClockEstimate>>>,
+    ) -> std::io::Result<Self> {
+        let socket = UdpSocket::bind(("0.0.0.0", 0)).await?;
+        socket.connect(address).await?;
+        socket.send(&[HELLO]).await.ok();
+
+        Ok(Self {
+            socket,
+            nack,
+            estimates,
+            frames: BTreeMap::new(),
+            last_delivered: None,
+            last_datagram: Instant::now(),
+            given_up: BTreeSet::new(),
+            late_fragments: 0,
+            unrecoverable_frames: 0,
+            expired_frames: 0,
+        })
+    }
+
+    // Same translation as ClockOffsetCorrector, the clocks being assumed shared without estimates
+    fn local_timestamp(&self, capture_timestamp: u128) -> u128 {
+        let estimate = self
+            .estimates
+            .as_ref()
+            .and_then(|estimates| *estimates.borrow());
+
+        match estimate {
+            Some(estimate) => estimate.local_timestamp(capture_timestamp, now_timestamp_micros()),
+            None => capture_timestamp,
+        }
+    }
+
+    // Partial frame updated by a datagram, created when needed
+    fn partial_frame(&mut self, header: FrameHeader, count: usize) -> Option<&mut PartialFrame> {
+        if self
+            .last_delivered
+            .map_or(false, |last| header.frame_id <= last)
+        {
+            return None;
+        }
+
+        let nack = self.nack;
+        let local_capture_timestamp = self.local_timestamp(header.capture_timestamp as u128);
+        let frame = self
+            .frames
+            .entry(header.frame_id)
+            .or_insert_with(|| PartialFrame::new(count, local_capture_timestamp, nack));
+        frame.last_arrival = Instant::now();
+
+        Some(frame)
+    }
+
+    fn handle_datagram(&mut self, datagram: &[u8]) {
+        match datagram.first() {
+            Some(&packet_type @ (FRAGMENT | RETRANSMISSION))
+                if datagram.len() >= FRAGMENT_HEADER_SIZE =>
+            {
+                let header = FrameHeader::read(datagram);
+                let index = u16::from_le_bytes(datagram[17..19].try_into().unwrap()) as usize;
+                let count = u16::from_le_bytes(datagram[19..21].try_into().unwrap()) as usize;
+
+                if self.given_up.contains(&header.frame_id) {
+                    self.late_fragments += 1;
+                } else if let Some(frame) = self.partial_frame(header, count) {
+                    frame.insert(
+                        index,
+                        &datagram[FRAGMENT_HEADER_SIZE..],
+                        packet_type == RETRANSMISSION,
+                    );
+                }
+            }
+            Some(&REPAIR) if datagram.len() >= REPAIR_HEADER_SIZE => {
+                let frame_header = FrameHeader::read(datagram);
+                let count = u16::from_le_bytes(datagram[17..19].try_into().unwrap()) as usize;
+                let scheme = match scheme_from_id(datagram[19]) {
+                    Some(scheme) => scheme,
+                    None => return,
+                };
+                let group_size = datagram[20] as usize;
+                let repair_count = datagram[21] as usize;
+                let group = u16::from_le_bytes(datagram[22..24].try_into().unwrap()) as usize;
+                let index = datagram[24] as usize;
+                if group_size == 0 {
+                    return;
+                }
+
+                let header = RepairGroup {
+                    scheme,
+                    group_size,
+                    repairs: vec![None; repair_count],
+                };
+                if let Some(frame) = self.partial_frame(frame_header, count) {
+                    frame.insert_repair(group, index, &datagram[REPAIR_HEADER_SIZE..], header);
+                }
+            }
+            _ => {}
+        }
+
+        while self.frames.len() > MAX_PENDING_FRAMES {
+            let oldest = *self.frames.keys().next().unwrap();
+            self.give_up(oldest);
+        }
+    }
+
+    fn give_up(&mut self, frame_id: u64) {
+        let frame = self.frames.remove(&frame_id).unwrap();
+        debug!("Gave up on incomplete frame {}", frame_id);
+
+        self.unrecoverable_frames += 1;
+        if frame.is_expired() {
+            self.expired_frames += 1;
+        }
+
+        self.last_delivered = Some(frame_id);
+        self.given_up.insert(frame_id);
+        while self.given_up.len() > MAX_PENDING_FRAMES {
+            self.given_up.pop_first();
+        }
+    }
+
+    // Oldest frame if complete, giving up on the incomplete ones before it that cannot complete
+    // anymore
+    fn next_frame(&mut self) -> Option<(Vec<u8>, FrameCounters)> {
+        loop {
+            let (&frame_id, frame) = self.frames.iter().next()?;
+
+            if frame.missing > 0 {
+                let overtaken = self.frames.values().skip(1).any(|frame| frame.missing == 0);
+                // Retransmissions may still complete the frame before its deadline
+                let retransmittable = self.nack.is_some() && !frame.is_expired();
+                if !overtaken || retransmittable {
+                    return None;
+                }
+
+                self.give_up(frame_id);
+                continue;
+            }
+
+            let frame = self.frames.remove(&frame_id).unwrap();
+            self.last_delivered = Some(frame_id);
+
+            let (message, mut counters) = frame.assemble();
+            counters.late += std::mem::take(&mut self.late_fragments);
+            return Some((message, counters));
+        }
+    }
+
+    async fn send_nacks(&mut self) {
+        let nack = match self.nack {
+            Some(nack) => nack,
+            None => return,
+        };
+
+        for (&frame_id, frame) in self.frames.iter_mut() {
+            let indices = frame.missing_indices(&nack);
+            if indices.is_empty() {
+                continue;
+            }
+
+            frame.last_nack = Some(Instant::now());
+            frame.counters.requested += indices.len();
+            let datagram = nack::serialize(NACK, frame_id, &indices);
+            if let Err(err) = self.socket.send(&datagram).await {
+                debug!("Unable to request fragments of frame {}: {}", frame_id, err);
+            }
+        }
+    }
+
+    async fn receive(&mut self) -> (Vec<u8>, FrameCounters) {
+        let mut buffer = [0; MAX_DATAGRAM_SIZE];
+        let wait = match self.nack {
+            Some(_) => NACK_CHECK_INTERVAL,
+            None => HELLO_INTERVAL,
+        };
+
+        loop {
+            if let Some(frame) = self.next_frame() {
+                return frame;
+            }
+            self.send_nacks().await;
+
+            match tokio::time::timeout(wait, self.socket.recv(&mut buffer)).await {
+                Ok(Ok(size)) => {
+                    self.last_datagram = Instant::now();
+                    self.handle_datagram(&buffer[..size]);
+                }
+                // Nothing listening yet on the sender side
+                Ok(Err(err)) => {
+                    debug!("Unable to receive from the sender: {}", err);
+                    tokio::time::sleep(HELLO_INTERVAL).await;
+                    self.socket.send(&[HELLO]).await.ok();
+                }
+                Err(_) => {
+                    if self.last_datagram.elapsed() >= HELLO_INTERVAL {
+                        self.socket.send(&[HELLO]).await.ok();
+                        self.last_datagram = Instant::now();
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl FrameProcessor for UdpFrameReceiver {
+    async fn process(&mut self, mut frame_data: FrameData) -> Option<FrameData> {
+        let (message, counters) = self.receive().await;
+        frame_data.set("fec_recovered_packets", counters.recovered as u128);
+        frame_data.set("nack_requested_packets", counters.requested as u128);
+        frame_data.set("nack_retransmitted_packets", counters.retransmitted as u128);
+        frame_data.set("nack_late_packets", counters.late as u128);
+        frame_data.set(
+            "fec_unrecoverable_frames",
+            std::mem::take(&mut self.unrecoverable_frames) as u128,
+        );
+        frame_data.set(
+            "nack_expired_frames",
+            std::mem::take(&mut self.expired_frames) as u128,
+        );
+
+        if let Err(err) = deserialize_frame(&message, &mut frame_data) {
+            debug!("Malformed frame: {}", err);
+            frame_data.set_drop_reason(Some(DropReason::ConnectionError));
+        }
+
+        Some(frame_data)
+    }
+}